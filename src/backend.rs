@@ -1,16 +1,25 @@
 //! This module abstracts the backend of the compiler, i.e.,
 //! from Koopa IR in memory, to RISCV assembly in ASCII text.
 
-mod riscv;
+pub mod riscv;
 mod riscv_builder;
 mod riscv_text_builder;
 
+use crate::midend::Annotations;
 use koopa::ir::entities::Program;
 use riscv_builder::RiscvBuilder;
 use riscv_text_builder::RiscvTextBuilder;
 
-/// Converts a Koopa program to RISCV-32IM assembly.
-pub fn riscv_text_from(prog: &Program) -> String {
-    let prog = RiscvBuilder::build(prog);
-    RiscvTextBuilder::build(&prog)
+/// Converts a Koopa program to RISCV-32IM assembly,
+/// with the given annotations, e.g. read-only globals and source locations.
+/// With debug information, locations are emitted as DWARF line tables,
+/// otherwise as comments.
+pub fn riscv_text_from(prog: &Program, notes: &Annotations, debug: bool) -> String {
+    let prog = RiscvBuilder::build(prog, notes);
+    RiscvTextBuilder::build(&prog, debug)
+}
+
+/// Converts a Koopa program to an in-memory RISCV program, for simulation.
+pub fn riscv_from(prog: &Program, notes: &Annotations) -> riscv::Program {
+    RiscvBuilder::build(prog, notes)
 }
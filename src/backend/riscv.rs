@@ -6,6 +6,7 @@ pub type Reg = &'static str;
 
 pub struct Program {
     pub global_defs: LinkedList<GlobalDef>,
+    pub rodata_defs: LinkedList<GlobalDef>,
     pub funcs: LinkedList<Func>,
 }
 
@@ -17,11 +18,13 @@ pub enum MemFill {
 pub struct GlobalDef {
     pub name: String,
     pub init: LinkedList<MemFill>,
+    pub is_local: bool, // Not exported, e.g. string literals.
 }
 
 pub struct Func {
     pub name: String,
     pub blocks: LinkedList<Block>,
+    pub is_local: bool, // Not exported, e.g. helpers defined in every object file.
 }
 
 pub struct Block {
@@ -60,13 +63,27 @@ pub enum Inst {
     Li { rd: Reg, imm: i32 },
     La { rd: Reg, label: String },
     Mv { rd: Reg, rs: Reg },
+    // Pseudo instructions for debug information, see `RiscvTextBuilder`.
+    Loc { file: String, line: u32 }, // Source location of what follows.
+    CfiDefCfaOffset { offset: i32 },
+    CfiOffset { reg: Reg, offset: i32 },
+    CfiRestore { reg: Reg },
+    CfiRememberState,
+    CfiRestoreState,
 }
 
 impl Program {
     pub const fn new() -> Self {
         Self {
             global_defs: LinkedList::new(),
+            rodata_defs: LinkedList::new(),
             funcs: LinkedList::new(),
         }
     }
 }
+
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -7,6 +7,7 @@ mod func_meta;
 mod reg_manager;
 
 use super::riscv::{self, Reg};
+use crate::midend::{Annotations, LocMark};
 use func_meta::FuncMeta;
 use koopa::ir::entities::*;
 use reg_manager::RegManager;
@@ -20,15 +21,18 @@ pub struct RiscvBuilder<'a> {
     func_meta: FuncMeta,
     koopa_prog: Option<&'a Program>,
     koopa_func: Option<&'a FunctionData>,
+    notes: Option<&'a Annotations>,
 }
 
 impl<'a:'r, 'r> RiscvBuilder<'a> {
     /// RISCV registers used for passing arguments.
     const ARG_REGS: [Reg; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
 
-    /// Builds RISCV program from given Koopa IR program.
-    pub fn build(prog: &Program) -> riscv::Program {
+    /// Builds RISCV program from given Koopa IR program,
+    /// with annotations, e.g. which globals are read-only.
+    pub fn build(prog: &Program, notes: &Annotations) -> riscv::Program {
         let mut builder = Self::new();
+        builder.notes = Some(notes);
         builder.build_prog(prog);
         builder.prog
     }
@@ -41,6 +45,7 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
             func_meta: FuncMeta::new(),
             koopa_prog: None,
             koopa_func: None,
+            notes: None,
         }
     }
 
@@ -49,6 +54,12 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
         self.koopa_prog.unwrap()
     }
 
+    /// Returns the source locations in the given block of the current function.
+    fn block_locs(&self, block: BasicBlock) -> &'a [LocMark] {
+        let name = self.koopa_func().dfg().bb(block).name().as_ref().unwrap();
+        self.notes.unwrap().locs.get(self.koopa_func().name(), name)
+    }
+
     /// Returns internal `koopa_func` field.
     fn koopa_func(&self) -> &'a FunctionData {
         self.koopa_func.unwrap()
@@ -68,6 +79,7 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
         self.prog.funcs.push_back(riscv::Func {
             name: self.func_name(func).to_string(),
             blocks: LinkedList::new(),
+            is_local: false,
         });
     }
 
@@ -121,7 +133,7 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
 
     /// Returns whether the given function is a declaration.
     fn is_func_decl(&self, func: Function) -> bool {
-        self.koopa_prog().func(func).layout().entry_bb() == None
+        self.koopa_prog().func(func).layout().entry_bb().is_none()
     }
 
     /// Returns the data of the given function.
@@ -141,7 +153,7 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
     /// Returns the data of the given global value.
     /// DO NOT CALL THIS FUNCTION FROM OUTSIDE. WIELD THINGS HAPPEN.
     /// I DON'T UNDERSTAND WHY.
-    fn global_value_data(&self, value: Value) -> Ref<ValueData> {
+    fn global_value_data(&self, value: Value) -> Ref<'_, ValueData> {
         self.koopa_prog().borrow_value(value)
     }
 
@@ -153,6 +165,12 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
         //? self.global_value_data(var).name().as_ref().unwrap().as_str()
     }
 
+    /// Returns whether the given global variable is local to the unit,
+    /// if it is marked read-only, e.g. a constant array, see `Annotations`.
+    fn read_only_linkage(&self, var: Value) -> Option<bool> {
+        self.notes.unwrap().rodata.get(&self.global_var_name(var)).copied()
+    }
+
     //? Failed to compile this.
     //? fn global_value_kind(&'s self, var: Value) -> &'r ValueKind {
     //?     self.global_value_data(var).kind()
@@ -184,7 +202,7 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
     /// Returns whether the given value is a local variable
     /// of the current function.
     fn is_local_var(&self, value: Value) -> bool {
-        self.func_meta.offset(value) != None
+        self.func_meta.offset(value).is_some()
     }
 
     /// Returns whether the given local value is used as a function argument.
@@ -215,6 +233,16 @@ impl<'a:'r, 'r> RiscvBuilder<'a> {
         self.prog.global_defs.push_back(riscv::GlobalDef {
             name: self.global_var_name(var).to_string(),
             init,
+            is_local: false,
+        });
+    }
+
+    /// Appends a new read-only data definition to the program.
+    fn push_rodata_def(&mut self, var: Value, init: LinkedList<riscv::MemFill>, is_local: bool) {
+        self.prog.rodata_defs.push_back(riscv::GlobalDef {
+            name: self.global_var_name(var),
+            init,
+            is_local,
         });
     }
 
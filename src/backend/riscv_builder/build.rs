@@ -1,6 +1,6 @@
 //! Build RISCV program from Koopa IR.
 use super::RiscvBuilder;
-use crate::backend::riscv::Reg;
+use crate::backend::riscv::{self, Inst::*, Reg};
 use koopa::ir::entities::*;
 use koopa::ir::layout::*;
 
@@ -17,6 +17,12 @@ impl<'a> RiscvBuilder<'a> {
             return;
         }
 
+        let name = self.func_name(func);
+        if self.notes.unwrap().zero_func.as_deref() == Some(name) {
+            self.build_zero_func(name.to_string());
+            return;
+        }
+
         self.enter_func(func);
 
         let func = self.func_data(func);
@@ -44,12 +50,20 @@ impl<'a> RiscvBuilder<'a> {
         if is_entry {
             let size = self.frame_size() as i32;
             self.build_addi("sp", "sp", -size);
+            self.push_inst(CfiDefCfaOffset { offset: size });
             if !self.is_leaf_func() {
                 self.build_sw("ra", size - 4, "sp");
+                self.push_inst(CfiOffset { reg: "ra", offset: -4 });
             }
         }
 
-        node.insts().keys().for_each(|&inst| { self.build_inst(inst, None); });
+        let mut locs = self.block_locs(bb).iter().peekable();
+        node.insts().keys().enumerate().for_each(|(idx, &inst)| {
+            if let Some((_, file, line)) = locs.next_if(|(at, ..)| *at == idx) {
+                self.push_inst(Loc { file: file.clone(), line: *line });
+            }
+            self.build_inst(inst, None);
+        });
     }
 
     pub fn build_inst(&mut self, value: Value, dst: Option<Reg>) -> Option<Reg> {
@@ -79,6 +93,47 @@ impl<'a> RiscvBuilder<'a> {
             self.free_reg(value, reg);
         }
 
+        // Values used in other blocks live in the stack in between.
+        if let Some(imm) = self.func_meta.spill(value) {
+            let reg = res.unwrap();
+            self.build_sw(reg, imm as i32, "sp");
+            self.free_reg(value, reg);
+        }
+
         res
     }
+
+    /// Builds the helper of the given name defined by the midend to zero
+    /// arrays. Its Koopa body is replaced by a tight loop:
+    /// `a0` points to the first word, `a1` is the number of words.
+    /// Labels are local, and never clash as no SysY identifier has a `.`.
+    fn build_zero_func(&mut self, name: String) {
+        let (body, cond) = (format!(".L{name}_body"), format!(".L{name}_cond"));
+        let block = |name: &str, insts: Vec<riscv::Inst>| riscv::Block {
+            name: name.to_string(),
+            insts: insts.into_iter().collect(),
+        };
+        let blocks = [
+            block(&name, vec![
+                Add { rd: "a1", rs1: "a1", rs2: "a1" },
+                Add { rd: "a1", rs1: "a1", rs2: "a1" },
+                Add { rd: "a1", rs1: "a0", rs2: "a1" }, // End of the array.
+                J { label: cond.clone() },
+            ]),
+            block(&body, vec![
+                Sw { rs: "x0", imm12: 0, rd: "a0" },
+                Addi { rd: "a0", rs: "a0", imm12: 4 },
+            ]),
+            block(&cond, vec![
+                Sub { rd: "t0", rs1: "a1", rs2: "a0" },
+                Bnez { rs: "t0", label: body.clone() },
+                Ret,
+            ]),
+        ];
+        self.prog.funcs.push_back(riscv::Func {
+            name,
+            blocks: blocks.into_iter().collect(),
+            is_local: true,
+        });
+    }
 }
@@ -6,7 +6,7 @@ use koopa::ir::dfg::DataFlowGraph;
 use koopa::ir::entities::*;
 
 fn is_imm12(imm: i32) -> bool {
-    imm >= -2048 && imm <= 2047
+    (-2048..=2047).contains(&imm)
 }
 
 #[allow(non_upper_case_globals)]
@@ -58,19 +58,20 @@ impl RiscvBuilder<'_> {
     // TODO: coming up with a more suitable name.
     fn move_inst_to(&mut self, inst: Value, dst: Option<Reg>) -> Reg {
         let rs = self.reg_mgr.reg(inst);
-        if dst == None || dst == Some(rs) { // Optimization.
-            return rs;
-        } else {
-            let rd = dst.unwrap();
-            let need_swap = self.reg_mgr.move_to(rs, rd);
-            if need_swap {
-                self.push_inst(Mv { rd: t0, rs: rd });
-                self.push_inst(Mv { rd, rs });
-                self.push_inst(Mv { rd: rs, rs: t0 });
-            } else {
-                self.push_inst(Mv { rd, rs });
+        match dst {
+            None => rs,
+            Some(rd) if rd == rs => rs, // Optimization.
+            Some(rd) => {
+                let need_swap = self.reg_mgr.move_to(rs, rd);
+                if need_swap {
+                    self.push_inst(Mv { rd: t0, rs: rd });
+                    self.push_inst(Mv { rd, rs });
+                    self.push_inst(Mv { rd: rs, rs: t0 });
+                } else {
+                    self.push_inst(Mv { rd, rs });
+                }
+                rd
             }
-            return rd;
         }
     }
 
@@ -83,6 +84,12 @@ impl RiscvBuilder<'_> {
                 assert_eq!(dst, None);
                 self.build_func_arg_ref(value).unwrap()
             }
+            // Reloaded from the stack, see `build_inst`.
+            _ if self.func_meta.spill(value).is_some() => {
+                let rd = self.alloc_reg(value, None);
+                self.build_lw(rd, self.func_meta.spill(value).unwrap() as i32, "sp");
+                self.move_inst_to(value, dst)
+            }
             // Expect the above two cases, `value` is guaranteed
             // to be built already when calling.
             _ => self.move_inst_to(value, dst),
@@ -102,8 +109,8 @@ impl RiscvBuilder<'_> {
         self.build_addi("sp", "sp", -size);
         let arg_num = off / 4;
         (0..arg_num).for_each(|i| {
-            self.build_lw(t0, i as i32 * 4 + size, "sp");
-            self.build_sw(t0, i as i32 * 4, "sp");
+            self.build_lw(t0, i * 4 + size, "sp");
+            self.build_sw(t0, i * 4, "sp");
         });
         regs.iter().enumerate().for_each(|(i, reg)| {
             self.build_sw(reg, (i as i32) * 4 + off, "sp");
@@ -150,9 +157,9 @@ impl RiscvBuilder<'_> {
         let used_by = value_data.used_by();
         if used_by.len() > 1 {
             assert!(matches!(value_data.kind(), Alloc(..)));
-            return None;
-        } else if used_by.len() == 0 {
-            return None;
+            None
+        } else if used_by.is_empty() {
+            None
         } else {
             let &user = used_by.iter().next().unwrap();
             let user_data = dfg.value(user);
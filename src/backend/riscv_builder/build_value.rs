@@ -47,7 +47,7 @@ impl RiscvBuilder<'_> {
     pub fn build_integer(&mut self, value: Value, dst: OptReg) -> OptReg {
         let int = to_arm!(self, value, Integer);
         let imm = int.value();
-        if dst == None && imm == 0 { // Optimization.
+        if dst.is_none() && imm == 0 { // Optimization.
             return Some("x0");
         }
         let rd = self.alloc_reg(value, dst);
@@ -66,6 +66,7 @@ impl RiscvBuilder<'_> {
 
             match kind {
                 Integer(int) => res.push_back(Word(int.value())),
+                ZeroInit(..) => res.push_back(Zero(data.ty().size())),
                 Aggregate(agg) => {
                     let mut list = self.build_aggregate(agg);
                     res.append(&mut list);
@@ -120,6 +121,12 @@ impl RiscvBuilder<'_> {
             panic!("Unexpected value kind");
         };
 
+        // Extern variables are marked by `undef` initializer,
+        // their storage is allocated in other object files.
+        if let Undef(..) = self.koopa_prog().borrow_value(alloc.init()).kind() {
+            return;
+        }
+
         // HACK: Have to nest `data` and `kind` in a block, or else won't compile.
         let init = {
             let data = self.koopa_prog().borrow_value(alloc.init());
@@ -148,6 +155,22 @@ impl RiscvBuilder<'_> {
             res
         };
 
+        // Merge zeros, so that sparse initializers stay small.
+        let init = init.into_iter().fold(LinkedList::new(), |mut res, fill| {
+            let fill = match fill {
+                Word(0) => Zero(4),
+                fill => fill,
+            };
+            match (res.back_mut(), fill) {
+                (Some(Zero(size)), Zero(more)) => *size += more,
+                (_, fill) => res.push_back(fill),
+            }
+            res
+        });
+        if let Some(is_local) = self.read_only_linkage(value) {
+            self.push_rodata_def(value, init, is_local);
+            return;
+        }
         self.push_global_def(value, init);
     }
 
@@ -155,6 +178,7 @@ impl RiscvBuilder<'_> {
         let store = to_arm!(self, value, Store);
         let src = store.value();
         let dst = store.dest();
+
         let rs = self.move_inst(src, None);
 
         if dst.is_global() {
@@ -389,14 +413,19 @@ impl RiscvBuilder<'_> {
         if let Some(value) = ret.value() {
             self.move_inst(value, Some("a0"));
         }
+        // The epilogue may be followed by other blocks, still in the frame.
+        self.push_inst(Inst::CfiRememberState);
         if !self.is_leaf_func() {
             self.build_lw("ra", self.frame_size() as i32 - 4, "sp");
+            self.push_inst(Inst::CfiRestore { reg: "ra" });
         }
         self.build_addi("sp", "sp", self.frame_size() as i32);
+        self.push_inst(Inst::CfiDefCfaOffset { offset: 0 });
         if let Some(value) = ret.value() {
             self.free_reg(value, "a0");
         }
         self.push_inst(Inst::Ret);
+        self.push_inst(Inst::CfiRestoreState);
         None
     }
 }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 pub struct FuncMeta {
     frame_size: usize,
     offset: HashMap<Value, usize>,
+    spill: HashMap<Value, usize>,
     is_leaf: bool,
     arg_size: usize,
 }
@@ -17,6 +18,7 @@ impl FuncMeta {
         Self {
             frame_size: 0,
             offset: HashMap::new(),
+            spill: HashMap::new(),
             is_leaf: false,
             arg_size: 0,
         }
@@ -32,6 +34,12 @@ impl FuncMeta {
         self.offset.get(&value).copied()
     }
 
+    /// Returns the offset of the slot of the given value, if it is used
+    /// outside its own block, in terms of bytes.
+    pub fn spill(&self, value: Value) -> Option<usize> {
+        self.spill.get(&value).copied()
+    }
+
     /// Returns whether the function is a leaf node, i.e.,
     /// no function call from within.
     pub fn is_leaf(&self) -> bool {
@@ -49,7 +57,7 @@ impl FuncMeta {
         func.layout()
             .bbs()
             .nodes()
-            .flat_map(|block| block.insts().keys().map(|&handle| handle))
+            .flat_map(|block| block.insts().keys().copied())
             .collect()
     }
 }
@@ -93,6 +101,20 @@ impl From<&FunctionData> for FuncMeta {
             }
         });
 
+        // Reserve frame for values used outside their blocks, since blocks
+        // are built in layout order, and registers don't survive them.
+        values.iter().for_each(|&handle| {
+            if res.offset.contains_key(&handle) {
+                return;
+            }
+            let bb = func.layout().parent_bb(handle);
+            let used_by = func.dfg().value(handle).used_by();
+            if used_by.iter().any(|&user| func.layout().parent_bb(user) != bb) {
+                res.spill.insert(handle, res.frame_size);
+                res.frame_size += 4;
+            }
+        });
+
         // Determine whether the function is a leaf node.
         res.is_leaf = !values
             .iter()
@@ -107,7 +129,7 @@ impl From<&FunctionData> for FuncMeta {
         let arg_num = values
             .iter()
             .map(|&handle| match kind(handle) {
-                Call(call) => call.args().len() as usize,
+                Call(call) => call.args().len(),
                 _ => 0,
             })
             .max()
@@ -115,13 +137,13 @@ impl From<&FunctionData> for FuncMeta {
         if arg_num > MAX_ARG_NUM_IN_REG {
             res.arg_size = 4 * (arg_num - MAX_ARG_NUM_IN_REG);
             res.frame_size += res.arg_size;
-            res.offset.values_mut().for_each(|offset| {
+            res.offset.values_mut().chain(res.spill.values_mut()).for_each(|offset| {
                 *offset += res.arg_size;
             });
         }
 
         // Align frame size to 16B.
-        res.frame_size = (res.frame_size + 15) / 16 * 16;
+        res.frame_size = res.frame_size.div_ceil(16) * 16;
 
         res
     }
@@ -77,7 +77,7 @@ impl RegManager {
     /// Allocates the given register for the given value and returns it.
     /// When argument `reg` is `None`, it means any register is ok.
     pub fn alloc(&mut self, val: Value, reg: Option<Reg>) -> Reg {
-        assert!(self.val2reg.get(&val) == None, "Value already allocated");
+        assert!(!self.val2reg.contains_key(&val), "Value already allocated");
         if let Some(reg) = reg {
             if reg == "x0" { // x0 is always free.
                 self.val2reg.insert(val, reg);
@@ -87,12 +87,12 @@ impl RegManager {
             assert_eq!(self.reg2val[idx], None, "Register already allocated");
             self.reg2val[idx] = Some(val);
             self.val2reg.insert(val, reg);
-            return reg;
+            reg
         } else {
             let idx = self.find_free_reg();
             self.reg2val[idx] = Some(val);
             self.val2reg.insert(val, Self::REG_NAME[idx]);
-            return Self::REG_NAME[idx];
+            Self::REG_NAME[idx]
         }
     }
 
@@ -164,7 +164,7 @@ impl RegManager {
     /// Returns whether the given register is free.
     pub fn is_free(&mut self, reg: Reg) -> bool {
         let idx = Self::reg2idx(reg);
-        self.reg2val[idx] == None
+        self.reg2val[idx].is_none()
     }
 
     /// Resets the register manager. All mappings are cleared.
@@ -4,25 +4,31 @@ mod build_from;
 
 use super::riscv::Program;
 use build_from::BuildFrom;
+use std::collections::HashMap;
 
 pub struct RiscvTextBuilder {
     text: String,
+    debug: bool, // Whether to emit debug information.
+    files: HashMap<String, usize>, // Source files to their indices in `.file`.
 }
 
 impl RiscvTextBuilder {
     const TAB: &str = "    ";
 
-    /// Builds RISCV text from given RISCV program.
-    pub fn build(prog: &Program) -> String {
-        let mut builder = Self::new();
+    /// Builds RISCV text from given RISCV program,
+    /// with or without debug information.
+    pub fn build(prog: &Program, debug: bool) -> String {
+        let mut builder = Self::new(debug);
         builder.build_from(prog);
         builder.text
     }
 
     /// Creates a new RISCV text builder.
-    fn new() -> Self {
+    fn new(debug: bool) -> Self {
         Self {
             text: String::new(),
+            debug,
+            files: HashMap::new(),
         }
     }
 }
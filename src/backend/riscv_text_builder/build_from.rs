@@ -1,6 +1,8 @@
 //! This module defines and implements the `BuildFrom` trait for `RiscvTextBuilder`.
 //! RISCV text generating is done by simply scanning the in-memory
 //! RISCV program and appending the corresponding text.
+//! Debug information includes `.file`/`.loc` for DWARF line tables,
+//! `.type`/`.size` of functions and CFI directives for unwinding.
 
 use super::super::riscv::*;
 use super::RiscvTextBuilder;
@@ -21,6 +23,10 @@ impl BuildFrom<Program> for RiscvTextBuilder {
     fn build_from(&mut self, prog: &Program) {
         push_text!(self, "{TAB}.data\n");
         prog.global_defs.iter().for_each(|def| self.build_from(def));
+        if !prog.rodata_defs.is_empty() {
+            push_text!(self, "{TAB}.section .rodata\n");
+            prog.rodata_defs.iter().for_each(|def| self.build_from(def));
+        }
         push_text!(self, "{TAB}.text\n");
         prog.funcs.iter().for_each(|func| self.build_from(func));
     }
@@ -28,33 +34,49 @@ impl BuildFrom<Program> for RiscvTextBuilder {
 
 impl BuildFrom<GlobalDef> for RiscvTextBuilder {
     fn build_from(&mut self, global_def: &GlobalDef) {
-        use MemFill::*;
         let name = &global_def.name;
         let init = &global_def.init;
-        push_text!(self, "{TAB}.globl {name}\n");
+        if !global_def.is_local {
+            push_text!(self, "{TAB}.globl {name}\n");
+        }
         push_text!(self, "{name}:\n");
-        init.iter().for_each(|fill| {
-            match fill {
-                Word(value) => push_text!(self, "{TAB}.word {value}\n"),
-                Zero(size) => push_text!(self, "{TAB}.zero {size}\n"),
-            }
-        });
+        init.iter().for_each(|fill| self.build_from(fill));
         push_text!(self, "\n");
     }
 }
 
-impl BuildFrom<Func> for RiscvTextBuilder {
-    fn build_from(&mut self, func: &Func) {
-        push_text!(self, "{TAB}.globl {}\n", func.name);
-        func.blocks.iter().for_each(|block| self.build_from(block));
-        push_text!(self, "\n");
+impl BuildFrom<MemFill> for RiscvTextBuilder {
+    fn build_from(&mut self, fill: &MemFill) {
+        use MemFill::*;
+        match fill {
+            Word(value) => push_text!(self, "{TAB}.word {value}\n"),
+            Zero(size) => push_text!(self, "{TAB}.zero {size}\n"),
+        }
     }
 }
 
-impl BuildFrom<Block> for RiscvTextBuilder {
-    fn build_from(&mut self, block: &Block) {
-        push_text!(self, "{}:\n", block.name);
-        block.insts.iter().for_each(|inst| self.build_from(inst));
+impl BuildFrom<Func> for RiscvTextBuilder {
+    fn build_from(&mut self, func: &Func) {
+        let name = &func.name;
+        if !func.is_local {
+            push_text!(self, "{TAB}.globl {name}\n");
+        }
+        if self.debug {
+            push_text!(self, "{TAB}.type {name}, @function\n");
+        }
+        // The first block is labeled with the function name.
+        func.blocks.iter().enumerate().for_each(|(i, block)| {
+            push_text!(self, "{}:\n", block.name);
+            if i == 0 && self.debug {
+                push_text!(self, "{TAB}.cfi_startproc\n");
+            }
+            block.insts.iter().for_each(|inst| self.build_from(inst));
+        });
+        if self.debug {
+            push_text!(self, "{TAB}.cfi_endproc\n");
+            push_text!(self, "{TAB}.size {name}, .-{name}\n");
+        }
+        push_text!(self, "\n");
     }
 }
 
@@ -66,7 +88,7 @@ impl BuildFrom<Inst> for RiscvTextBuilder {
             Bnez { rs, label } => format!("bnez {rs}, {label}"),
             J { label } => format!("j {label}"),
             Call { label } => format!("call {label}"),
-            Ret => format!("ret"),
+            Ret => "ret".to_string(),
             Lw { rd, imm12, rs } => format!("lw {rd}, {imm12}({rs})"),
             Sw { rs, imm12, rd } => format!("sw {rs}, {imm12}({rd})"),
             Add { rd, rs1, rs2 } => format!("add {rd}, {rs1}, {rs2}"),
@@ -91,6 +113,35 @@ impl BuildFrom<Inst> for RiscvTextBuilder {
             Li { rd, imm } => format!("li {rd}, {imm}"),
             La { rd, label } => format!("la {rd}, {label}"),
             Mv { rd, rs } => format!("mv {rd}, {rs}"),
+            _ => return self.build_debug(inst),
+        };
+        push_text!(self, "{TAB}{text}\n");
+    }
+}
+
+impl RiscvTextBuilder {
+    /// Builds the given pseudo instruction for debug information.
+    /// Without debug information, only locations are emitted, as comments.
+    fn build_debug(&mut self, inst: &Inst) {
+        use Inst::*;
+        let text = match inst {
+            Loc { file, line } if !self.debug => format!("# {file}:{line}"),
+            _ if !self.debug => return,
+            Loc { file, line } => {
+                let cnt = self.files.len();
+                let idx = *self.files.entry(file.clone()).or_insert(cnt + 1);
+                // Declared on first use, which is allowed anywhere.
+                if idx > cnt {
+                    push_text!(self, "{TAB}.file {idx} \"{file}\"\n");
+                }
+                format!(".loc {idx} {line} 0")
+            }
+            CfiDefCfaOffset { offset } => format!(".cfi_def_cfa_offset {offset}"),
+            CfiOffset { reg, offset } => format!(".cfi_offset {reg}, {offset}"),
+            CfiRestore { reg } => format!(".cfi_restore {reg}"),
+            CfiRememberState => ".cfi_remember_state".to_string(),
+            CfiRestoreState => ".cfi_restore_state".to_string(),
+            _ => panic!("Unexpected arm"),
         };
         push_text!(self, "{TAB}{text}\n");
     }
@@ -0,0 +1,13 @@
+//! Language server of SysY, see `sysy_compiler::lsp`.
+
+use std::io;
+use std::process;
+use sysy_compiler::lsp;
+
+fn main() {
+    // Usage: sysy-lsp
+    // Messages are read from standard input and written to standard output.
+    // The exit code is 1 if the client exits without shutting down first.
+    let is_shutdown = lsp::serve(io::stdin().lock(), io::stdout().lock());
+    process::exit(!is_shutdown as i32);
+}
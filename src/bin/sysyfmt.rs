@@ -0,0 +1,62 @@
+//! Formatter of SysY programs, reprinting them in the canonical style
+//! of the compiler, comments and directives kept.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+use sysy_compiler::frontend;
+
+fn main() {
+    // Usage: sysyfmt [--check] [--indent=<n>] [<file>...]
+    // Files are formatted in place, or standard input to standard output
+    // if none is given. With `--check`, nothing is written, but files that
+    // are not formatted are listed, and the exit code is 1 if there are any.
+    let mut check = false;
+    let mut indent = 4;
+    let files = env::args()
+        .skip(1)
+        .filter(|arg| {
+            if let Some(n) = arg.strip_prefix("--indent=") {
+                indent = n.parse().expect("Invalid indentation");
+                return false;
+            }
+            check |= arg == "--check";
+            arg != "--check"
+        })
+        .collect::<Vec<_>>();
+    if let Some(arg) = files.iter().find(|arg| arg.starts_with("--")) {
+        panic!("Unknown option: {arg}");
+    }
+
+    if files.is_empty() {
+        let mut prog = String::new();
+        io::stdin().read_to_string(&mut prog).unwrap();
+        let formatted = frontend::format_sysy_text(&prog, "<stdin>", indent);
+        if check {
+            process::exit((formatted != prog) as i32);
+        }
+        print!("{formatted}");
+        return;
+    }
+
+    let unformatted = files
+        .iter()
+        .filter(|file| {
+            let prog = fs::read_to_string(file).unwrap();
+            let formatted = frontend::format_sysy_text(&prog, file, indent);
+            if formatted == prog {
+                return false;
+            }
+            if check {
+                println!("{file}");
+            } else {
+                fs::write(file, formatted).unwrap();
+            }
+            true
+        })
+        .count();
+    if check && unformatted > 0 {
+        process::exit(1);
+    }
+}
@@ -4,26 +4,200 @@
 //! because sematic analysis is performed automatically.
 
 pub mod ast;
+mod comment;
+mod lexer;
+mod lint;
+mod literal;
+mod preprocessor;
 mod sem_analyzer;
+mod sysy_text_builder;
 
 pub use ast::Program;
+use ast::Exprs;
+use lalrpop_util::ParseError;
+use lexer::{Lexer, Tok};
+pub use lint::{Lint, Lints, Warning};
+pub use sysy_text_builder::Printed;
 use parser::ProgramParser;
+use preprocessor::{LineMap, Preprocessor};
+pub use sem_analyzer::{Index, SymbolInfo};
 use sem_analyzer::SemAnalyzer;
+use sysy_text_builder::SysyTextBuilder;
+use std::cell::RefCell;
 
-lalrpop_util::lalrpop_mod!(parser, "/frontend/sysy.rs");
+lalrpop_util::lalrpop_mod!(
+    #[allow(clippy::all)]
+    parser,
+    "/frontend/sysy.rs"
+);
 
 impl Program {
     /// Creates an AST from SysY program in ASCII text.
-    pub fn from_sysy_text(prog: &str) -> Self {
-        ProgramParser::new()
-            .parse(&prog)
-            .expect("Parse error")
-            .analyze_sem()
+    /// The path of the program is used to resolve `#include`
+    /// and to report errors. Warnings are reported at the given levels.
+    pub fn from_sysy_text(prog: &str, path: &str, lints: &Lints) -> Self {
+        let (prog, warnings) = Self::parse(prog, path);
+        let (prog, mut sem_warnings) = prog.analyze_sem();
+        let mut warnings = warnings;
+        warnings.append(&mut sem_warnings);
+        lints.report(warnings, &prog.2);
+        prog
     }
 
-    /// Analyzes the semantics of input AST and returns the transformed AST.
-    fn analyze_sem(mut self) -> Self {
-        SemAnalyzer::run_on(&mut self);
-        self
+    /// Creates an AST from SysY program in ASCII text, without semantic
+    /// analysis, so that it can be displayed as text again.
+    /// Warnings found in parsing are dropped.
+    pub fn parse_sysy_text(prog: &str, path: &str) -> Self {
+        Self::parse(prog, path).0
     }
+
+    /// Parses SysY program in ASCII text, returns the AST along with
+    /// warnings found in parsing.
+    fn parse(prog: &str, path: &str) -> (Self, Vec<Warning>) {
+        let (prog, line_map) = Preprocessor::run(prog, path);
+        Self::parse_preprocessed(&prog, line_map)
+    }
+
+    /// Creates an AST from SysY program in ASCII text like `parse_sysy_text`,
+    /// but recovers from syntax errors, see `parse_recovering`.
+    pub fn parse_sysy_text_recovering(
+        prog: &str,
+        path: &str,
+    ) -> (Option<Self>, Vec<Warning>, Vec<String>) {
+        let (prog, line_map) = Preprocessor::run(prog, path);
+        Self::parse_recovering(&prog, line_map)
+    }
+
+    /// Parses preprocessed text, whose lines are mapped by the given line map.
+    /// All syntax errors are reported at once, one per line.
+    fn parse_preprocessed(prog: &str, line_map: LineMap) -> (Self, Vec<Warning>) {
+        let (prog, warnings, errors) = Self::parse_recovering(prog, line_map);
+        if !errors.is_empty() {
+            panic!("{}", errors.join("\n"));
+        }
+        (prog.unwrap(), warnings)
+    }
+
+    /// Parses preprocessed text, recovering from syntax errors.
+    /// Returns the AST, in which skipped code becomes error nodes,
+    /// unless the parser fails to recover, e.g. at the end of input.
+    /// Warnings are returned along with errors, located as `file:line: parse error: msg`.
+    fn parse_recovering(
+        prog: &str,
+        line_map: LineMap,
+    ) -> (Option<Self>, Vec<Warning>, Vec<String>) {
+        let exprs = RefCell::new(Exprs::default());
+        let warnings = RefCell::new(vec![]);
+        let errors = RefCell::new(vec![]);
+        let lexer = Lexer::new(prog, &errors, &warnings);
+        let result = ProgramParser::new().parse(&exprs, &warnings, &errors, lexer);
+        let mut errors = errors.into_inner();
+        let prog = match result {
+            Ok(mut prog) => {
+                prog.2 = line_map.clone();
+                Some(prog)
+            }
+            Err(err) => {
+                errors.push(SyntaxError::new(err));
+                None
+            }
+        };
+        // Lexical errors are found ahead of parse errors.
+        errors.sort_by_key(|err| err.offset);
+        let errors = errors
+            .into_iter()
+            .map(|err| format!("{}: parse error: {}", line_map.locate(err.offset), err.msg))
+            .collect();
+        (prog, warnings.into_inner(), errors)
+    }
+
+    /// Creates ASTs from several translation units, each given as
+    /// a (program text, path) pair, and checks their consistency.
+    pub fn from_sysy_units(units: &[(String, String)], lints: &Lints) -> Vec<Self> {
+        let progs = units
+            .iter()
+            .map(|(prog, path)| Self::from_sysy_text(prog, path, lints))
+            .collect::<Vec<_>>();
+        let pairs = progs
+            .iter()
+            .zip(units)
+            .map(|(prog, (_, path))| (prog, path.as_str()))
+            .collect::<Vec<_>>();
+        SemAnalyzer::check_units(&pairs);
+        progs
+    }
+
+    /// Analyzes the semantics of input AST and returns the transformed AST,
+    /// along with warnings found during analysis.
+    fn analyze_sem(mut self) -> (Self, Vec<Warning>) {
+        let warnings = SemAnalyzer::run_on(&mut self);
+        (self, warnings)
+    }
+
+    /// Analyzes the semantics like `analyze_sem`, also returns the index
+    /// of symbols, for editor integration. Error nodes of partial ASTs are skipped.
+    /// Semantic errors still panic.
+    pub fn analyze_sem_with_index(mut self) -> (Self, Vec<Warning>, Index) {
+        let (warnings, index) = SemAnalyzer::run_with_index(&mut self);
+        (self, warnings, index)
+    }
+}
+
+/// A syntax error at the given offset in preprocessed text.
+pub struct SyntaxError {
+    pub offset: usize,
+    pub msg: String,
+}
+
+impl SyntaxError {
+    /// Creates a syntax error from what the parser reports.
+    /// Errors from the lexer are as they are.
+    fn new(err: ParseError<usize, Tok, SyntaxError>) -> Self {
+        let expected = |expected: Vec<String>| {
+            // Terminals are named as in /frontend/sysy.lalrpop.
+            let names = expected
+                .iter()
+                .map(|token| match token.as_str() {
+                    "IDENT" => "identifier".to_string(),
+                    "INT_LIT" => "integer literal".to_string(),
+                    "CHAR_LIT" => "character literal".to_string(),
+                    "STR_LIT" => "string literal".to_string(),
+                    _ => format!("`{}`", token.trim_matches('"')),
+                })
+                .collect::<Vec<_>>();
+            match &names[..] {
+                [] => String::new(),
+                [name] => format!(", expected {name}"),
+                _ => format!(", expected one of {}", names.join(", ")),
+            }
+        };
+        match err {
+            ParseError::InvalidToken { location } => Self {
+                offset: location,
+                msg: "invalid token".to_string(),
+            },
+            ParseError::UnrecognizedEof { location, expected: tokens } => Self {
+                offset: location,
+                msg: format!("unexpected end of input{}", expected(tokens)),
+            },
+            ParseError::UnrecognizedToken { token: (l, token, _), expected: tokens } => Self {
+                offset: l,
+                msg: format!("unexpected `{token}`{}", expected(tokens)),
+            },
+            ParseError::ExtraToken { token: (l, token, _) } => Self {
+                offset: l,
+                msg: format!("extra token `{token}`"),
+            },
+            ParseError::User { error } => error,
+        }
+    }
+}
+
+/// Formats SysY program in ASCII text in the style of `Program`'s display,
+/// indented by the given number of spaces. Comments and directives are kept,
+/// and so are single blank lines between declarations and statements.
+/// Nothing is preprocessed, macros included, so the text must parse as it is.
+pub fn format_sysy_text(prog: &str, path: &str, indent: usize) -> String {
+    let (ast, _) = Program::parse_preprocessed(prog, LineMap::of_text(prog, path));
+    SysyTextBuilder::format(&ast, prog, indent)
 }
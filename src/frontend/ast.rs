@@ -4,175 +4,401 @@
 //! for convenience, while maintaining equivalence.
 //! See /frontend/sysy.lalrpop for the adjusted grammars.
 
-pub struct Program(pub Vec<CompUnit>);
+pub use super::preprocessor::LineMap;
+use std::collections::BTreeMap;
 
+// The line map is filled in after parsing, to locate spans.
+pub struct Program(pub Vec<CompUnit>, pub Exprs, pub LineMap);
+
+// Line maps only locate nodes, like spans, see `without_spans`.
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.1 == other.1
+    }
+}
+
+impl Program {
+    /// Returns the program with all spans cleared, so that programs compare
+    /// by structure, e.g. before and after being printed and parsed back.
+    pub fn without_spans(mut self) -> Self {
+        self.0.iter_mut().for_each(|unit| match unit {
+            CompUnit::VarDecl(decl) => decl.clear_spans(),
+            CompUnit::FuncDef(def) => {
+                def.params.iter_mut().for_each(FuncFParam::clear_span);
+                def.block.clear_spans();
+                (def.span, def.name_span) = (Span::default(), Span::default());
+            }
+            CompUnit::FuncDecl(decl) => {
+                decl.params.iter_mut().for_each(FuncFParam::clear_span);
+                (decl.span, decl.name_span) = (Span::default(), Span::default());
+            }
+            CompUnit::Error(span) => *span = Span::default(),
+        });
+        self.1.infos.iter_mut().for_each(|info| info.span = Span::default());
+        self
+    }
+}
+
+#[derive(PartialEq)]
 pub enum CompUnit {
     VarDecl(VarDecl),
     FuncDef(FuncDef),
+    FuncDecl(FuncDecl),
+    Error(Span), // Skipped in error recovery, see /frontend/sysy.lalrpop.
 }
 
 // Variable declaration.
 
+#[derive(PartialEq)]
 pub struct VarDecl {
     pub is_global: bool,
     pub is_const: bool,
+    pub is_extern: bool,
     pub btype: BType,
     pub var_defs: Vec<VarDef>,
+    pub span: Span, // The whole declaration, `;` included.
 }
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum BType {
     Int, Void,
 }
 
+// Spans cover the identifiers, used in warnings.
+#[derive(PartialEq)]
 pub enum VarDef {
-    Scalar(String, Option<Exp>),
-    Array(String, Vec<Exp>, Option<InitList>),
+    Scalar(String, Option<ExprId>, Span),
+    Array(String, Vec<ExprId>, Option<InitList>, Span),
 }
 
+#[derive(PartialEq)]
 pub enum InitList {
-    Exp(Exp),
+    Exp(ExprId),
     List(Vec<InitList>),
-    Flat(Vec<Exp>), // This arm not used in parsing.
+    Flat(Vec<InitRun>), // This arm not used in parsing.
 }
 
+/// A run of consecutive values in a flattened initializer list,
+/// starting at the given offset. Elements covered by no run are zeros.
+#[derive(PartialEq)]
+pub struct InitRun(pub usize, pub Vec<ExprId>);
+
 // Function definition.
+// Spans of functions and blocks cover them as a whole,
+// used to put comments back in formatting.
+
+#[derive(PartialEq)]
+pub struct FuncDef {
+    pub btype: BType,
+    pub ident: String,
+    pub params: Vec<FuncFParam>,
+    pub block: Block,
+    pub span: Span,
+    pub name_span: Span, // Of the name only, like spans of parameters.
+}
 
-pub struct FuncDef(pub BType, pub String, pub Vec<FuncFParam>, pub Block);
+// Function declaration, i.e., prototype.
+#[derive(PartialEq)]
+pub struct FuncDecl {
+    pub btype: BType,
+    pub ident: String,
+    pub params: Vec<FuncFParam>,
+    pub span: Span,
+    pub name_span: Span,
+}
 
+#[derive(PartialEq)]
 pub enum FuncFParam {
-    Scalar(BType, String),
-    Array(BType, String, Vec<Exp>),
+    Scalar(BType, String, Span),
+    Array(BType, String, Vec<ExprId>, Span),
 }
 
 // Block.
 
-pub struct Block(pub Vec<BlockItem>);
+#[derive(PartialEq)]
+pub struct Block(pub Vec<BlockItem>, pub Span);
 
+#[derive(PartialEq)]
 pub enum BlockItem {
     VarDecl(VarDecl),
-    Stmt(Stmt),
+    Stmt(Stmt, Span),
 }
 
 // Statement.
 
+#[derive(PartialEq)]
 pub enum Stmt {
-    Assign(LVal, Exp),
+    Assign(ExprId, ExprId), // The left one is a variable or an indexing.
     Empty,
-    Exp(Exp),
+    Exp(ExprId),
     Block(Block),
-    If(Exp, Box<Stmt>, Option<Box<Stmt>>),
-    While(Exp, Box<Stmt>),
+    If(ExprId, Box<Stmt>, Option<Box<Stmt>>),
+    While(ExprId, Box<Stmt>),
     Break,
     Continue,
-    Return(Option<Exp>),
+    Return(Option<ExprId>),
+    Error, // Skipped in error recovery, spanned by its `BlockItem`.
 }
 
 // Expression.
+// Expressions live in an arena, `Exprs`, and refer to each other by id.
+// Precedence is resolved by the parser, so it doesn't show up here.
+
+#[derive(Clone, PartialEq)]
+pub enum Expr {
+    Binary(BinaryOp, ExprId, ExprId),
+    Unary(UnaryOp, ExprId),
+    Call(String, Vec<ExprId>),
+    Index(ExprId, ExprId), // Array and index.
+    Var(String),
+    Lit(Lit),
+}
 
-#[derive(Clone)]
-pub enum Exp {
-    LOrExp(LOrExp),
-    Number(Number), // This arm not used in parsing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Mul, Div, Rem,
+    Add, Sub,
+    Lt, Le, Gt, Ge,
+    Eq, Ne,
+    And, Or,
 }
 
-impl Exp {
-    pub fn from_number(value: i32) -> Self {
-        Exp::Number(Number(value))
-    }
+#[derive(Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Plus, Minus, Not,
+}
 
-    pub fn set_value(&mut self, value: i32) {
-        *self = Exp::from_number(value);
-    }
+#[derive(Clone, PartialEq)]
+pub enum Lit {
+    Int(i32),
+    Str(Vec<u8>), // Only used as function call argument.
+}
 
-    pub fn value(&self) -> i32 {
-        if let Exp::Number(Number(value)) = self {
-            *value
+// Type.
+
+/// Type of a variable or an expression.
+#[derive(Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Void, // Only for function calls.
+    // Sizes of each dimension.
+    Array(Vec<i32>),
+    // Pointer to an array with the given sizes, or to `int` if empty.
+    // Array parameters `int a[][n]` have type `Pointer(vec![n])`.
+    Pointer(Vec<i32>),
+}
+
+impl Type {
+    /// Returns the type after indexing `n` times,
+    /// or `None` if it can't be indexed that many times.
+    pub fn index(&self, n: usize) -> Option<Type> {
+        let sizes = match self {
+            _ if n == 0 => return Some(self.clone()),
+            Type::Int | Type::Void => return None,
+            Type::Array(sizes) => &sizes[1..],
+            Type::Pointer(sizes) => &sizes[..],
+        };
+        if sizes.is_empty() {
+            Type::Int.index(n - 1)
         } else {
-            panic!("Unexpected arm");
+            Type::Array(sizes.to_vec()).index(n - 1)
         }
     }
-}
 
-#[derive(Clone)]
-pub enum LVal {
-    Ident(String),
-    ArrayElem(String, Vec<Exp>),
+    /// Returns whether a value of this type can be passed as
+    /// an argument to a parameter of the given type.
+    /// Arrays decay into pointers to their first elements.
+    pub fn is_passable_to(&self, param: &Type) -> bool {
+        match (self, param) {
+            (Type::Array(sizes), Type::Pointer(elem)) => sizes[1..] == elem[..],
+            _ => self == param,
+        }
+    }
 }
 
-#[derive(Clone)]
-pub enum PrimaryExp {
-    BracketedExp(Box<Exp>),
-    Number(Number),
-    LVal(LVal),
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dims = |sizes: &[i32]| {
+            sizes.iter().map(|size| format!("[{size}]")).collect::<String>()
+        };
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Void => write!(f, "void"),
+            Type::Array(sizes) => write!(f, "int{}", dims(sizes)),
+            Type::Pointer(sizes) => write!(f, "int[]{}", dims(sizes)),
+        }
+    }
 }
 
-#[derive(Clone)]
-pub struct Number(pub i32);
+// Expression arena.
 
-#[derive(Clone)]
-pub enum UnaryExp {
-    Primary(Box<PrimaryExp>),
-    FuncCall(String, Vec<Exp>),
-    OpUnary(UnaryOp, Box<UnaryExp>),
-}
+/// Id of an expression, i.e., its index in `Exprs`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(pub usize);
 
-#[derive(Clone)]
-pub enum UnaryOp {
-    Plus, Minus, Not,
+/// Byte range in preprocessed source text.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Span {
+    pub begin: usize,
+    pub end: usize,
 }
 
-#[derive(Clone)]
-pub enum MulExp {
-    Unary(Box<UnaryExp>),
-    MulOpUnary(Box<MulExp>, MulOp, Box<UnaryExp>),
+/// Information of an expression. Span is recorded in parsing,
+/// the others are filled in semantic analysis.
+#[derive(Clone, PartialEq)]
+pub struct ExprInfo {
+    pub span: Span,
+    pub ty: Option<Type>,
+    pub is_const: bool,
+    pub value: Option<i32>, // Only for constant expressions.
 }
 
-#[derive(Clone)]
-pub enum MulOp {
-    Mul, Div, Rem,
+/// Arena of expressions along with their information, indexed by `ExprId`.
+#[derive(Clone, Default, PartialEq)]
+pub struct Exprs {
+    exprs: Vec<Expr>,
+    infos: Vec<ExprInfo>,
 }
 
-#[derive(Clone)]
-pub enum AddExp {
-    Mul(Box<MulExp>),
-    AddOpMul(Box<AddExp>, AddOp, Box<MulExp>),
-}
+impl Exprs {
+    /// Adds an expression spanning the given range, and returns its id.
+    pub fn add(&mut self, expr: Expr, begin: usize, end: usize) -> ExprId {
+        self.exprs.push(expr);
+        self.infos.push(ExprInfo {
+            span: Span { begin, end },
+            ty: None,
+            is_const: false,
+            value: None,
+        });
+        ExprId(self.exprs.len() - 1)
+    }
 
-#[derive(Clone)]
-pub enum AddOp {
-    Add, Sub,
+    pub fn info(&self, id: ExprId) -> &ExprInfo {
+        &self.infos[id.0]
+    }
+
+    pub fn info_mut(&mut self, id: ExprId) -> &mut ExprInfo {
+        &mut self.infos[id.0]
+    }
+
+    /// Returns an iterator over the information of all expressions.
+    pub fn infos(&self) -> impl Iterator<Item = &ExprInfo> {
+        self.infos.iter()
+    }
+
+    /// Returns the type of the given expression.
+    pub fn ty(&self, id: ExprId) -> &Type {
+        self.info(id).ty.as_ref().expect("Type of expression not set")
+    }
+
+    /// Returns the value of the given expression, which must be an integer literal.
+    pub fn value(&self, id: ExprId) -> i32 {
+        if let Expr::Lit(Lit::Int(value)) = self[id] {
+            value
+        } else {
+            panic!("Unexpected arm");
+        }
+    }
+
+    /// Returns the values of the given folded runs, indexed by their offsets.
+    /// Zeros are left out, like omitted values.
+    pub fn run_values(&self, runs: &[InitRun]) -> BTreeMap<usize, i32> {
+        runs.iter()
+            .flat_map(|InitRun(offset, values)| {
+                values.iter().enumerate().map(move |(i, value)| (offset + i, *value))
+            })
+            .map(|(idx, value)| (idx, self.value(value)))
+            .filter(|(_, value)| *value != 0)
+            .collect()
+    }
+
+    /// Returns the indices of the given left value, outermost first.
+    /// e.g. `a[i][j]` => `[i, j]`
+    pub fn indices(&self, mut id: ExprId) -> Vec<ExprId> {
+        let mut indices = vec![];
+        while let Expr::Index(arr, index) = self[id] {
+            indices.push(index);
+            id = arr;
+        }
+        indices.reverse();
+        indices
+    }
+
+    /// Returns the variable at the root of the given left value.
+    pub fn var_of(&self, id: ExprId) -> &String {
+        match &self[self.root(id)] {
+            Expr::Var(ident) => ident,
+            _ => panic!("Unexpected arm"),
+        }
+    }
+
+    /// Returns the expression at the root of the given left value,
+    /// i.e., the one not indexed.
+    pub fn root(&self, mut id: ExprId) -> ExprId {
+        while let Expr::Index(arr, _) = self[id] {
+            id = arr;
+        }
+        id
+    }
 }
 
-#[derive(Clone)]
-pub enum RelExp {
-    Add(Box<AddExp>),
-    RelOpAdd(Box<RelExp>, RelOp, Box<AddExp>),
+impl std::ops::Index<ExprId> for Exprs {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.0]
+    }
 }
 
-#[derive(Clone)]
-pub enum RelOp {
-    Le, Lt, Ge, Gt,
+impl std::ops::IndexMut<ExprId> for Exprs {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.0]
+    }
 }
 
-#[derive(Clone)]
-pub enum EqExp {
-    Rel(Box<RelExp>),
-    EqOpRel(Box<EqExp>, EqOp, Box<RelExp>),
+// Helpers of `Program::without_spans`.
+
+impl VarDecl {
+    fn clear_spans(&mut self) {
+        self.var_defs.iter_mut().for_each(|def| match def {
+            VarDef::Scalar(_, _, span) | VarDef::Array(_, _, _, span) => *span = Span::default(),
+        });
+        self.span = Span::default();
+    }
 }
 
-#[derive(Clone)]
-pub enum EqOp {
-    Eq, Ne,
+impl FuncFParam {
+    fn clear_span(&mut self) {
+        let (FuncFParam::Scalar(_, _, span) | FuncFParam::Array(_, _, _, span)) = self;
+        *span = Span::default();
+    }
 }
 
-#[derive(Clone)]
-pub enum LAndExp {
-    Eq(Box<EqExp>),
-    LAndEq(Box<LAndExp>, Box<EqExp>),
+impl Block {
+    fn clear_spans(&mut self) {
+        self.0.iter_mut().for_each(|item| match item {
+            BlockItem::VarDecl(decl) => decl.clear_spans(),
+            BlockItem::Stmt(stmt, span) => {
+                stmt.clear_spans();
+                *span = Span::default();
+            }
+        });
+        self.1 = Span::default();
+    }
 }
 
-#[derive(Clone)]
-pub enum LOrExp {
-    LAnd(Box<LAndExp>),
-    LOrLAnd(Box<LOrExp>, Box<LAndExp>),
+impl Stmt {
+    fn clear_spans(&mut self) {
+        match self {
+            Stmt::Block(block) => block.clear_spans(),
+            Stmt::If(_, then, opt_else) => {
+                then.clear_spans();
+                if let Some(otherwise) = opt_else {
+                    otherwise.clear_spans();
+                }
+            }
+            Stmt::While(_, body) => body.clear_spans(),
+            _ => {}
+        }
+    }
 }
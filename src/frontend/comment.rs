@@ -0,0 +1,48 @@
+//! Comments and preprocessing directives, which the parser skips,
+//! collected from unpreprocessed text so that formatting keeps them.
+
+use super::ast::Span;
+use super::lexer::quoted_len;
+
+/// A comment, or a directive line, along with where it is in the text.
+pub struct Comment {
+    pub span: Span,
+    pub text: String,
+    pub is_directive: bool,
+}
+
+/// Collects comments and directives in the given text, in order.
+/// Literals are skipped, so that `"//"` is no comment.
+/// An unterminated block comment extends to the end, left to the parser.
+pub fn scan(text: &str) -> Vec<Comment> {
+    let bytes = text.as_bytes();
+    let mut comments = vec![];
+    let mut line_start = true; // Only whitespace so far on the current line.
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &text[i..];
+        let (len, is_comment) = if rest.starts_with("//") || (line_start && rest.starts_with('#')) {
+            (rest.find(['\n', '\r']).unwrap_or(rest.len()), true)
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            (body.find("*/").map_or(rest.len(), |end| end + 4), true)
+        } else if rest.starts_with(['"', '\'']) {
+            (quoted_len(rest), false)
+        } else {
+            (rest.chars().next().unwrap().len_utf8(), false)
+        };
+        if is_comment {
+            comments.push(Comment {
+                span: Span { begin: i, end: i + len },
+                text: rest[..len].trim_end().to_string(),
+                is_directive: rest.starts_with('#'),
+            });
+        }
+        line_start = match bytes[i] {
+            b'\n' | b'\r' => true,
+            b' ' | b'\t' => line_start,
+            _ => false,
+        };
+        i += len;
+    }
+    comments
+}
@@ -0,0 +1,203 @@
+//! Lexer of SysY, plugged into the parser as an external lexer,
+//! see the `extern` block in /frontend/sysy.lalrpop.
+//! Whitespace, comments and directives are skipped, the latter only
+//! where they may begin, i.e., with nothing but whitespace before on the line.
+//! Lexical errors are recorded along with parse errors, and the lexer goes on,
+//! producing the token that was probably meant if any,
+//! so that parsing goes on as well. Only an unterminated block comment
+//! stops it, since the rest of the text is in the comment.
+
+use super::lint::{Lint, Warning};
+use super::literal;
+use super::SyntaxError;
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+
+const KEYWORDS: [&str; 10] = [
+    "const", "extern", "int", "void", "if", "else", "while", "break", "continue", "return",
+];
+
+// Longer ones first, so that `<=` is not taken as `<` followed by `=`.
+const PUNCTS: [&str; 23] = [
+    "<=", ">=", "==", "!=", "&&", "||", "(", ")", "[", "]", "{", "}", ",", ";", "=", "+", "-",
+    "*", "/", "%", "!", "<", ">",
+];
+
+/// A token, holding its text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tok<'input> {
+    Keyword(&'static str),
+    Punct(&'static str),
+    Ident(&'input str),
+    // Literals are validated by the lexer, and decoded by the parser.
+    IntLit(&'input str),
+    CharLit(&'input str),
+    StrLit(&'input str),
+}
+
+impl Display for Tok<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Keyword(s) | Tok::Punct(s) => write!(f, "{s}"),
+            Tok::Ident(s) | Tok::IntLit(s) | Tok::CharLit(s) | Tok::StrLit(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// A token along with its byte span, as the parser takes it.
+pub type Spanned<'input> = (usize, Tok<'input>, usize);
+
+/// Lexer over the given text.
+pub struct Lexer<'input, 'e> {
+    text: &'input str,
+    pos: usize,
+    errors: &'e RefCell<Vec<SyntaxError>>,
+    warnings: &'e RefCell<Vec<Warning>>,
+    nested: bool,     // Whether the last block comment has `/*` in it.
+    line_start: bool, // Only whitespace so far on the current line.
+}
+
+impl<'input, 'e> Lexer<'input, 'e> {
+    /// Creates a lexer recording lexical errors in `errors`,
+    /// and warnings, e.g. on nested comments, in `warnings`.
+    pub fn new(
+        text: &'input str,
+        errors: &'e RefCell<Vec<SyntaxError>>,
+        warnings: &'e RefCell<Vec<Warning>>,
+    ) -> Self {
+        Self {
+            text,
+            pos: 0,
+            errors,
+            warnings,
+            nested: false,
+            line_start: true,
+        }
+    }
+
+    fn error(&self, offset: usize, msg: String) {
+        self.errors.borrow_mut().push(SyntaxError { offset, msg });
+    }
+
+    /// Returns the length of the longest prefix of `rest` made of
+    /// identifier characters, which numeric literals are scanned with too,
+    /// so that `12ab` is an invalid literal, not `12` followed by `ab`.
+    fn word_len(rest: &str) -> usize {
+        rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len())
+    }
+
+    /// Skips the block comment at the current position.
+    /// `/*` in it is likely meant to open a nested comment, which
+    /// the comment doesn't end after, so it is warned about.
+    fn block_comment(&mut self) -> Result<(), SyntaxError> {
+        let body = &self.text[self.pos + 2..];
+        let Some(end) = body.find("*/") else {
+            let msg = "unterminated block comment".to_string();
+            return Err(SyntaxError { offset: self.pos, msg });
+        };
+        let nested = body[..end].find("/*");
+        if let Some(offset) = nested {
+            let msg = "`/*` within block comment".to_string();
+            let offset = self.pos + 2 + offset;
+            self.warnings.borrow_mut().push(Warning { lint: Lint::NestedComment, offset, msg });
+        }
+        self.nested = nested.is_some();
+        self.pos += end + 4;
+        Ok(())
+    }
+
+    /// Scans the character or string literal at the current position.
+    fn quoted(&mut self, rest: &'input str) -> Tok<'input> {
+        let len = quoted_len(rest);
+        let lit = &rest[..len];
+        let (tok, result) = match rest.as_bytes()[0] {
+            b'\'' => (Tok::CharLit(lit), literal::char_value(lit).err()),
+            _ => (Tok::StrLit(lit), literal::string_value(lit).err()),
+        };
+        if let Some(msg) = result {
+            self.error(self.pos, msg);
+        }
+        self.pos += len;
+        tok
+    }
+}
+
+/// Returns the length of the character or string literal at the beginning
+/// of `text`, which ends at the closing quote, or before the end of the line.
+/// Literals are skipped with this wherever comments are told from them.
+pub fn quoted_len(text: &str) -> usize {
+    let quote = text.as_bytes()[0];
+    let mut escaped = false;
+    for (i, c) in text.bytes().enumerate().skip(1) {
+        if c == b'\n' || c == b'\r' {
+            return i;
+        }
+        if !escaped && c == quote {
+            return i + 1;
+        }
+        escaped = !escaped && c == b'\\';
+    }
+    text.len()
+}
+
+impl<'input> Iterator for Lexer<'input, '_> {
+    type Item = Result<Spanned<'input>, SyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let begin = self.pos;
+            let rest = &self.text[begin..];
+            let c = rest.chars().next()?;
+            let line_start = std::mem::replace(&mut self.line_start, false);
+            let tok = if c.is_ascii_whitespace() {
+                self.line_start = line_start || c == '\n' || c == '\r';
+                self.pos += 1;
+                continue;
+            } else if rest.starts_with("//") || (line_start && c == '#') {
+                // Directives are left in text only when formatting.
+                self.pos += rest.find(['\n', '\r']).unwrap_or(rest.len());
+                continue;
+            } else if rest.starts_with("/*") {
+                if let Err(err) = self.block_comment() {
+                    self.pos = self.text.len();
+                    return Some(Err(err));
+                }
+                continue;
+            } else if rest.starts_with("*/") && !rest.starts_with("*/*") {
+                // Never valid, as `/` is no unary operator.
+                // Likely the end of a comment, which has ended earlier.
+                let hint = if self.nested { ", block comments do not nest" } else { "" };
+                self.error(begin, format!("`*/` outside of a comment{hint}"));
+                self.pos += 2;
+                continue;
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let word = &rest[..Self::word_len(rest)];
+                self.pos += word.len();
+                KEYWORDS.iter().find(|&&k| k == word).map_or(Tok::Ident(word), |k| Tok::Keyword(k))
+            } else if c.is_ascii_digit() {
+                let lit = &rest[..Self::word_len(rest)];
+                if let Err(msg) = literal::int_value(lit) {
+                    self.error(begin, msg);
+                }
+                self.pos += lit.len();
+                Tok::IntLit(lit)
+            } else if c == '\'' || c == '"' {
+                self.quoted(rest)
+            } else if let Some(punct) = PUNCTS.iter().find(|&&p| rest.starts_with(p)) {
+                self.pos += punct.len();
+                Tok::Punct(punct)
+            } else if c == '&' || c == '|' {
+                // Bitwise operators are not supported, logical ones are likely meant.
+                let punct = if c == '&' { "&&" } else { "||" };
+                self.error(begin, format!("unexpected `{c}`, did you mean `{punct}`?"));
+                self.pos += 1;
+                Tok::Punct(punct)
+            } else {
+                self.error(begin, format!("unexpected character {c:?}"));
+                self.pos += c.len_utf8();
+                continue;
+            };
+            return Some(Ok((begin, tok, self.pos)));
+        }
+    }
+}
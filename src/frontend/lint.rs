@@ -0,0 +1,115 @@
+//! Warnings, i.e., diagnostics that don't stop compilation by default.
+//! Each warning belongs to a named lint, whose level can be changed
+//! from the command line, like `-Aunused-variable` or `-Dall`.
+
+use super::preprocessor::LineMap;
+use std::collections::HashMap;
+
+/// Named lints.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    Uninitialized,
+    ShadowGlobal,
+    UnreachableCode,
+    ConstantCondition,
+    OverflowingLiterals,
+    NestedComment,
+}
+
+impl Lint {
+    const ALL: [Lint; 7] = [
+        Lint::UnusedVariable,
+        Lint::Uninitialized,
+        Lint::ShadowGlobal,
+        Lint::UnreachableCode,
+        Lint::ConstantCondition,
+        Lint::OverflowingLiterals,
+        Lint::NestedComment,
+    ];
+
+    /// Returns the name of the lint used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::Uninitialized => "uninitialized",
+            Lint::ShadowGlobal => "shadow-global",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::OverflowingLiterals => "overflowing-literals",
+            Lint::NestedComment => "nested-comment",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// A warning at the given offset in preprocessed source text.
+pub struct Warning {
+    pub lint: Lint,
+    pub offset: usize,
+    pub msg: String,
+}
+
+/// Levels of all lints. Every lint warns by default.
+pub struct Lints {
+    levels: HashMap<Lint, Level>,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Self {
+            levels: Lint::ALL.iter().map(|lint| (*lint, Level::Warn)).collect(),
+        }
+    }
+}
+
+impl Lints {
+    /// Applies a command line flag, `-W<lint>`, `-A<lint>` or `-D<lint>`,
+    /// where `<lint>` may be `all`. Returns whether the flag is a lint flag.
+    /// Later flags override earlier ones.
+    pub fn parse_flag(&mut self, flag: &str) -> bool {
+        let level = match flag.get(..2) {
+            Some("-W") => Level::Warn,
+            Some("-A") => Level::Allow,
+            Some("-D") => Level::Deny,
+            _ => return false,
+        };
+        let name = &flag[2..];
+        if name == "all" {
+            self.levels.values_mut().for_each(|lvl| *lvl = level);
+            return true;
+        }
+        let lint = Lint::ALL
+            .into_iter()
+            .find(|lint| lint.name() == name)
+            .unwrap_or_else(|| panic!("Unknown lint: {name}"));
+        self.levels.insert(lint, level);
+        true
+    }
+
+    /// Prints the given warnings in source order, skipping allowed ones.
+    /// Panics if any of them is denied.
+    pub fn report(&self, mut warnings: Vec<Warning>, line_map: &LineMap) {
+        warnings.sort_by_key(|warning| warning.offset);
+        let mut denied = 0;
+        warnings.iter().for_each(|Warning { lint, offset, msg }| {
+            let (kind, flag) = match self.levels[lint] {
+                Level::Allow => return,
+                Level::Warn => ("warning", 'W'),
+                Level::Deny => {
+                    denied += 1;
+                    ("error", 'D')
+                }
+            };
+            let loc = line_map.locate(*offset);
+            eprintln!("{loc}: {kind}: {msg} [-{flag}{}]", lint.name());
+        });
+        assert!(denied == 0, "Aborting due to {denied} denied warning(s)");
+    }
+}
@@ -0,0 +1,122 @@
+//! Decoding of literals.
+//! SysY itself has neither character nor string literals,
+//! we accept them as an extension of C style,
+//! with the usual escape sequences (`\n`, `\x41`, `\101`, etc.).
+
+/// Decodes a decimal, octal or hexadecimal integer literal.
+/// The value wraps around modulo 2^32 and is reinterpreted as `i32`,
+/// e.g. `2147483648` is `i32::MIN`, and `0xFFFFFFFF` is `-1`.
+/// Also returns whether the literal fits in 32 bits.
+/// Fails on digits out of the radix, including suffixes like `12ab`.
+pub fn int_value(lit: &str) -> Result<(i32, bool), String> {
+    let hex = lit.strip_prefix("0x").or(lit.strip_prefix("0X"));
+    let (digits, radix, kind) = if let Some(hex) = hex {
+        (hex, 16, "hexadecimal")
+    } else if lit.len() > 1 && lit.starts_with('0') {
+        (&lit[1..], 8, "octal")
+    } else {
+        (lit, 10, "decimal")
+    };
+    if digits.is_empty() {
+        return Err(format!("{kind} literal `{lit}` has no digits"));
+    }
+    let (mut value, mut fits) = (0u32, true);
+    for c in digits.chars() {
+        let digit = c
+            .to_digit(radix)
+            .ok_or_else(|| format!("invalid digit `{c}` in {kind} literal `{lit}`"))?;
+        let (shifted, overflow) = value.overflowing_mul(radix);
+        fits &= !overflow;
+        let (added, overflow) = shifted.overflowing_add(digit);
+        fits &= !overflow;
+        value = added;
+    }
+    Ok((value as i32, fits))
+}
+
+/// Decodes the body of a character or string literal, i.e.,
+/// the text between the quotes, into raw bytes.
+pub fn unescape(body: &str) -> Result<Vec<u8>, String> {
+    let mut res = Vec::with_capacity(body.len());
+    let mut iter = body.bytes().peekable();
+
+    while let Some(byte) = iter.next() {
+        if byte != b'\\' {
+            res.push(byte);
+            continue;
+        }
+
+        let escaped = iter.next().ok_or("dangling backslash in literal")?;
+        let value = match escaped {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'\\' | b'\'' | b'"' | b'?' => escaped,
+            b'x' => {
+                let mut value = 0u32;
+                let mut digits = 0;
+                while let Some(digit) = iter.peek().and_then(|&c| (c as char).to_digit(16)) {
+                    value = value * 16 + digit;
+                    digits += 1;
+                    iter.next();
+                }
+                if digits == 0 {
+                    return Err("expected hex digits after \\x".to_string());
+                }
+                if value > 0xff {
+                    return Err("hex escape sequence out of range".to_string());
+                }
+                value as u8
+            }
+            b'0'..=b'7' => {
+                let mut value = (escaped - b'0') as u32;
+                // At most 3 octal digits, including the one consumed above.
+                for _ in 0..2 {
+                    match iter.peek() {
+                        Some(&c @ b'0'..=b'7') => {
+                            value = value * 8 + (c - b'0') as u32;
+                            iter.next();
+                        }
+                        _ => break,
+                    }
+                }
+                if value > 0xff {
+                    return Err("octal escape sequence out of range".to_string());
+                }
+                value as u8
+            }
+            _ => return Err(format!("unknown escape sequence \\{}", escaped as char)),
+        };
+        res.push(value);
+    }
+
+    Ok(res)
+}
+
+/// Returns the value of a character literal, quotes included.
+/// Like C, the value has type `int`.
+pub fn char_value(lit: &str) -> Result<i32, String> {
+    let bytes = unescape(body(lit, "character")?)?;
+    if bytes.len() != 1 {
+        return Err(format!("invalid character literal {lit}"));
+    }
+    // Plain `char` is unsigned on RISC-V, so is ours.
+    Ok(bytes[0] as i32)
+}
+
+/// Returns the content of a string literal, quotes included.
+pub fn string_value(lit: &str) -> Result<Vec<u8>, String> {
+    unescape(body(lit, "string")?)
+}
+
+/// Returns the text between the quotes of a literal of the given kind.
+fn body<'a>(lit: &'a str, kind: &str) -> Result<&'a str, String> {
+    let quote = &lit[..1];
+    lit[1..]
+        .strip_suffix(quote)
+        .ok_or_else(|| format!("unterminated {kind} literal"))
+}
@@ -0,0 +1,391 @@
+//! A tiny preprocessor running before the parser.
+//! It supports a small subset of the C preprocessor:
+//! `#include "file"`, object-like `#define`, `#undef`,
+//! `#ifdef`, `#ifndef`, `#else`, `#endif`, and `__LINE__`.
+//! Besides, `starttime()` and `stoptime()` are rewritten to
+//! `_sysy_starttime(__LINE__)` and `_sysy_stoptime(__LINE__)`,
+//! just like what `sylib.h` of the official runtime does.
+//!
+//! The output keeps one line per source line (directives become empty lines),
+//! and a line map records where each output line comes from.
+
+use super::lexer::quoted_len;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Maps lines of preprocessed text back to source files.
+#[derive(Clone, Default)]
+pub struct LineMap {
+    files: Vec<String>,
+    lines: Vec<(usize, usize)>, // (index in `files`, 1-based line number)
+    line_starts: Vec<usize>,    // Byte offsets in preprocessed text.
+}
+
+impl LineMap {
+    /// Returns the source location of the given byte offset
+    /// in preprocessed text, formatted as `file:line`.
+    pub fn locate(&self, offset: usize) -> String {
+        let (file, line, _) = self.position(offset);
+        format!("{file}:{line}")
+    }
+
+    /// Returns the source file, 1-based line number and 0-based column
+    /// of the given byte offset in preprocessed text.
+    /// Columns are off after expanded macros on the same line.
+    pub fn position(&self, offset: usize) -> (&str, usize, usize) {
+        let idx = self.line_starts.partition_point(|&start| start <= offset);
+        let idx = idx.saturating_sub(1);
+        let (file, line) = self.lines[idx];
+        (&self.files[file], line, offset - self.line_starts[idx])
+    }
+
+    /// Returns the byte offset in preprocessed text of the given position,
+    /// the inverse of `position`, or `None` if the line is not included.
+    pub fn offset(&self, file: &str, line: usize, column: usize) -> Option<usize> {
+        let file = self.files.iter().position(|f| f == file)?;
+        let idx = self.lines.iter().position(|&pos| pos == (file, line))?;
+        Some(self.line_starts[idx] + column)
+    }
+
+    /// Returns the line map of unpreprocessed text, whose lines are
+    /// where they come from.
+    pub fn of_text(text: &str, path: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect::<Vec<_>>();
+        Self {
+            files: vec![path.to_string()],
+            lines: (1..=line_starts.len()).map(|line| (0, line)).collect(),
+            line_starts,
+        }
+    }
+}
+
+/// Conditional inclusion state of one `#ifdef` / `#ifndef`.
+struct Cond {
+    active: bool,      // Whether the current branch is taken.
+    parent: bool,      // Whether the enclosing region is active.
+    seen_else: bool,
+}
+
+/// Preprocessor.
+pub struct Preprocessor {
+    text: String,
+    line_map: LineMap,
+    macros: HashMap<String, String>,
+    conds: Vec<Cond>, // Actually a stack.
+    in_comment: bool, // Inside a block comment spanning lines.
+    include_stack: Vec<String>,
+}
+
+impl Preprocessor {
+    /// Maximum depth of nested `#include`.
+    const MAX_INCLUDE_DEPTH: usize = 64;
+
+    /// Preprocesses the given SysY program, whose path is used
+    /// to resolve `#include` and to report locations.
+    pub fn run(prog: &str, path: &str) -> (String, LineMap) {
+        let mut pp = Self::new();
+        pp.process(prog, path);
+        (pp.text, pp.line_map)
+    }
+
+    /// Creates a new preprocessor.
+    fn new() -> Self {
+        Self {
+            text: String::new(),
+            line_map: LineMap {
+                files: vec![],
+                lines: vec![],
+                line_starts: vec![],
+            },
+            macros: HashMap::new(),
+            conds: vec![],
+            in_comment: false,
+            include_stack: vec![],
+        }
+    }
+
+    /// Returns whether lines are currently emitted.
+    fn is_active(&self) -> bool {
+        self.conds.last().is_none_or(|cond| cond.active)
+    }
+
+    /// Processes a whole file.
+    fn process(&mut self, prog: &str, path: &str) {
+        assert!(
+            !self.include_stack.iter().any(|p| p == path),
+            "{path}: recursive #include"
+        );
+        assert!(
+            self.include_stack.len() < Self::MAX_INCLUDE_DEPTH,
+            "{path}: #include nested too deeply"
+        );
+        self.include_stack.push(path.to_string());
+        self.line_map.files.push(path.to_string());
+        let file = self.line_map.files.len() - 1;
+        let depth = self.conds.len();
+
+        prog.lines().enumerate().for_each(|(i, line)| {
+            let loc = format!("{path}:{}", i + 1);
+            let trimmed = line.trim_start();
+            if !self.in_comment && trimmed.starts_with('#') {
+                let directive = self.strip_comments(&trimmed[1..]);
+                self.directive(&directive, path, &loc);
+                // A block comment left open goes on, so does it in the output.
+                let text = if self.in_comment && self.is_active() { "/*" } else { "" };
+                self.push_line(file, i + 1, text);
+            } else if self.is_active() {
+                let line = self.expand(line, i + 1);
+                self.push_line(file, i + 1, &line);
+            } else {
+                self.expand(line, i + 1); // Only to keep track of block comments.
+                self.push_line(file, i + 1, "");
+            }
+        });
+
+        assert_eq!(self.conds.len(), depth, "{path}: unterminated #ifdef");
+        self.include_stack.pop();
+    }
+
+    /// Appends a line to the output.
+    fn push_line(&mut self, file: usize, line: usize, text: &str) {
+        self.line_map.line_starts.push(self.text.len());
+        self.line_map.lines.push((file, line));
+        self.text.push_str(text);
+        self.text.push('\n');
+    }
+
+    /// Handles a directive, `#` excluded.
+    fn directive(&mut self, directive: &str, path: &str, loc: &str) {
+        let directive = directive.trim();
+        let (name, rest) = directive
+            .split_once(char::is_whitespace)
+            .unwrap_or((directive, ""));
+        let rest = rest.trim();
+
+        match name {
+            "ifdef" | "ifndef" => {
+                let defined = self.macros.contains_key(Self::ident_arg(rest, loc));
+                let parent = self.is_active();
+                self.conds.push(Cond {
+                    active: parent && (defined == (name == "ifdef")),
+                    parent,
+                    seen_else: false,
+                });
+            }
+            "else" => {
+                let cond = self.conds.last_mut().unwrap_or_else(|| {
+                    panic!("{loc}: #else without #ifdef");
+                });
+                assert!(!cond.seen_else, "{loc}: duplicate #else");
+                cond.seen_else = true;
+                cond.active = cond.parent && !cond.active;
+            }
+            "endif" => {
+                self.conds.pop().unwrap_or_else(|| {
+                    panic!("{loc}: #endif without #ifdef");
+                });
+            }
+            _ if !self.is_active() => {} // Skipped region.
+            "define" => {
+                let (ident, body) = rest
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((rest, ""));
+                if ident.contains('(') {
+                    panic!("{loc}: function-like macros are not supported");
+                }
+                let ident = Self::ident_arg(ident, loc);
+                self.macros.insert(ident.to_string(), body.trim().to_string());
+            }
+            "undef" => {
+                self.macros.remove(Self::ident_arg(rest, loc));
+            }
+            "include" => self.include(rest, path, loc),
+            "" => {} // Null directive.
+            _ => panic!("{loc}: unsupported directive #{name}"),
+        }
+    }
+
+    /// Removes comments from a directive, like C does before preprocessing,
+    /// so that `#define N 10 // size` defines `N` as `10`.
+    /// Block comments become spaces, and one left open goes on to following lines.
+    fn strip_comments(&mut self, directive: &str) -> String {
+        let mut res = String::with_capacity(directive.len());
+        let mut rest = directive;
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("//") {
+                break;
+            } else if let Some(body) = rest.strip_prefix("/*") {
+                let Some(end) = body.find("*/") else {
+                    self.in_comment = true;
+                    break;
+                };
+                res.push(' ');
+                rest = &body[end + 2..];
+            } else if c == '"' || c == '\'' {
+                let len = quoted_len(rest);
+                res.push_str(&rest[..len]);
+                rest = &rest[len..];
+            } else {
+                res.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        res
+    }
+
+    /// Checks the argument of a directive is an identifier and returns it.
+    fn ident_arg<'s>(arg: &'s str, loc: &str) -> &'s str {
+        let is_ident = arg.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && arg.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        assert!(is_ident, "{loc}: expected identifier, found `{arg}`");
+        arg
+    }
+
+    /// Handles `#include`.
+    fn include(&mut self, arg: &str, path: &str, loc: &str) {
+        let name = if let Some(name) = arg.strip_prefix('"') {
+            name.strip_suffix('"')
+        } else if let Some(name) = arg.strip_prefix('<') {
+            name.strip_suffix('>')
+        } else {
+            None
+        };
+        let name = name.unwrap_or_else(|| panic!("{loc}: malformed #include"));
+
+        // Runtime functions are built into the compiler.
+        // Other system headers are meaningless for SysY.
+        if name == "sylib.h" || arg.starts_with('<') {
+            return;
+        }
+
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let file = dir.join(name);
+        let file = file.to_string_lossy();
+        let prog = fs::read_to_string(file.as_ref())
+            .unwrap_or_else(|err| panic!("{loc}: cannot include {file}: {err}"));
+        self.process(&prog, &file);
+    }
+
+    /// Expands macros in an ordinary line.
+    fn expand(&mut self, line: &str, lineno: usize) -> String {
+        let mut res = String::with_capacity(line.len());
+        let mut rest = line;
+
+        while let Some(c) = rest.chars().next() {
+            if self.in_comment {
+                let end = match rest.find("*/") {
+                    Some(i) => {
+                        self.in_comment = false;
+                        i + 2
+                    }
+                    None => rest.len(),
+                };
+                res.push_str(&rest[..end]);
+                rest = &rest[end..];
+            } else if rest.starts_with("//") {
+                res.push_str(rest);
+                break;
+            } else if rest.starts_with("/*") {
+                self.in_comment = true;
+                res.push_str("/*");
+                rest = &rest[2..];
+            } else if c == '"' || c == '\'' {
+                let len = quoted_len(rest);
+                res.push_str(&rest[..len]);
+                rest = &rest[len..];
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let (ident, after) = rest.split_at(len);
+                let text = self.expand_ident(ident, lineno, &mut vec![]);
+                rest = Self::push_expansion(&mut res, &text, after, lineno);
+            } else if c.is_ascii_digit() {
+                // Skip the whole number, so that `0x_` isn't mistaken for `_`.
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                res.push_str(&rest[..len]);
+                rest = &rest[len..];
+            } else {
+                res.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        res
+    }
+
+    /// Expands an identifier, recursively.
+    /// `expanding` holds macros being expanded, which are not expanded again.
+    fn expand_ident(&self, ident: &str, lineno: usize, expanding: &mut Vec<String>) -> String {
+        if ident == "__LINE__" {
+            return lineno.to_string();
+        }
+        let Some(body) = self.macros.get(ident) else {
+            return ident.to_string();
+        };
+        if expanding.iter().any(|name| name == ident) {
+            return ident.to_string();
+        }
+
+        expanding.push(ident.to_string());
+        let mut res = String::new();
+        let mut rest = body.as_str();
+        while let Some(c) = rest.chars().next() {
+            if c == '"' || c == '\'' {
+                let len = quoted_len(rest);
+                res.push_str(&rest[..len]);
+                rest = &rest[len..];
+            } else if c.is_ascii_alphanumeric() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let (word, after) = rest.split_at(len);
+                if c.is_ascii_digit() {
+                    res.push_str(word);
+                    rest = after;
+                } else {
+                    let text = self.expand_ident(word, lineno, expanding);
+                    rest = Self::push_expansion(&mut res, &text, after, lineno);
+                }
+            } else {
+                res.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        expanding.pop();
+        res
+    }
+
+    /// Appends the expansion of an identifier followed by `rest`,
+    /// and returns what is left of `rest`.
+    /// An expansion ending with `starttime` or `stoptime` followed by `()`
+    /// is a call, rewritten with the line number. So are the calls
+    /// in macro bodies, or given by macros, e.g. `#define T starttime`.
+    fn push_expansion<'s>(res: &mut String, text: &str, rest: &'s str, lineno: usize) -> &'s str {
+        let start = text
+            .rfind(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(0, |i| i + 1);
+        let (before, last) = text.split_at(start);
+        match (last, Self::strip_empty_parens(rest)) {
+            ("starttime" | "stoptime", Some(after)) => {
+                res.push_str(&format!("{before}_sysy_{last}({lineno})"));
+                after
+            }
+            _ => {
+                res.push_str(text);
+                rest
+            }
+        }
+    }
+
+    /// Strips `()` at the beginning of `text`, whitespace allowed.
+    fn strip_empty_parens(text: &str) -> Option<&str> {
+        let text = text.trim_start().strip_prefix('(')?;
+        text.trim_start().strip_prefix(')')
+    }
+}
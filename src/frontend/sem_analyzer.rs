@@ -1,35 +1,83 @@
 //! Semantic analyzer.
 
+// Semantic errors panic, located at the node being analyzed.
+macro_rules! error {
+    ($self:tt, $($arg:tt)*) => {
+        panic!("{}: {}", $self.line_map.locate($self.pos.get()), format!($($arg)*))
+    };
+}
+
 mod analyze_sem;
 mod eval;
 mod fold;
 mod flatten;
+mod flow;
+mod index;
+mod link;
+mod lower;
 mod symtab;
 mod update;
 
 use super::ast::*;
+use super::lint::{Lint, Warning};
 use analyze_sem::Analyze;
-use std::collections::{HashMap, VecDeque};
-use symtab::SymTab;
+pub use index::{Index, SymbolInfo};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use symtab::Symbol::{self, *};
+use symtab::SymTab;
 
 /// Semantics analyzer.
 pub struct SemAnalyzer {
     symtabs: VecDeque<SymTab>, // Actually a stack, Rust std didn't provide it.
     ident_cnt: HashMap<String, u32>,
+    globals: HashSet<String>, // Tokens of global symbols.
+    exprs: RefCell<Exprs>, // Taken from the program during analysis.
+    line_map: LineMap,
+    pos: Cell<usize>, // Offset of the node being analyzed, to locate errors.
+    locals: HashMap<String, usize>, // Tokens of local variables to their offsets.
+    used: RefCell<HashSet<String>>, // Tokens of variables ever read.
+    warnings: RefCell<Vec<Warning>>,
+    index: RefCell<Index>,
 }
 
 impl SemAnalyzer {
-    /// Runs semantic analysis on the given program.
-    pub fn run_on(prog: &mut Program) {
-        Self::new().analyze(prog);
+    /// Runs semantic analysis on the given program, returns the warnings.
+    pub fn run_on(prog: &mut Program) -> Vec<Warning> {
+        Self::run_with_index(prog).0
+    }
+
+    /// Runs semantic analysis on the given program, returns the warnings
+    /// along with the index of symbols.
+    pub fn run_with_index(prog: &mut Program) -> (Vec<Warning>, Index) {
+        let mut analyzer = Self::new(prog.2.clone());
+        analyzer.analyze(prog);
+        let mut index = analyzer.index.take();
+        // Constant expressions may be evaluated more than once.
+        index.refs.sort_by_key(|(span, _)| span.begin);
+        index.refs.dedup_by_key(|(span, _)| span.begin);
+        (analyzer.warnings.take(), index)
+    }
+
+    /// Checks global symbols among translation units,
+    /// each given with its path. All units must have been analyzed.
+    pub fn check_units(units: &[(&Program, &str)]) {
+        link::check_linkage(units);
     }
 
     /// Creates a new semantic analyzer.
-    fn new() -> Self {
+    fn new(line_map: LineMap) -> Self {
         Self {
             symtabs: VecDeque::new(),
             ident_cnt: HashMap::new(),
+            globals: HashSet::new(),
+            exprs: RefCell::new(Exprs::default()),
+            line_map,
+            pos: Cell::new(0),
+            locals: HashMap::new(),
+            used: RefCell::new(HashSet::new()),
+            warnings: RefCell::new(vec![]),
+            index: RefCell::new(Index::default()),
         }
     }
 
@@ -38,15 +86,76 @@ impl SemAnalyzer {
         self.symtabs.push_front(SymTab::new());
     }
 
-    /// Exits the current scope.
+    /// Exits the current scope, warning about local variables never read.
     fn exit_scope(&mut self) {
-        self.symtabs.pop_front();
+        let table = self.symtabs.pop_front().unwrap();
+        table.iter().for_each(|(ident, symbol)| {
+            let Some(token) = symbol.token() else {
+                return;
+            };
+            if let Some(offset) = self.locals.get(token) {
+                if !self.used.borrow().contains(token) {
+                    let msg = format!("unused variable {ident}");
+                    self.warn(Lint::UnusedVariable, *offset, msg);
+                }
+            }
+        });
+    }
+
+    /// Records a warning at the given offset.
+    fn warn(&self, lint: Lint, offset: usize, msg: String) {
+        self.warnings.borrow_mut().push(Warning { lint, offset, msg });
+    }
+
+    /// Records the variable just declared in a local scope at the given offset,
+    /// which should be read somewhere, and should not shadow a global one.
+    fn declare_local(&mut self, ident: &str, offset: usize) {
+        if self.is_global_scope() {
+            return;
+        }
+        let token = self.token_of(ident).expect("Unexpected symbol");
+        self.locals.insert(token.clone(), offset);
+        match self.symtabs.back().unwrap().get(ident) {
+            None | Some(Function { .. }) => {}
+            Some(_) => {
+                let msg = format!("declaration of {ident} shadows a global variable");
+                self.warn(Lint::ShadowGlobal, offset, msg);
+            }
+        }
+    }
+
+    /// Returns the token of the given variable, or `None` if it is not one.
+    fn token_of(&self, ident: &str) -> Option<&String> {
+        let symbol = self.symtabs.iter().find_map(|table| table.get(ident));
+        symbol.and_then(|symbol| symbol.token())
+    }
+
+    /// Marks the given variable as read.
+    fn mark_used(&self, ident: &str) {
+        if let Some(token) = self.token_of(ident) {
+            self.used.borrow_mut().insert(token.clone());
+        }
+    }
+
+    /// Marks the given variable as never read.
+    fn unmark_used(&self, ident: &str) {
+        if let Some(token) = self.token_of(ident) {
+            self.used.borrow_mut().remove(token);
+        }
+    }
+
+    /// Returns whether the given variable has been read.
+    fn is_used(&self, ident: &str) -> bool {
+        self.token_of(ident).is_some_and(|token| self.used.borrow().contains(token))
     }
 
     /// Traverses the symbol table stack and returns the symbol
     /// corresponding to the given identifier.
     fn symbol(&self, ident: &str) -> &Symbol {
-        self.symtabs.iter().find_map(|table| table.get(ident)).unwrap()
+        self.symtabs
+            .iter()
+            .find_map(|table| table.get(ident))
+            .unwrap_or_else(|| error!(self, "Use of undeclared identifier {ident}"))
     }
 
     /// Converts the identifier to its mangled name.
@@ -54,36 +163,200 @@ impl SemAnalyzer {
         let mangled = match self.symbol(ident) {
             Int { token } => token,
             ConstInt { token, .. } => token,
-            IntArray { token } => token,
+            IntArray { token, .. } => token,
+            ConstIntArray { token, .. } => token,
+            Function { .. } => error!(self, "Function {ident} used as a variable"),
         };
         *ident = mangled.to_string();
     }
 
-    /// Returns the value of the given identifier,
-    /// which must be a constant integer.
-    fn value(&self, ident: &str) -> i32 {
-        if let ConstInt { value, .. } = self.symbol(ident) {
-            return *value;
-        } else {
-            panic!("Get value of non-const symbol {ident}");
+    /// Returns the value of the given identifier if it is a constant integer.
+    /// Mangled names are accepted, which are never constants.
+    fn const_value(&self, ident: &str) -> Option<i32> {
+        match self.symtabs.iter().find_map(|table| table.get(ident)) {
+            Some(ConstInt { value, .. }) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the element of the given constant array at the given indices,
+    /// if it is a constant array, and all indices are known and in range.
+    fn const_elem(&self, ident: &str, indices: &[Option<i32>]) -> Option<i32> {
+        let Some(ConstIntArray { ty: Type::Array(sizes), values, .. }) =
+            self.symtabs.iter().find_map(|table| table.get(ident))
+        else {
+            return None;
+        };
+        if indices.len() != sizes.len() {
+            return None;
         }
+        let offset = indices.iter().zip(sizes).try_fold(0, |acc, (index, size)| {
+            let index = (*index)?;
+            (0..*size).contains(&index).then(|| acc * *size as usize + index as usize)
+        })?;
+        Some(values.get(&offset).copied().unwrap_or(0))
     }
 
-    /// Returns whether the given identifier is a constant integer.
+    /// Returns whether the given identifier is a constant, which cannot be assigned.
     fn is_const(&self, ident: &str) -> bool {
-        matches!(self.symbol(ident), ConstInt { .. })
+        matches!(self.symbol(ident), ConstInt { .. } | ConstIntArray { .. })
+    }
+
+    /// Returns the source location of the given expression, as `file:line`.
+    fn locate(&self, id: ExprId) -> String {
+        self.line_map.locate(self.exprs.borrow().info(id).span.begin)
+    }
+
+    /// Records the type and the constant value, if any, of the given expression.
+    fn annotate(&self, id: ExprId, ty: Type, value: Option<i32>) {
+        let mut exprs = self.exprs.borrow_mut();
+        let info = exprs.info_mut(id);
+        info.ty = Some(ty);
+        info.is_const = value.is_some();
+        info.value = value;
+    }
+
+    /// Returns the values of the given folded expressions.
+    fn values(&self, ids: &[ExprId]) -> Vec<i32> {
+        let exprs = self.exprs.borrow();
+        ids.iter().map(|id| exprs.value(*id)).collect()
+    }
+
+    /// Returns the type of the given variable.
+    fn var_type(&self, ident: &str) -> Type {
+        match self.symbol(ident) {
+            Int { .. } | ConstInt { .. } => Type::Int,
+            IntArray { ty, .. } | ConstIntArray { ty, .. } => ty.clone(),
+            Function { .. } => error!(self, "Function {ident} used as a variable"),
+        }
+    }
+
+    /// Returns whether the current scope is the global scope.
+    fn is_global_scope(&self) -> bool {
+        self.symtabs.len() == 1
+    }
+
+    /// Records the names of all global symbols in the given program,
+    /// so that no local token collides with them.
+    fn reserve_globals(&mut self, prog: &Program) {
+        prog.0.iter().for_each(|unit| match unit {
+            CompUnit::VarDecl(decl) => decl.var_defs.iter().for_each(|def| {
+                let (VarDef::Scalar(ident, ..) | VarDef::Array(ident, ..)) = def;
+                self.globals.insert(format!("@{ident}"));
+            }),
+            CompUnit::FuncDef(FuncDef { ident, .. })
+            | CompUnit::FuncDecl(FuncDecl { ident, .. }) => {
+                self.globals.insert(format!("@{ident}"));
+            }
+            CompUnit::Error(..) => {}
+        });
     }
 
     /// Returns the next token of the given identifier.
+    /// Global symbols keep their names for linkage with other
+    /// translation units; local ones are suffixed to be unique.
     fn next_token(&mut self, ident: &str) -> String {
+        if self.is_global_scope() {
+            return format!("@{ident}");
+        }
         let cnt = self.ident_cnt.entry(ident.to_string()).or_default();
-        let token = format!("@{ident}_{cnt}");
-        *cnt += 1;
-        token
+        loop {
+            let token = format!("@{ident}_{cnt}");
+            *cnt += 1;
+            // Parameters are also referred to with a `_f` suffix in Koopa.
+            let param = token.clone() + "_f";
+            if !self.globals.contains(&token) && !self.globals.contains(&param) {
+                return token;
+            }
+        }
+    }
+
+    /// Returns the return type and parameters of the given function.
+    fn function(&self, ident: &str) -> (BType, &[Type]) {
+        let symbol = self.symtabs.iter().find_map(|table| table.get(ident));
+        match symbol {
+            Some(Function { ret, params, .. }) => (*ret, params),
+            Some(_) => error!(self, "Called object {ident} is not a function"),
+            None => error!(self, "Implicit declaration of function {ident}"),
+        }
+    }
+
+    /// Returns the types of the given parameters, whose sizes must have been folded.
+    fn param_types(params: &[FuncFParam], exprs: &Exprs) -> Vec<Type> {
+        params
+            .iter()
+            .map(|param| match param {
+                FuncFParam::Scalar(..) => Type::Int,
+                FuncFParam::Array(_, _, sizes, _) => {
+                    Type::Pointer(sizes.iter().map(|size| exprs.value(*size)).collect())
+                }
+            })
+            .collect()
+    }
+
+    /// Inserts the runtime library functions into the global scope.
+    fn insert_lib_funcs(&mut self) {
+        use Type::*;
+        let array = || Pointer(vec![]);
+        [
+            ("getint", BType::Int, vec![]),
+            ("getch", BType::Int, vec![]),
+            ("getarray", BType::Int, vec![array()]),
+            ("putint", BType::Void, vec![Int]),
+            ("putch", BType::Void, vec![Int]),
+            ("putarray", BType::Void, vec![Int, array()]),
+            ("putstr", BType::Void, vec![array()]), // Takes a string literal.
+            ("_sysy_starttime", BType::Void, vec![Int]),
+            ("_sysy_stoptime", BType::Void, vec![Int]),
+        ]
+        .into_iter()
+        .for_each(|(ident, ret, params)| {
+            self.insert_function(ident.to_string(), ret, params, true);
+            self.index_decl(ident, None, true);
+        });
+    }
+
+    /// Inserts a function symbol into the global scope, checking that it agrees
+    /// with any previous declaration. Later declarations don't undo definitions.
+    fn insert_function(&mut self, ident: String, ret: BType, params: Vec<Type>, is_def: bool) {
+        let is_defined = match self.symtabs.back().unwrap().get(&ident) {
+            None => is_def,
+            Some(Function {
+                ret: prev_ret,
+                params: prev_params,
+                is_defined,
+            }) => {
+                if *prev_ret != ret || *prev_params != params {
+                    error!(self, "Conflicting types for function {ident}");
+                }
+                if is_def && *is_defined {
+                    error!(self, "Redefinition of function {ident}");
+                }
+                is_def || *is_defined
+            }
+            Some(_) => error!(self, "{ident} redeclared as a function"),
+        };
+        self.symtabs.back_mut().unwrap().insert(
+            ident,
+            Function {
+                ret,
+                params,
+                is_defined,
+            },
+        );
+    }
+
+    /// Panics if the given identifier names a function in the current scope,
+    /// which a variable cannot be redeclared as.
+    fn check_not_function(&self, ident: &str) {
+        if let Some(Function { .. }) = self.symtabs.front().unwrap().get(ident) {
+            error!(self, "Function {ident} redeclared as a variable");
+        }
     }
 
     /// Inserts an integer symbol into the symbol table of the current scope.
     fn insert_int(&mut self, ident: String) {
+        self.check_not_function(&ident);
         let token = self.next_token(&ident);
         self.symtabs
             .front_mut()
@@ -94,6 +367,7 @@ impl SemAnalyzer {
     /// Inserts a constant integer symbol into the symbol table
     /// of the current scope.
     fn insert_const_int(&mut self, ident: String, value: i32) {
+        self.check_not_function(&ident);
         let token = self.next_token(&ident);
         self.symtabs
             .front_mut()
@@ -101,13 +375,76 @@ impl SemAnalyzer {
             .insert(ident, ConstInt { token, value });
     }
 
-    /// Inserts an integer array symbol into the symbol table
-    /// of the current scope.
-    fn insert_int_array(&mut self, ident: String) {
+    /// Inserts an integer array symbol of the given type
+    /// into the symbol table of the current scope.
+    fn insert_int_array(&mut self, ident: String, ty: Type) {
+        self.check_not_function(&ident);
         let token = self.next_token(&ident);
         self.symtabs
             .front_mut()
             .unwrap()
-            .insert(ident, IntArray { token });
+            .insert(ident, IntArray { token, ty });
+    }
+
+    /// Records the declaration of the given symbol at the given span,
+    /// which is a reference, too. A function is located at its definition,
+    /// or its first declaration if not defined.
+    fn index_decl(&self, ident: &str, span: Option<Span>, is_def: bool) {
+        let key = self.key_of(ident);
+        let detail = self.detail(ident);
+        let index = &mut *self.index.borrow_mut();
+        let info = index.symbols.entry(key.clone()).or_insert(SymbolInfo {
+            name: ident.to_string(),
+            detail,
+            decl: None,
+        });
+        if is_def || info.decl.is_none() {
+            info.decl = span;
+        }
+        if let Some(span) = span {
+            index.refs.push((span, key));
+        }
+    }
+
+    /// Records a reference to the given identifier at the given span,
+    /// if it names a symbol. Mangled names never do.
+    fn index_ref(&self, ident: &str, span: Span) {
+        if self.symtabs.iter().any(|table| table.get(ident).is_some()) {
+            let key = self.key_of(ident);
+            self.index.borrow_mut().refs.push((span, key));
+        }
+    }
+
+    /// Returns the key of the given symbol in the index.
+    fn key_of(&self, ident: &str) -> String {
+        self.token_of(ident).map_or(ident, String::as_str).to_string()
+    }
+
+    /// Returns the declaration of the given symbol as text.
+    fn detail(&self, ident: &str) -> String {
+        // e.g. `int[][3]` => `int a[][3]`
+        let declarator = |ty: &Type| ty.to_string().replacen("int", &format!("int {ident}"), 1);
+        match self.symbol(ident) {
+            Int { .. } => format!("int {ident}"),
+            ConstInt { value, .. } => format!("const int {ident} = {value}"),
+            IntArray { ty, .. } => declarator(ty),
+            ConstIntArray { ty, .. } => format!("const {}", declarator(ty)),
+            Function { ret, params, .. } => {
+                let params = params.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+                format!("{ret} {ident}({})", params.join(", "))
+            }
+        }
+    }
+
+    /// Turns the given array in the current scope into a constant array
+    /// of the given values. Constant arrays are placed in read-only data,
+    /// see the midend.
+    fn make_const_array(&mut self, ident: &str, values: BTreeMap<usize, i32>) {
+        let table = self.symtabs.front_mut().unwrap();
+        let Some(IntArray { token, ty }) = table.get(ident) else {
+            panic!("Unexpected symbol");
+        };
+        let (token, ty) = (token.clone(), ty.clone());
+        table.insert(ident.to_string(), ConstIntArray { token, ty, values });
     }
 }
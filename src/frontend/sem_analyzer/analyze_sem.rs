@@ -5,9 +5,12 @@
 use super::eval::Eval;
 use super::flatten::flatten;
 use super::fold::Fold;
+use super::lower::lower_putf;
 use super::update::Update;
 use super::SemAnalyzer;
 use crate::frontend::ast::*;
+use crate::frontend::lint::Lint;
+use std::collections::BTreeMap;
 
 pub trait Analyze<T> {
     /// Analyzes the given AST node, maybe changes it.
@@ -16,23 +19,35 @@ pub trait Analyze<T> {
 
 impl Analyze<Program> for SemAnalyzer {
     fn analyze(&mut self, prog: &mut Program) {
+        self.reserve_globals(prog);
+        self.exprs.replace(std::mem::take(&mut prog.1));
         self.enter_scope(); // Global scope.
+        self.insert_lib_funcs();
         prog.0.iter_mut().for_each(|unit| self.analyze(unit));
         self.exit_scope();
+        prog.1 = self.exprs.take();
     }
 }
 
 impl Analyze<CompUnit> for SemAnalyzer {
     fn analyze(&mut self, comp_unit: &mut CompUnit) {
         use CompUnit::*;
+        let span = match comp_unit {
+            VarDecl(var_decl) => var_decl.span,
+            FuncDef(func_def) => func_def.span,
+            FuncDecl(func_decl) => func_decl.span,
+            Error(span) => *span,
+        };
+        self.pos.set(span.begin);
         match comp_unit {
             VarDecl(var_decl) => self.analyze(var_decl),
             FuncDef(func_def) => self.analyze(func_def),
+            FuncDecl(func_decl) => self.analyze(func_decl),
+            Error(_) => {}
         }
     }
 }
 
-
 impl Analyze<VarDecl> for SemAnalyzer {
     fn analyze(&mut self, decl: &mut VarDecl) {
         use VarDef::*;
@@ -40,29 +55,47 @@ impl Analyze<VarDecl> for SemAnalyzer {
         let is_const = decl.is_const;
 
         decl.var_defs.iter_mut().for_each(|def| {
+            if decl.is_extern {
+                let initialized = match def {
+                    Scalar(_, opt_exp, _) => opt_exp.is_some(),
+                    Array(_, _, opt_init, _) => opt_init.is_some(),
+                };
+                if initialized {
+                    error!(self, "Extern variable cannot be initialized");
+                }
+            }
             match def {
-                Scalar(ident, opt_exp) => {
+                Scalar(ident, opt_exp, span) => {
+                    self.pos.set(span.begin);
                     if is_const {
-                        let value = self.eval(opt_exp.as_ref().unwrap());
+                        let exp = opt_exp.as_ref().unwrap();
+                        let value = self.eval(exp).unwrap_or_else(|| {
+                            error!(self, "Initializer of constant {ident} is not a constant expression")
+                        });
                         self.insert_const_int(ident.clone(), value);
+                        self.declare_local(ident, span.begin);
+                        self.index_decl(ident, Some(*span), true);
                         return;
                     }
                     self.insert_int(ident.clone());
+                    self.declare_local(ident, span.begin);
+                    self.index_decl(ident, Some(*span), true);
                     self.mangle(ident);
                     if let Some(exp) = opt_exp {
                         if is_global {
                             self.fold(exp);
                         } else {
-                            self.update(exp);
+                            self.update_value(*exp);
                         }
                     }
                 }
-                Array(ident, sizes, opt_init) => {
-                    self.insert_int_array(ident.clone());
-                    self.mangle(ident);
+                Array(ident, sizes, opt_init, span) => {
+                    self.pos.set(span.begin);
                     sizes.iter_mut().for_each(|size| {
                         self.fold(size);
                     });
+                    let sizes = self.values(sizes);
+                    self.insert_int_array(ident.clone(), Type::Array(sizes.clone()));
                     if let Some(init) = opt_init {
                         if is_global || is_const {
                             self.fold(init);
@@ -73,8 +106,20 @@ impl Analyze<VarDecl> for SemAnalyzer {
                         let InitList::List(list) = init else {
                             panic!("Unexpected arm");
                         };
-                        *init = flatten(list, sizes);
+                        *init = flatten(list, &sizes).unwrap_or_else(|err| error!(self, "{err}"));
+                    }
+                    // Values are only known after folding, during which the array
+                    // itself is not a constant, e.g. `const int a[2] = {1, a[0]};`.
+                    if is_const {
+                        let values = match opt_init {
+                            Some(InitList::Flat(runs)) => self.exprs.borrow().run_values(runs),
+                            _ => BTreeMap::new(),
+                        };
+                        self.make_const_array(ident, values);
                     }
+                    self.declare_local(ident, span.begin);
+                    self.index_decl(ident, Some(*span), true);
+                    self.mangle(ident);
                 }
             }
         });
@@ -84,8 +129,24 @@ impl Analyze<VarDecl> for SemAnalyzer {
 impl Analyze<FuncDef> for SemAnalyzer {
     fn analyze(&mut self, func_def: &mut FuncDef) {
         self.enter_scope(); // Function scope.
-        func_def.2.iter_mut().for_each(|param| self.analyze(param));
-        self.analyze(&mut func_def.3); // Body.
+        func_def.params.iter_mut().for_each(|param| self.analyze(param));
+        // Inserted before the body, so that it can call itself.
+        let params = Self::param_types(&func_def.params, &self.exprs.borrow());
+        self.insert_function(func_def.ident.clone(), func_def.btype, params, true);
+        self.index_decl(&func_def.ident, Some(func_def.name_span), true);
+        self.analyze(&mut func_def.block); // Body.
+        self.exit_scope();
+        self.check_flow(&func_def.block);
+    }
+}
+
+impl Analyze<FuncDecl> for SemAnalyzer {
+    fn analyze(&mut self, func_decl: &mut FuncDecl) {
+        self.enter_scope(); // Prototype scope.
+        func_decl.params.iter_mut().for_each(|param| self.analyze(param));
+        let params = Self::param_types(&func_decl.params, &self.exprs.borrow());
+        self.insert_function(func_decl.ident.clone(), func_decl.btype, params, false);
+        self.index_decl(&func_decl.ident, Some(func_decl.name_span), false);
         self.exit_scope();
     }
 }
@@ -94,14 +155,19 @@ impl Analyze<FuncFParam> for SemAnalyzer {
     fn analyze(&mut self, param: &mut FuncFParam) {
         use FuncFParam::*;
         match param {
-            Scalar(_, ident) => {
+            Scalar(_, ident, span) => {
+                self.pos.set(span.begin);
                 self.insert_int(ident.clone());
+                self.index_decl(ident, Some(*span), true);
                 self.mangle(ident);
             }
-            Array(_, ident, sizes) => {
-                self.insert_int_array(ident.clone());
-                self.mangle(ident);
+            Array(_, ident, sizes, span) => {
+                self.pos.set(span.begin);
                 sizes.iter_mut().for_each(|exp| self.fold(exp));
+                let sizes = self.values(sizes);
+                self.insert_int_array(ident.clone(), Type::Pointer(sizes));
+                self.index_decl(ident, Some(*span), true);
+                self.mangle(ident);
             }
         }
     }
@@ -119,7 +185,10 @@ impl Analyze<BlockItem> for SemAnalyzer {
     fn analyze(&mut self, block_item: &mut BlockItem) {
         use BlockItem::*;
         match block_item {
-            Stmt(stmt) => self.analyze(stmt),
+            Stmt(stmt, span) => {
+                self.pos.set(span.begin);
+                self.analyze(stmt);
+            }
             VarDecl(decl) => self.analyze(decl),
         }
     }
@@ -130,30 +199,72 @@ impl Analyze<Stmt> for SemAnalyzer {
         use Stmt::*;
         match stmt {
             Assign(lval, exp) => {
+                let ident = self.exprs.borrow().var_of(*lval).clone();
+                if self.is_const(&ident) {
+                    error!(self, "Assignment of read-only variable {ident}");
+                }
+                // HACK: Assigning to a scalar doesn't read it, undo the marking in `update`.
+                let is_read = self.is_used(&ident);
+                let is_scalar = matches!(self.exprs.borrow()[*lval], Expr::Var(..));
                 self.update(lval);
-                self.update(exp);
+                if is_scalar && !is_read {
+                    self.unmark_used(&ident);
+                }
+                let ty = self.exprs.borrow().ty(*lval).clone();
+                if ty != Type::Int {
+                    error!(self, "Assignment to {ident} of type `{ty}`");
+                }
+                self.update_value(*exp);
+            }
+            Empty | Error => {}
+            Exp(exp) => {
+                let expr = self.exprs.borrow()[*exp].clone();
+                match expr {
+                    Expr::Call(ident, args) if ident == "putf" => {
+                        let mut exprs = self.exprs.borrow_mut();
+                        let span = exprs.info(*exp).span;
+                        let block = lower_putf(&args, &mut exprs, span);
+                        drop(exprs);
+                        let block = block.unwrap_or_else(|err| error!(self, "{err}"));
+                        *stmt = Block(block);
+                        self.analyze(stmt);
+                    }
+                    _ => self.update(exp),
+                }
             }
-            Empty => {}
-            Exp(exp) => self.update(exp),
             Block(block) => self.analyze(block),
             If(exp, stmt, opt_stmt) => {
-                self.update(exp);
+                self.update_condition(*exp);
                 self.analyze(stmt.as_mut());
                 if let Some(stmt) = opt_stmt {
                     self.analyze(stmt.as_mut());
                 }
             }
             While(exp, stmt) => {
-                self.update(exp);
+                self.update_condition(*exp);
                 self.analyze(stmt.as_mut());
             }
             Break => {}
             Continue => {}
             Return(opt_exp) => {
                 if let Some(exp) = opt_exp {
-                    self.update(exp);
+                    self.update_value(*exp);
                 }
             }
         }
     }
 }
+
+impl SemAnalyzer {
+    /// Updates the given condition, warning if it is always true or false.
+    /// Literals are taken as intended, like `while (1)`.
+    fn update_condition(&self, exp: ExprId) {
+        let is_lit = matches!(self.exprs.borrow()[exp], Expr::Lit(..));
+        self.update_value(exp);
+        let info = self.exprs.borrow().info(exp).clone();
+        if let (false, Some(value)) = (is_lit, info.value) {
+            let msg = format!("condition is always {}", value != 0);
+            self.warn(Lint::ConstantCondition, info.span.begin, msg);
+        }
+    }
+}
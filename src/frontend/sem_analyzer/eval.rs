@@ -1,143 +1,85 @@
 //! Evaluate constant expressions.
-
-use core::panic;
+//! Evaluation fails on non-constant expressions, which contain
+//! variables, function calls or non-constant indexing.
+//! Arithmetic wraps on overflow, just like the generated RISC-V code,
+//! while division by zero and `INT_MIN / -1` are reported as errors.
 
 use super::SemAnalyzer;
 use crate::frontend::ast::*;
 
-macro_rules! panic_arm {
-    () => {
-        panic!("Unexpected arm")
-    };
-}
-
 pub trait Eval<T> {
-    /// Evaluates the given expression.
-    fn eval(&self, target: &T) -> i32;
-}
-
-impl Eval<Exp> for SemAnalyzer {
-    fn eval(&self, exp: &Exp) -> i32 {
-        use Exp::*;
-        match exp {
-            LOrExp(lor) => self.eval(lor),
-            Number(..) => panic_arm!(),
-        }
-    }
-}
-
-impl Eval<LVal> for SemAnalyzer {
-    fn eval(&self, lval: &LVal) -> i32 {
-        use LVal::*;
-        match lval {
-            Ident(ident) => self.value(ident),
-            ArrayElem(..) => panic_arm!(),
-        }
-    }
+    /// Evaluates the given expression,
+    /// returns `None` if it is not a constant expression.
+    fn eval(&self, target: &T) -> Option<i32>;
 }
 
-impl Eval<PrimaryExp> for SemAnalyzer {
-    fn eval(&self, exp: &PrimaryExp) -> i32 {
-        use PrimaryExp::*;
-        match exp {
-            BracketedExp(bexp) => self.eval(bexp.as_ref()),
-            Number(num) => self.eval(num),
-            LVal(lval) => self.eval(lval),
+impl Eval<ExprId> for SemAnalyzer {
+    fn eval(&self, id: &ExprId) -> Option<i32> {
+        let expr = self.exprs.borrow()[*id].clone();
+        match expr {
+            // The right operand is not evaluated if short-circuited,
+            // so `0 && 1 / 0` is fine.
+            Expr::Binary(BinaryOp::And, lhs, _) if self.eval(&lhs)? == 0 => Some(0),
+            Expr::Binary(BinaryOp::Or, lhs, _) if self.eval(&lhs)? != 0 => Some(1),
+            Expr::Binary(op, lhs, rhs) => {
+                let (x, y) = (self.eval(&lhs)?, self.eval(&rhs)?);
+                match eval_binary(op, x, y) {
+                    Ok(value) => Some(value),
+                    Err(err) => panic!("{}: {err} in constant expression", self.locate(*id)),
+                }
+            }
+            Expr::Unary(op, exp) => Some(eval_unary(op, self.eval(&exp)?)),
+            Expr::Call(..) => None,
+            Expr::Index(..) => {
+                let ident = self.exprs.borrow().var_of(*id).clone();
+                let var = self.exprs.borrow().root(*id);
+                self.mark_used(&ident);
+                self.index_ref(&ident, self.exprs.borrow().info(var).span);
+                let indices = self.exprs.borrow().indices(*id);
+                let indices = indices.iter().map(|index| self.eval(index)).collect::<Vec<_>>();
+                self.const_elem(&ident, &indices)
+            }
+            Expr::Var(ident) => {
+                self.mark_used(&ident);
+                self.index_ref(&ident, self.exprs.borrow().info(*id).span);
+                self.const_value(&ident)
+            }
+            Expr::Lit(Lit::Int(value)) => Some(value),
+            Expr::Lit(Lit::Str(..)) => None,
         }
     }
 }
 
-impl Eval<Number> for SemAnalyzer {
-    fn eval(&self, num: &Number) -> i32 {
-        num.0
+/// Applies a binary operator to constant operands, wrapping on overflow.
+/// Both operands are evaluated, even for `&&` and `||`.
+/// Division by zero and `INT_MIN / -1` are undefined, which fail.
+pub fn eval_binary(op: BinaryOp, x: i32, y: i32) -> Result<i32, &'static str> {
+    use BinaryOp::*;
+    match op {
+        Div | Rem if y == 0 => Err("division by zero"),
+        Div | Rem if x == i32::MIN && y == -1 => Err("integer overflow in division"),
+        Mul => Ok(x.wrapping_mul(y)),
+        Div => Ok(x / y),
+        Rem => Ok(x % y),
+        Add => Ok(x.wrapping_add(y)),
+        Sub => Ok(x.wrapping_sub(y)),
+        Lt => Ok((x < y) as i32),
+        Le => Ok((x <= y) as i32),
+        Gt => Ok((x > y) as i32),
+        Ge => Ok((x >= y) as i32),
+        Eq => Ok((x == y) as i32),
+        Ne => Ok((x != y) as i32),
+        And => Ok((x != 0 && y != 0) as i32),
+        Or => Ok((x != 0 || y != 0) as i32),
     }
 }
 
-impl Eval<UnaryExp> for SemAnalyzer {
-    fn eval(&self, exp: &UnaryExp) -> i32 {
-        use UnaryExp::*;
-        use UnaryOp::*;
-        match exp {
-            Primary(bexp) => self.eval(bexp.as_ref()),
-            FuncCall(..) => panic_arm!(),
-            OpUnary(op, bexp) => {
-                let value = self.eval(bexp.as_ref());
-                match op {
-                    Plus => value,
-                    Minus => -value,
-                    Not => (value == 0) as i32,
-                }
-            }
-        }
+/// Applies a unary operator to a constant operand, wrapping on overflow.
+pub fn eval_unary(op: UnaryOp, x: i32) -> i32 {
+    use UnaryOp::*;
+    match op {
+        Plus => x,
+        Minus => x.wrapping_neg(),
+        Not => (x == 0) as i32,
     }
 }
-
-// This saves me from writing a lot of similar code.
-// I really hope lalrpop could support defining rule precedence.
-macro_rules! impl_eval_binary_op {
-    ($T:ty, $arm1:tt, $arm2:tt, $clo:tt) => {
-        impl Eval<$T> for SemAnalyzer {
-            fn eval(&self, exp: &$T) -> i32 {
-                use $T::*;
-                match exp {
-                    $arm1(bexp) => self.eval(bexp.as_ref()),
-                    $arm2(bexps, bexp) => {
-                        let lhs = self.eval(bexps.as_ref());
-                        let rhs = self.eval(bexp.as_ref());
-                        $clo(lhs, rhs)
-                    }
-                }
-            }
-        }
-    };
-    ($T:ty, $arm1:tt, $arm2:tt, $O:ty,
-        op_rule: $($arm:tt => $clo:tt,)*) => {
-        impl Eval<$T> for SemAnalyzer {
-            fn eval(&self, exp: &$T) -> i32 {
-                use $T::*;
-                use $O::*;
-                match exp {
-                    $arm1(bexp) => self.eval(bexp.as_ref()),
-                    $arm2(bexps, op, bexp) => {
-                        let lhs = self.eval(bexps.as_ref());
-                        let rhs = self.eval(bexp.as_ref());
-                        match op {
-                            $($arm => $clo(lhs, rhs),)*
-                        }
-                    }
-                }
-            }
-        }
-    };
-}
-
-impl_eval_binary_op!(MulExp, Unary, MulOpUnary, MulOp,
-    op_rule:
-        Mul => (|x, y| x * y),
-        Div => (|x, y| x / y),
-        Rem => (|x, y| x % y),
-);
-
-impl_eval_binary_op!(AddExp, Mul, AddOpMul, AddOp,
-    op_rule:
-        Add => (|x, y| x + y),
-        Sub => (|x, y| x - y),
-);
-
-impl_eval_binary_op!(RelExp, Add, RelOpAdd, RelOp,
-    op_rule:
-        Le => (|x, y| (x <= y) as i32),
-        Lt => (|x, y| (x < y) as i32),
-        Ge => (|x, y| (x >= y) as i32),
-        Gt => (|x, y| (x > y) as i32),
-);
-
-impl_eval_binary_op!(EqExp, Rel, EqOpRel, EqOp,
-    op_rule:
-        Eq => (|x, y| (x == y) as i32),
-        Ne => (|x, y| (x != y) as i32),
-);
-
-impl_eval_binary_op!(LAndExp, Eq, LAndEq, (|x, y| (x != 0 && y != 0) as i32));
-
-impl_eval_binary_op!(LOrExp, LAnd, LOrLAnd, (|x, y| (x != 0 || y != 0) as i32));
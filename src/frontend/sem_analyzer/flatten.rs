@@ -1,5 +1,6 @@
-//! Flatten initializer lists and fill in omitted values with zeros.
-//! This can be space-consuming if you create a large array with only zeros.
+//! Flatten initializer lists into runs of explicit values.
+//! Omitted values are zeros, which are left out of the runs,
+//! so a large array with only a few values stays small.
 
 // This module, along with every piece of code in midend that uses it,
 // is written very awfully.
@@ -7,47 +8,64 @@
 // which allows for more graceful handling of C style aggregate initializer.
 // Or possibly those C syntax rules are simply a mess.
 
-use crate::frontend::ast::{self, Exp, InitList};
+use crate::frontend::ast::{InitList, InitRun};
 
-pub fn flatten(list: &[InitList], sizes: &[Exp]) -> InitList {
+/// Flattens the given list for an array of the given sizes.
+/// Fails if there are excess elements.
+pub fn flatten(list: &[InitList], sizes: &[i32]) -> Result<InitList, &'static str> {
     // e.g. [1, 2, 3] => [3, 3 * 2, 3 * 2 * 1]
     let dims = sizes
         .iter()
         .rev()
         .scan(1, |acc, size| {
-            *acc *= size.value() as usize;
+            *acc *= *size as usize;
             Some(*acc)
         })
         .collect::<Vec<_>>();
 
-    let mut flat = Vec::with_capacity(dims.last().unwrap().clone());
-    flatten_helper(&mut flat, dims, list);
-    InitList::Flat(flat)
+    let mut runs = vec![];
+    flatten_helper(&mut runs, 0, dims, list)?;
+    Ok(InitList::Flat(runs))
 }
 
-fn flatten_helper(dst: &mut Vec<Exp>, dims: Vec<usize>, list: &[InitList]) {
+/// Flattens the given list starting at the given offset,
+/// returns the offset right after the sub-array it initializes.
+fn flatten_helper(
+    runs: &mut Vec<InitRun>,
+    begin: usize,
+    dims: Vec<usize>,
+    list: &[InitList],
+) -> Result<usize, &'static str> {
     use InitList::*;
-    let begin_size = dst.len();
-    list.iter().for_each(|init| match init {
-        Exp(exp) => {
-            dst.push(exp.clone()); // NOTE: Overhead.
+    let mut pos = begin;
+    for init in list {
+        match init {
+            Exp(exp) => {
+                match runs.last_mut() {
+                    Some(InitRun(offset, values)) if *offset + values.len() == pos => {
+                        values.push(*exp);
+                    }
+                    _ => runs.push(InitRun(pos, vec![*exp])),
+                }
+                pos += 1;
+            }
+            List(sub_list) => {
+                let sub_dims = dims
+                    .iter()
+                    .take(dims.len() - 1)
+                    .map_while(|dim| if pos.is_multiple_of(*dim) { Some(*dim) } else { None })
+                    .collect::<Vec<_>>();
+                pos = flatten_helper(runs, pos, sub_dims, sub_list)?;
+            }
+            Flat(..) => {
+                panic!("Unexpected arm");
+            }
         }
-        List(sub_list) => {
-            let len = dst.len();
-            let sub_dims = dims
-                .iter()
-                .take(dims.len() - 1)
-                .map_while(|dim| if len % dim == 0 { Some(*dim) } else { None })
-                .collect::<Vec<_>>();
-            flatten_helper(dst, sub_dims, sub_list);
-        }
-        Flat(..) => {
-            panic!("Unexpected arm");
-        }
-    });
+    }
 
-    let rest = dims.last().unwrap() + begin_size - dst.len();
-    (0..rest).for_each(|_| {
-        dst.push(ast::Exp::from_number(0));
-    });
+    let end = begin + dims.last().unwrap();
+    if pos > end {
+        return Err("Excess elements in array initializer");
+    }
+    Ok(end)
 }
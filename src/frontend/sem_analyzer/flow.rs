@@ -0,0 +1,206 @@
+//! Flow-sensitive checks on function bodies, run after semantic analysis,
+//! i.e., on mangled names and lowered statements, but before the midend.
+//! No CFG of basic blocks is built, as the Koopa IR has: control flow in
+//! SysY is structured, without `goto`, so its edges follow from the AST,
+//! and dataflow is run along them in a single walk. Each statement maps
+//! the set of variables definitely assigned before it to the set after it,
+//! or to `None` if it doesn't fall through, which gives reachability too.
+//! Edges out of conditions folded by `Eval` are left out, and `break`s join
+//! the exit of their loop. Loop bodies are walked once, without iterating
+//! to a fixpoint: sets only grow along a path, so what flows back to the
+//! loop head includes what enters it, and their intersection is the latter.
+//! Basic blocks of the Koopa IR would do as well, but they lack the spans
+//! warnings point at, and have unreachable blocks of the midend's own.
+
+use super::SemAnalyzer;
+use crate::frontend::ast::*;
+use crate::frontend::lint::{Lint, Warning};
+use std::collections::HashSet;
+
+/// Variables definitely assigned at some point, `None` if unreachable.
+type State = Option<HashSet<String>>;
+
+/// Returns the state where control flows from either of the given ones.
+fn join(lhs: State, rhs: State) -> State {
+    match (lhs, rhs) {
+        (None, state) | (state, None) => state,
+        (Some(lhs), Some(rhs)) => Some(lhs.intersection(&rhs).cloned().collect()),
+    }
+}
+
+pub struct FlowChecker<'a> {
+    exprs: &'a Exprs,
+    uninit: HashSet<String>, // Local scalars declared without initializers.
+    reported: HashSet<String>,
+    breaks: Vec<State>, // Joined states at `break`s, one per enclosing loop.
+    pub warnings: Vec<Warning>,
+}
+
+impl<'a> FlowChecker<'a> {
+    pub fn new(exprs: &'a Exprs) -> Self {
+        Self {
+            exprs,
+            uninit: HashSet::new(),
+            reported: HashSet::new(),
+            breaks: vec![],
+            warnings: vec![],
+        }
+    }
+
+    /// Checks the given function body.
+    pub fn check_body(&mut self, body: &Block) {
+        self.block(body, Some(HashSet::new()));
+    }
+
+    fn warn(&mut self, lint: Lint, offset: usize, msg: String) {
+        self.warnings.push(Warning { lint, offset, msg });
+    }
+
+    /// Only the first unreachable item of a reachable block is reported,
+    /// code behind a constant condition is left to `constant-condition`.
+    fn block(&mut self, block: &Block, mut state: State) -> State {
+        let mut reported = state.is_none();
+        block.0.iter().for_each(|item| {
+            if state.is_none() && !reported {
+                let offset = match item {
+                    BlockItem::Stmt(Stmt::Empty, _) => return,
+                    BlockItem::Stmt(_, span) => span.begin,
+                    BlockItem::VarDecl(decl) => match &decl.var_defs[0] {
+                        VarDef::Scalar(.., span) | VarDef::Array(.., span) => span.begin,
+                    },
+                };
+                self.warn(Lint::UnreachableCode, offset, "unreachable code".to_string());
+                reported = true;
+            }
+            state = match item {
+                BlockItem::Stmt(stmt, _) => self.stmt(stmt, state.take()),
+                BlockItem::VarDecl(decl) => self.decl(decl, state.take()),
+            };
+        });
+        state
+    }
+
+    fn decl(&mut self, decl: &VarDecl, state: State) -> State {
+        decl.var_defs.iter().for_each(|def| match def {
+            VarDef::Scalar(token, None, _) if !decl.is_const => {
+                self.uninit.insert(token.clone());
+            }
+            VarDef::Scalar(_, Some(exp), _) => self.read(*exp, &state),
+            VarDef::Array(_, _, Some(InitList::Flat(runs)), _) => {
+                runs.iter()
+                    .flat_map(|InitRun(_, values)| values)
+                    .for_each(|exp| self.read(*exp, &state));
+            }
+            _ => {}
+        });
+        state
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, state: State) -> State {
+        use Stmt::*;
+        match stmt {
+            Assign(lval, exp) => {
+                self.read(*exp, &state);
+                match &self.exprs[*lval] {
+                    Expr::Var(token) => state.map(|mut state| {
+                        state.insert(token.clone());
+                        state
+                    }),
+                    _ => {
+                        self.read(*lval, &state);
+                        state
+                    }
+                }
+            }
+            Empty | Error => state,
+            Exp(exp) => {
+                self.read(*exp, &state);
+                state
+            }
+            Block(block) => self.block(block, state),
+            If(exp, then, opt_else) => {
+                self.read(*exp, &state);
+                // Only one branch is reachable under a constant condition.
+                let always = self.exprs.info(*exp).value.map(|value| value != 0);
+                let branch = |taken: bool| {
+                    if always == Some(!taken) {
+                        None
+                    } else {
+                        state.clone()
+                    }
+                };
+                let then = self.stmt(then, branch(true));
+                let otherwise = match opt_else {
+                    Some(otherwise) => self.stmt(otherwise, branch(false)),
+                    None => branch(false),
+                };
+                join(then, otherwise)
+            }
+            While(exp, body) => {
+                self.read(*exp, &state);
+                let value = self.exprs.info(*exp).value;
+                let body_state = if value == Some(0) { None } else { state.clone() };
+                self.breaks.push(None);
+                self.stmt(body, body_state);
+                let breaks = self.breaks.pop().unwrap();
+                // Exits when the condition is false, or at `break`.
+                let exit = if matches!(value, Some(value) if value != 0) { None } else { state };
+                join(exit, breaks)
+            }
+            Break => {
+                let breaks = self.breaks.pop().unwrap();
+                self.breaks.push(join(breaks, state));
+                None
+            }
+            Continue => None,
+            Return(opt_exp) => {
+                if let Some(exp) = opt_exp {
+                    self.read(*exp, &state);
+                }
+                None
+            }
+        }
+    }
+
+    /// Reports variables read in the given expression before assignment.
+    fn read(&mut self, id: ExprId, state: &State) {
+        let Some(assigned) = state else {
+            return;
+        };
+        match &self.exprs[id] {
+            Expr::Binary(_, lhs, rhs) => {
+                self.read(*lhs, state);
+                self.read(*rhs, state);
+            }
+            Expr::Unary(_, exp) => self.read(*exp, state),
+            Expr::Call(_, args) => args.iter().for_each(|arg| self.read(*arg, state)),
+            Expr::Index(arr, index) => {
+                self.read(*arr, state);
+                self.read(*index, state);
+            }
+            Expr::Var(token) => {
+                if self.uninit.contains(token)
+                    && !assigned.contains(token)
+                    && self.reported.insert(token.clone())
+                {
+                    // Tokens of local variables are `@ident_N`.
+                    let ident = &token[1..token.rfind('_').unwrap()];
+                    let offset = self.exprs.info(id).span.begin;
+                    let msg = format!("variable {ident} may be used uninitialized");
+                    self.warn(Lint::Uninitialized, offset, msg);
+                }
+            }
+            Expr::Lit(..) => {}
+        }
+    }
+}
+
+impl SemAnalyzer {
+    /// Runs flow-sensitive checks on the given analyzed function body.
+    pub fn check_flow(&self, body: &Block) {
+        let exprs = self.exprs.borrow();
+        let mut checker = FlowChecker::new(&exprs);
+        checker.check_body(body);
+        self.warnings.borrow_mut().append(&mut checker.warnings);
+    }
+}
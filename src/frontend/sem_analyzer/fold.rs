@@ -1,7 +1,5 @@
 //! Fold AST nodes to constant values.
 
-use core::panic;
-
 use super::eval::Eval;
 use super::SemAnalyzer;
 use crate::frontend::ast::*;
@@ -22,15 +20,10 @@ impl Fold<InitList> for SemAnalyzer {
     }
 }
 
-impl Fold<Exp> for SemAnalyzer {
-    fn fold(&self, exp: &mut Exp) {
-        use Exp::*;
-        match exp {
-            LOrExp(lor) => {
-                let value = self.eval(lor);
-                exp.set_value(value);
-            }
-            Number(..) => panic!("Unexpected arm"),
-        }
+impl Fold<ExprId> for SemAnalyzer {
+    fn fold(&self, id: &mut ExprId) {
+        let value = self.eval(id).unwrap_or_else(|| error!(self, "Expected a constant expression"));
+        self.exprs.borrow_mut()[*id] = Expr::Lit(Lit::Int(value));
+        self.annotate(*id, Type::Int, Some(value));
     }
 }
@@ -0,0 +1,38 @@
+//! Index of symbols, recording where they are declared and referred to,
+//! for editor integration. Symbols are keyed by their tokens,
+//! or names for functions, which are never mangled.
+
+use crate::frontend::ast::Span;
+use std::collections::HashMap;
+
+/// Index of all symbols in a program.
+#[derive(Default)]
+pub struct Index {
+    pub symbols: HashMap<String, SymbolInfo>,
+    // Identifiers referring to symbols along with their keys, in source order.
+    // Declarations are references, too.
+    pub refs: Vec<(Span, String)>,
+}
+
+/// Information of a symbol.
+pub struct SymbolInfo {
+    pub name: String,
+    pub detail: String, // Declaration as text, e.g. `const int a[2][3]`.
+    // Identifier declaring the symbol, the definition for a function if any.
+    // None for library functions, unless declared again.
+    pub decl: Option<Span>,
+}
+
+impl Index {
+    /// Returns the key of the symbol referred to at the given offset, if any.
+    pub fn key_at(&self, offset: usize) -> Option<&String> {
+        let idx = self.refs.partition_point(|(span, _)| span.end < offset);
+        let (span, key) = self.refs.get(idx)?;
+        (span.begin <= offset).then_some(key)
+    }
+
+    /// Returns the spans of all references to the given symbol.
+    pub fn refs_of<'a>(&'a self, key: &'a str) -> impl Iterator<Item = Span> + 'a {
+        self.refs.iter().filter(move |(_, k)| k == key).map(|(span, _)| *span)
+    }
+}
@@ -0,0 +1,110 @@
+//! Cross check global symbols among translation units.
+//! Each unit is analyzed on its own, so conflicts between units,
+//! e.g. `int f(int);` in one and `void f(int a[]) {}` in another,
+//! can only be found by comparing their global symbols afterwards.
+//! Constant scalars are not checked, they are folded and take no storage.
+//! Constant arrays link like other global arrays.
+
+use super::SemAnalyzer;
+use crate::frontend::ast::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Type of a global symbol, as far as linkage is concerned.
+#[derive(PartialEq)]
+enum GlobalType {
+    Var(Type),
+    // Return type and parameters.
+    Func(BType, Vec<Type>),
+}
+
+/// An occurrence of a global symbol.
+struct Global<'a> {
+    ty: GlobalType,
+    is_def: bool,
+    path: &'a str,
+}
+
+/// Checks that every global symbol is defined at most once,
+/// and all its declarations agree on its type.
+/// Each unit comes with its path, which is used in error messages.
+/// Undefined symbols are fine, they may come from the runtime or C code.
+pub fn check_linkage(units: &[(&Program, &str)]) {
+    let mut globals: HashMap<&str, Global> = HashMap::new();
+
+    units.iter().for_each(|&(prog, path)| {
+        collect(prog).into_iter().for_each(|(name, ty, is_def)| {
+            let global = Global { ty, is_def, path };
+            let Some(prev) = globals.get_mut(name) else {
+                globals.insert(name, global);
+                return;
+            };
+            assert!(
+                prev.ty == global.ty,
+                "{path}: conflicting types for `{name}`: {} here, {} in {}",
+                global.ty, prev.ty, prev.path
+            );
+            assert!(
+                !(prev.is_def && global.is_def),
+                "{path}: multiple definitions of `{name}`, previous one in {}",
+                prev.path
+            );
+            if global.is_def {
+                *prev = global;
+            }
+        });
+    });
+}
+
+/// Collects global symbols in the given program,
+/// returning their names, types and whether they are definitions.
+fn collect(prog: &Program) -> Vec<(&str, GlobalType, bool)> {
+    let mut res = vec![];
+    let dims = |sizes: &[ExprId]| sizes.iter().map(|size| prog.1.value(*size)).collect();
+    let params = |fparams| SemAnalyzer::param_types(fparams, &prog.1);
+
+    prog.0.iter().for_each(|unit| match unit {
+        CompUnit::VarDecl(decl) => {
+            decl.var_defs.iter().for_each(|def| {
+                let (ident, ty) = match def {
+                    VarDef::Scalar(..) if decl.is_const => return,
+                    VarDef::Scalar(ident, ..) => (ident, GlobalType::Var(Type::Int)),
+                    VarDef::Array(ident, sizes, ..) => {
+                        (ident, GlobalType::Var(Type::Array(dims(sizes))))
+                    }
+                };
+                // Global variables are mangled to `@ident`.
+                res.push((&ident[1..], ty, !decl.is_extern));
+            });
+        }
+        CompUnit::Error(..) => {}
+        CompUnit::FuncDef(FuncDef { btype, ident, params: fparams, .. }) => {
+            res.push((ident.as_str(), GlobalType::Func(*btype, params(fparams)), true));
+        }
+        CompUnit::FuncDecl(FuncDecl { btype, ident, params: fparams, .. }) => {
+            res.push((ident.as_str(), GlobalType::Func(*btype, params(fparams)), false));
+        }
+    });
+
+    res
+}
+
+impl fmt::Display for GlobalType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlobalType::Var(ty) => write!(f, "`{ty}`"),
+            GlobalType::Func(btype, params) => {
+                let ret = match btype {
+                    BType::Int => "int",
+                    BType::Void => "void",
+                };
+                let params = params
+                    .iter()
+                    .map(|param| param.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "`{ret}({params})`")
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Lower calls to `putf` into calls to simpler runtime functions.
+//! `putf` is variadic, which cannot be expressed in Koopa IR.
+//! Since the format string must be a literal, we split it at compile time,
+//! e.g. `putf("a = %d\n", a)` becomes `putstr("a = "); putint(a); putstr("\n");`.
+
+use crate::frontend::ast::*;
+
+/// Lowers `putf` with the given arguments. Generated calls and string literals
+/// are added to the arena, spanning the original call.
+/// Fails if the format string doesn't match the arguments.
+pub fn lower_putf(args: &[ExprId], exprs: &mut Exprs, span: Span) -> Result<Block, String> {
+    let format = match args.first().map(|id| &exprs[*id]) {
+        Some(Expr::Lit(Lit::Str(format))) => format.clone(),
+        _ => return Err("The first argument of putf must be a string literal".to_string()),
+    };
+    let args = &args[1..];
+
+    let mut calls = vec![];
+    let mut args = args.iter();
+    let mut text = vec![];
+    let mut iter = format.iter();
+
+    let mut flush = |calls: &mut Vec<(&str, ExprId)>, text: &mut Vec<u8>| {
+        if !text.is_empty() {
+            let lit = exprs.add(Expr::Lit(Lit::Str(text.clone())), span.begin, span.end);
+            calls.push(("putstr", lit));
+            text.clear();
+        }
+    };
+
+    while let Some(&byte) = iter.next() {
+        if byte != b'%' {
+            text.push(byte);
+            continue;
+        }
+
+        let callee = match iter.next() {
+            Some(b'%') => {
+                text.push(b'%');
+                continue;
+            }
+            Some(b'd') => "putint",
+            Some(b'c') => "putch",
+            Some(&spec) => return Err(format!("Unsupported format specifier %{}", spec as char)),
+            None => return Err("Dangling % in format string".to_string()),
+        };
+        let arg = args.next().ok_or("Too few arguments for putf")?;
+        flush(&mut calls, &mut text);
+        calls.push((callee, *arg));
+    }
+    flush(&mut calls, &mut text);
+
+    if args.next().is_some() {
+        return Err("Too many arguments for putf".to_string());
+    }
+    let items = calls
+        .into_iter()
+        .map(|(callee, arg)| {
+            let call = Expr::Call(callee.to_string(), vec![arg]);
+            BlockItem::Stmt(Stmt::Exp(exprs.add(call, span.begin, span.end)), span)
+        })
+        .collect();
+    Ok(Block(items, span))
+}
@@ -5,14 +5,33 @@
 //! Advanced functionalities (name mangling, scope management, etc.)
 //! are implemented in SemAnalyzer.
 
-use std::collections::HashMap;
+use crate::frontend::ast::{BType, Type};
+use std::collections::{BTreeMap, HashMap};
 
 // Symbol table entry.
 pub enum Symbol {
     // Token is used as mangled name.
     Int { token: String },
     ConstInt { token: String, value: i32 },
-    IntArray { token: String },
+    // Type is either an array or a pointer (array parameter).
+    IntArray { token: String, ty: Type },
+    // Values are flattened, indexed by offsets. Zeros are left out.
+    ConstIntArray { token: String, ty: Type, values: BTreeMap<usize, i32> },
+    // Functions are not mangled.
+    Function { ret: BType, params: Vec<Type>, is_defined: bool },
+}
+
+impl Symbol {
+    /// Returns the token of a variable, or `None` for a function.
+    pub fn token(&self) -> Option<&String> {
+        match self {
+            Symbol::Int { token }
+            | Symbol::ConstInt { token, .. }
+            | Symbol::IntArray { token, .. }
+            | Symbol::ConstIntArray { token, .. } => Some(token),
+            Symbol::Function { .. } => None,
+        }
+    }
 }
 
 // Symbol table.
@@ -33,6 +52,11 @@ impl SymTab {
         self.table.get(ident)
     }
 
+    /// Returns an iterator over all identifier-symbol pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Symbol)> {
+        self.table.iter()
+    }
+
     /// Inserts a identifier-symbol pair into the symbol table.
     pub fn insert(&mut self, ident: String, symbol: Symbol) {
         self.table.insert(ident, symbol);
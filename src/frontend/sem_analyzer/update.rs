@@ -1,9 +1,10 @@
 //! Update AST nodes with semantic information.
-//! For now, all it does is replacing identifiers
-//! with their mangled names or constant values.
+//! Identifiers are replaced with their mangled names or constant values,
+//! and every expression is annotated with its type and constant value.
 
+use super::eval::{eval_binary, eval_unary};
 use super::SemAnalyzer;
-use crate::frontend::ast::{self, *};
+use crate::frontend::ast::*;
 
 pub trait Update<T> {
     /// Updates the given expression.
@@ -14,109 +15,109 @@ impl Update<InitList> for SemAnalyzer {
     fn update(&self, init: &mut InitList) {
         use InitList::*;
         match init {
-            Exp(exp) => self.update(exp),
+            Exp(exp) => self.update_value(*exp),
             List(list) => list.iter_mut().for_each(|list| self.update(list)),
             Flat(..) => panic!("Unexpected arm"),
         }
     }
 }
 
-impl Update<Exp> for SemAnalyzer {
-    fn update(&self, exp: &mut Exp) {
-        use Exp::*;
-        match exp {
-            LOrExp(lor) => self.update(lor),
-            Number(..) => panic!("Unexpected arm"),
-        }
-    }
-}
-
-impl Update<LVal> for SemAnalyzer {
-    fn update(&self, lval: &mut LVal) {
-        use LVal::*;
-        match lval {
-            Ident(ident) => {
-                self.mangle(ident);
+impl Update<ExprId> for SemAnalyzer {
+    fn update(&self, id: &mut ExprId) {
+        let id = *id;
+        let expr = self.exprs.borrow()[id].clone();
+        let span = self.exprs.borrow().info(id).span;
+        let value = |id| self.exprs.borrow().info(id).value;
+        self.pos.set(span.begin);
+        match expr {
+            Expr::Binary(op, lhs, rhs) => {
+                self.update_value(lhs);
+                self.update_value(rhs);
+                // Undefined operations are left to runtime, not reported here.
+                let value = value(lhs)
+                    .zip(value(rhs))
+                    .and_then(|(x, y)| eval_binary(op, x, y).ok());
+                self.annotate(id, Type::Int, value);
             }
-            ArrayElem(ident, indices) => {
-                self.mangle(ident);
-                indices.iter_mut().for_each(|exp| self.update(exp));
+            Expr::Unary(op, exp) => {
+                self.update_value(exp);
+                self.annotate(id, Type::Int, value(exp).map(|x| eval_unary(op, x)));
             }
-        }
-    }
-}
-
-impl Update<PrimaryExp> for SemAnalyzer {
-    fn update(&self, exp: &mut PrimaryExp) {
-        use PrimaryExp::*;
-        use ast::LVal::*;
-        match exp {
-            BracketedExp(bexp) => self.update(bexp.as_mut()),
-            Number(..) => {}
-            LVal(lval) => {
-                match lval {
-                    Ident(ident) => {
-                        if self.is_const(ident) {
-                            let value = self.value(ident);
-                            *exp = Number(ast::Number(value));
-                        } else {
-                            self.update(lval);
-                        }
+            Expr::Call(ident, args) => {
+                // Statement-level `putf` has been lowered in `Analyze<Stmt>`.
+                if ident == "putf" {
+                    error!(self, "putf can only be used as a statement");
+                }
+                let (ret, params) = self.function(&ident);
+                if args.len() != params.len() {
+                    error!(self, "Wrong number of arguments to function {ident}");
+                }
+                let name = Span { begin: span.begin, end: span.begin + ident.len() };
+                self.index_ref(&ident, name);
+                args.into_iter().zip(params).for_each(|(mut arg, param)| {
+                    let is_str = matches!(self.exprs.borrow()[arg], Expr::Lit(Lit::Str(..)));
+                    if is_str && ident != "putstr" {
+                        error!(self, "String literal passed to {ident}");
                     }
-                    ArrayElem(..) => self.update(lval),
+                    self.update(&mut arg);
+                    let ty = self.exprs.borrow().ty(arg).clone();
+                    if !ty.is_passable_to(param) {
+                        error!(
+                            self,
+                            "Incompatible argument to function {ident}: expected `{param}`, found `{ty}`"
+                        );
+                    }
+                });
+                let ty = match ret {
+                    BType::Int => Type::Int,
+                    BType::Void => Type::Void,
+                };
+                self.annotate(id, ty, None);
+            }
+            Expr::Index(mut arr, index) => {
+                let ident = self.exprs.borrow().var_of(arr).clone();
+                self.mark_used(&ident);
+                self.update(&mut arr);
+                self.update_value(index);
+                let arr_ty = self.exprs.borrow().ty(arr).clone();
+                let ty = arr_ty.index(1).unwrap_or_else(|| {
+                    error!(self, "Too many subscripts for {ident} of type `{arr_ty}`")
+                });
+                // Elements of constant arrays at constant indices are known.
+                let indices = self.exprs.borrow().indices(id);
+                let indices = indices.iter().map(|index| value(*index)).collect::<Vec<_>>();
+                self.annotate(id, ty, self.const_elem(&ident, &indices));
+            }
+            Expr::Var(mut ident) => {
+                self.mark_used(&ident);
+                self.index_ref(&ident, span);
+                if let Some(value) = self.const_value(&ident) {
+                    self.exprs.borrow_mut()[id] = Expr::Lit(Lit::Int(value));
+                    self.annotate(id, Type::Int, Some(value));
+                    return;
                 }
+                let ty = self.var_type(&ident);
+                self.mangle(&mut ident);
+                self.exprs.borrow_mut()[id] = Expr::Var(ident);
+                self.annotate(id, ty, None);
             }
+            Expr::Lit(Lit::Int(value)) => self.annotate(id, Type::Int, Some(value)),
+            Expr::Lit(Lit::Str(..)) => self.annotate(id, Type::Pointer(vec![]), None),
         }
     }
 }
 
-impl Update<UnaryExp> for SemAnalyzer {
-    fn update(&self, exp: &mut UnaryExp) {
-        use UnaryExp::*;
-        match exp {
-            Primary(bexp) => self.update(bexp.as_mut()),
-            FuncCall(_, exps) => {
-                exps.iter_mut().for_each(|exp| self.update(exp));
-            }
-            OpUnary(_, bexp) => self.update(bexp.as_mut()),
+impl SemAnalyzer {
+    /// Updates the given expression, which must be an integer.
+    pub fn update_value(&self, mut id: ExprId) {
+        let ident = match self.exprs.borrow()[id] {
+            Expr::Var(..) | Expr::Index(..) => self.exprs.borrow().var_of(id).clone(),
+            _ => "Expression".to_string(),
+        };
+        self.update(&mut id);
+        let ty = self.exprs.borrow().ty(id).clone();
+        if ty != Type::Int {
+            error!(self, "{ident} of type `{ty}` used as a value");
         }
     }
 }
-
-macro_rules! impl_update_binary_op {
-    ($T:ty, $arm1:tt, $arm2:tt, var) => {
-        impl Update<$T> for SemAnalyzer {
-            fn update(&self, exp: &mut $T) {
-                use $T::*;
-                match exp {
-                    $arm1(bexp) => self.update(bexp.as_mut()),
-                    $arm2(bexps, _, bexp) => {
-                        self.update(bexp.as_mut());
-                        self.update(bexps.as_mut());
-                    }
-                }
-            }
-        }
-    };
-    ($T:ty, $arm1:tt, $arm2:tt, fixed) => {
-        impl Update<$T> for SemAnalyzer {
-            fn update(&self, exp: &mut $T) {
-                use $T::*;
-                match exp {
-                    $arm1(bexp) => self.update(bexp.as_mut()),
-                    $arm2(bexps, bexp) => {
-                        self.update(bexp.as_mut());
-                        self.update(bexps.as_mut());
-                    }
-                }
-            }
-        }
-    };
-}
-
-impl_update_binary_op!(MulExp, Unary, MulOpUnary, var);
-impl_update_binary_op!(AddExp, Mul, AddOpMul, var);
-impl_update_binary_op!(RelExp, Add, RelOpAdd, var);
-impl_update_binary_op!(EqExp, Rel, EqOpRel, var);
-impl_update_binary_op!(LAndExp, Eq, LAndEq, fixed);
-impl_update_binary_op!(LOrExp, LAnd, LOrLAnd, fixed);
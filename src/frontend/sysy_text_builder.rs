@@ -0,0 +1,633 @@
+//! SysY text builder, turning an AST back into source text.
+//! Only parsed ASTs are supported, since semantic analysis mangles names
+//! and flattens initializer lists. Operands are parenthesized and bodies
+//! braced only where needed, so the text parses back into the same AST.
+//! When formatting, comments of the source are put back by spans:
+//! those before a declaration or statement go on lines of their own before
+//! it, those on its last line go after it, and those in the middle,
+//! outside any nested block, are moved before it.
+
+use super::ast::*;
+use super::comment::{self, Comment};
+use std::fmt;
+
+macro_rules! push_text {
+    ($self:tt, $($arg:tt)*) => {
+        $self.text.push_str(&format!($($arg)*))
+    };
+}
+
+pub struct SysyTextBuilder<'a> {
+    text: String,
+    indent: usize,
+    tab: String,
+    exprs: &'a Exprs,
+    source: Option<Source<'a>>,
+}
+
+/// Source text being formatted.
+struct Source<'a> {
+    text: &'a str,
+    comments: Vec<Comment>, // Those not built yet.
+    last_end: usize,        // End of what was built last.
+}
+
+impl<'a> SysyTextBuilder<'a> {
+    /// Builds SysY text from the given parsed program.
+    pub fn build(prog: &'a Program) -> String {
+        let mut builder = Self::new(&prog.1);
+        builder.build_units(&prog.0);
+        builder.text
+    }
+
+    /// Builds SysY text from the given program parsed from the given source,
+    /// with its comments, indented by the given number of spaces.
+    pub fn format(prog: &'a Program, text: &'a str, indent: usize) -> String {
+        let mut builder = Self::new(&prog.1);
+        builder.tab = " ".repeat(indent);
+        builder.source = Some(Source {
+            text,
+            comments: comment::scan(text),
+            last_end: 0,
+        });
+        builder.build_units(&prog.0);
+        builder.build_comments(|_| true);
+        builder.text
+    }
+
+    fn new(exprs: &'a Exprs) -> Self {
+        Self {
+            text: String::new(),
+            indent: 0,
+            tab: " ".repeat(4),
+            exprs,
+            source: None,
+        }
+    }
+
+    fn push_indent(&mut self) {
+        self.text.push_str(&self.tab.repeat(self.indent));
+    }
+
+    /// Function definitions are set apart by blank lines,
+    /// other units only if they are in the source.
+    fn build_units(&mut self, units: &[CompUnit]) {
+        let mut prev_is_func = false;
+        units.iter().for_each(|unit| {
+            let is_func = matches!(unit, CompUnit::FuncDef(..));
+            if !self.text.is_empty() && (prev_is_func || is_func) {
+                self.build_blank_line();
+            }
+            self.build_from(unit);
+            prev_is_func = is_func;
+        });
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&SysyTextBuilder::build(self))
+    }
+}
+
+/// A node of a parsed AST, displayed as SysY text.
+/// Nodes refer to expressions by id, so they come with the arena.
+pub struct Printed<'a, T>(&'a T, &'a Exprs);
+
+impl Exprs {
+    /// Returns the given node, which refers to this arena, for display.
+    /// e.g. `exprs.display(&stmt).to_string()`
+    pub fn display<'a, T>(&'a self, node: &'a T) -> Printed<'a, T> {
+        Printed(node, self)
+    }
+}
+
+impl<T> fmt::Display for Printed<'_, T>
+where
+    for<'a> SysyTextBuilder<'a>: BuildFrom<T>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut builder = SysyTextBuilder::new(self.1);
+        builder.build_from(self.0);
+        f.write_str(&builder.text)
+    }
+}
+
+pub trait BuildFrom<T> {
+    fn build_from(&mut self, target: &T);
+}
+
+impl BuildFrom<CompUnit> for SysyTextBuilder<'_> {
+    /// Units take whole lines.
+    fn build_from(&mut self, unit: &CompUnit) {
+        match unit {
+            CompUnit::VarDecl(decl) => {
+                self.build_leading(decl.span, &[]);
+                self.build_from(decl);
+                self.build_trailing(decl.span);
+            }
+            CompUnit::FuncDef(func_def) => {
+                self.build_leading(func_def.span, &[func_def.block.1]);
+                self.build_from(func_def);
+                self.build_trailing(func_def.span);
+            }
+            CompUnit::FuncDecl(func_decl) => {
+                self.build_leading(func_decl.span, &[]);
+                self.build_from(func_decl);
+                self.build_trailing(func_decl.span);
+            }
+            CompUnit::Error(..) => panic!("Unexpected arm"),
+        }
+        push_text!(self, "\n");
+    }
+}
+
+impl BuildFrom<VarDecl> for SysyTextBuilder<'_> {
+    /// Declarations start where they are, and end without a newline.
+    fn build_from(&mut self, decl: &VarDecl) {
+        if decl.is_extern {
+            push_text!(self, "extern ");
+        }
+        if decl.is_const {
+            push_text!(self, "const ");
+        }
+        push_text!(self, "{}", decl.btype);
+        decl.var_defs.iter().enumerate().for_each(|(i, def)| {
+            push_text!(self, "{}", if i == 0 { " " } else { ", " });
+            self.build_from(def);
+        });
+        push_text!(self, ";");
+    }
+}
+
+impl BuildFrom<VarDef> for SysyTextBuilder<'_> {
+    fn build_from(&mut self, def: &VarDef) {
+        match def {
+            VarDef::Scalar(ident, opt_exp, _) => {
+                push_text!(self, "{ident}");
+                if let Some(exp) = opt_exp {
+                    push_text!(self, " = ");
+                    self.build_from(exp);
+                }
+            }
+            VarDef::Array(ident, sizes, opt_init, _) => {
+                push_text!(self, "{ident}");
+                self.build_dims(sizes);
+                if let Some(init) = opt_init {
+                    push_text!(self, " = ");
+                    self.build_from(init);
+                }
+            }
+        }
+    }
+}
+
+impl BuildFrom<InitList> for SysyTextBuilder<'_> {
+    fn build_from(&mut self, init: &InitList) {
+        match init {
+            InitList::Exp(exp) => self.build_from(exp),
+            InitList::List(inits) => {
+                push_text!(self, "{{");
+                inits.iter().enumerate().for_each(|(i, init)| {
+                    if i > 0 {
+                        push_text!(self, ", ");
+                    }
+                    self.build_from(init);
+                });
+                push_text!(self, "}}");
+            }
+            InitList::Flat(..) => panic!("Flattened initializer list"),
+        }
+    }
+}
+
+impl BuildFrom<FuncDef> for SysyTextBuilder<'_> {
+    fn build_from(&mut self, FuncDef { btype, ident, params, block, .. }: &FuncDef) {
+        self.build_func_head(btype, ident, params);
+        push_text!(self, " ");
+        self.build_from(block);
+    }
+}
+
+impl BuildFrom<FuncDecl> for SysyTextBuilder<'_> {
+    fn build_from(&mut self, FuncDecl { btype, ident, params, .. }: &FuncDecl) {
+        self.build_func_head(btype, ident, params);
+        push_text!(self, ";");
+    }
+}
+
+impl BuildFrom<FuncFParam> for SysyTextBuilder<'_> {
+    fn build_from(&mut self, param: &FuncFParam) {
+        match param {
+            FuncFParam::Scalar(btype, ident, _) => push_text!(self, "{btype} {ident}"),
+            FuncFParam::Array(btype, ident, sizes, _) => {
+                push_text!(self, "{btype} {ident}[]");
+                self.build_dims(sizes);
+            }
+        }
+    }
+}
+
+impl BuildFrom<Block> for SysyTextBuilder<'_> {
+    /// Blocks start where they are, and end without a newline.
+    fn build_from(&mut self, block: &Block) {
+        let end = block.1.end;
+        if block.0.is_empty() && !self.has_comments(|comment| comment.span.begin < end) {
+            push_text!(self, "{{}}");
+            return;
+        }
+        push_text!(self, "{{\n");
+        self.indent += 1;
+        block.0.iter().for_each(|item| self.build_from(item));
+        self.build_comments(|comment| comment.span.begin < end);
+        self.indent -= 1;
+        self.push_indent();
+        push_text!(self, "}}");
+    }
+}
+
+impl BuildFrom<BlockItem> for SysyTextBuilder<'_> {
+    /// Block items take whole lines.
+    fn build_from(&mut self, item: &BlockItem) {
+        let span = match item {
+            BlockItem::VarDecl(decl) => decl.span,
+            BlockItem::Stmt(_, span) => *span,
+        };
+        let mut blocks = vec![];
+        if let BlockItem::Stmt(stmt, _) = item {
+            block_spans(stmt, &mut blocks);
+        }
+        self.build_leading(span, &blocks);
+        self.push_indent();
+        match item {
+            BlockItem::VarDecl(decl) => self.build_from(decl),
+            BlockItem::Stmt(stmt, _) => self.build_from(stmt),
+        }
+        self.build_trailing(span);
+        push_text!(self, "\n");
+    }
+}
+
+impl BuildFrom<Stmt> for SysyTextBuilder<'_> {
+    /// Statements start where they are, and end without a newline.
+    fn build_from(&mut self, stmt: &Stmt) {
+        use Stmt::*;
+        match stmt {
+            Assign(lval, exp) => {
+                self.build_from(lval);
+                push_text!(self, " = ");
+                self.build_from(exp);
+                push_text!(self, ";");
+            }
+            Empty => push_text!(self, ";"),
+            Exp(exp) => {
+                self.build_from(exp);
+                push_text!(self, ";");
+            }
+            Block(block) => self.build_from(block),
+            If(exp, then, opt_else) => {
+                push_text!(self, "if (");
+                self.build_from(exp);
+                push_text!(self, ")");
+                let Some(otherwise) = opt_else else {
+                    self.build_body(then);
+                    return;
+                };
+                // An `if` without `else` at the end of `then` would take
+                // the `else`. Parsed ASTs never have one there.
+                if is_open(then) {
+                    self.build_braced(then);
+                } else {
+                    self.build_body(then);
+                }
+                if matches!(**then, Block(..)) || is_open(then) {
+                    push_text!(self, " else");
+                } else {
+                    push_text!(self, "\n");
+                    self.push_indent();
+                    push_text!(self, "else");
+                }
+                // Keeps `else if` chains flat.
+                if let If(..) = **otherwise {
+                    push_text!(self, " ");
+                    self.build_from(&**otherwise);
+                } else {
+                    self.build_body(otherwise);
+                }
+            }
+            While(exp, body) => {
+                push_text!(self, "while (");
+                self.build_from(exp);
+                push_text!(self, ")");
+                self.build_body(body);
+            }
+            Break => push_text!(self, "break;"),
+            Continue => push_text!(self, "continue;"),
+            Return(None) => push_text!(self, "return;"),
+            Return(Some(exp)) => {
+                push_text!(self, "return ");
+                self.build_from(exp);
+                push_text!(self, ";");
+            }
+            Error => panic!("Unexpected arm"),
+        }
+    }
+}
+
+impl BuildFrom<ExprId> for SysyTextBuilder<'_> {
+    /// Operands are parenthesized only if they bind looser than
+    /// their operators, or as loose on the right, since all binary
+    /// operators associate to the left.
+    fn build_from(&mut self, &id: &ExprId) {
+        match &self.exprs[id] {
+            &Expr::Binary(op, lhs, rhs) => {
+                let prec = precedence(&self.exprs[id]);
+                self.build_operand(lhs, prec);
+                push_text!(self, " {op} ");
+                self.build_operand(rhs, prec + 1);
+            }
+            &Expr::Unary(op, exp) => {
+                push_text!(self, "{op}");
+                // Keeps `- -a` from looking like a decrement.
+                if matches!(self.exprs[exp], Expr::Unary(inner, _) if inner == op) {
+                    push_text!(self, " ");
+                }
+                self.build_operand(exp, precedence(&self.exprs[id]));
+            }
+            Expr::Call(ident, args) => {
+                push_text!(self, "{ident}(");
+                args.iter().enumerate().for_each(|(i, arg)| {
+                    if i > 0 {
+                        push_text!(self, ", ");
+                    }
+                    self.build_from(arg);
+                });
+                push_text!(self, ")");
+            }
+            &Expr::Index(arr, index) => {
+                self.build_from(&arr);
+                push_text!(self, "[");
+                self.build_from(&index);
+                push_text!(self, "]");
+            }
+            Expr::Var(ident) => push_text!(self, "{ident}"),
+            Expr::Lit(lit) => push_text!(self, "{lit}"),
+        }
+    }
+}
+
+impl SysyTextBuilder<'_> {
+    /// Builds comments before the given span on lines of their own,
+    /// along with those inside it but outside the given nested blocks,
+    /// whose comments are built along with them.
+    fn build_leading(&mut self, span: Span, blocks: &[Span]) {
+        self.build_comments(|comment| {
+            let begin = comment.span.begin;
+            let in_blocks = blocks.iter().any(|block| block.begin < begin && begin < block.end);
+            begin < span.begin || (begin < span.end && !in_blocks)
+        });
+        if self.has_blank_line(span.begin) {
+            self.build_blank_line();
+        }
+    }
+
+    /// Builds comments right after the given span on the same line
+    /// after it, and marks the span as built.
+    fn build_trailing(&mut self, span: Span) {
+        let Some(source) = &mut self.source else {
+            return;
+        };
+        source.last_end = span.end;
+        loop {
+            let Source { text, comments, last_end } = self.source.as_mut().unwrap();
+            let end = *last_end;
+            let Some(pos) = comments.iter().position(|comment| {
+                let begin = comment.span.begin;
+                let between = &text[end.min(begin)..begin];
+                let is_blank = between.trim_matches([' ', '\t']).is_empty();
+                !comment.is_directive && begin >= end && is_blank
+            }) else {
+                return;
+            };
+            let comment = comments.remove(pos);
+            *last_end = comment.span.end;
+            push_text!(self, " {}", comment.text);
+        }
+    }
+
+    /// Builds comments satisfying the given predicate on lines of their own.
+    /// Directives are never indented.
+    fn build_comments(&mut self, pred: impl Fn(&Comment) -> bool) {
+        let Some(source) = &mut self.source else {
+            return;
+        };
+        take(&mut source.comments, pred).into_iter().for_each(|comment| {
+            if self.has_blank_line(comment.span.begin) {
+                self.build_blank_line();
+            }
+            if !comment.is_directive {
+                self.push_indent();
+            }
+            push_text!(self, "{}\n", comment.text);
+            self.source.as_mut().unwrap().last_end = comment.span.end;
+        });
+    }
+
+    /// Returns whether there are comments satisfying the given predicate.
+    fn has_comments(&self, pred: impl Fn(&Comment) -> bool) -> bool {
+        self.source.as_ref().is_some_and(|source| source.comments.iter().any(pred))
+    }
+
+    /// Returns whether there is a blank line in the source between
+    /// what was built last and the given offset.
+    fn has_blank_line(&self, offset: usize) -> bool {
+        let Some(source) = &self.source else {
+            return false;
+        };
+        // Lines between two newlines are whole lines.
+        let mut lines = source.text[source.last_end.min(offset)..offset].split('\n');
+        lines.next();
+        lines.next_back();
+        lines.any(|line| line.trim().is_empty())
+    }
+
+    /// Ends what was built last with a blank line, unless it is
+    /// the beginning of a block or of the text, or there is one already.
+    fn build_blank_line(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with("{\n") && !self.text.ends_with("\n\n") {
+            push_text!(self, "\n");
+        }
+    }
+
+    /// Builds the body of `if` or `while`. Blocks stay on the same line,
+    /// other statements go to the next line, indented.
+    fn build_body(&mut self, stmt: &Stmt) {
+        if let Stmt::Block(block) = stmt {
+            push_text!(self, " ");
+            self.build_from(block);
+            return;
+        }
+        push_text!(self, "\n");
+        self.indent += 1;
+        self.push_indent();
+        self.build_from(stmt);
+        self.indent -= 1;
+    }
+
+    /// Builds the given statement as the only one in a block.
+    fn build_braced(&mut self, stmt: &Stmt) {
+        push_text!(self, " {{\n");
+        self.indent += 1;
+        self.push_indent();
+        self.build_from(stmt);
+        push_text!(self, "\n");
+        self.indent -= 1;
+        self.push_indent();
+        push_text!(self, "}}");
+    }
+
+    fn build_func_head(&mut self, btype: &BType, ident: &str, params: &[FuncFParam]) {
+        push_text!(self, "{btype} {ident}(");
+        params.iter().enumerate().for_each(|(i, param)| {
+            if i > 0 {
+                push_text!(self, ", ");
+            }
+            self.build_from(param);
+        });
+        push_text!(self, ")");
+    }
+
+    fn build_dims(&mut self, sizes: &[ExprId]) {
+        sizes.iter().for_each(|size| {
+            push_text!(self, "[");
+            self.build_from(size);
+            push_text!(self, "]");
+        });
+    }
+
+    /// Builds the given operand, parenthesized if it binds looser
+    /// than the given precedence.
+    fn build_operand(&mut self, id: ExprId, prec: u8) {
+        if precedence(&self.exprs[id]) < prec {
+            push_text!(self, "(");
+            self.build_from(&id);
+            push_text!(self, ")");
+        } else {
+            self.build_from(&id);
+        }
+    }
+}
+
+/// Returns how tightly the given expression binds, following the grammar.
+fn precedence(expr: &Expr) -> u8 {
+    use BinaryOp::*;
+    match expr {
+        Expr::Binary(Or, ..) => 1,
+        Expr::Binary(And, ..) => 2,
+        Expr::Binary(Eq | Ne, ..) => 3,
+        Expr::Binary(Lt | Le | Gt | Ge, ..) => 4,
+        Expr::Binary(Add | Sub, ..) => 5,
+        Expr::Binary(Mul | Div | Rem, ..) => 6,
+        Expr::Unary(..) => 7,
+        Expr::Call(..) | Expr::Index(..) | Expr::Var(..) | Expr::Lit(..) => 8,
+    }
+}
+
+/// Collects spans of the outermost blocks in the given statement.
+fn block_spans(stmt: &Stmt, spans: &mut Vec<Span>) {
+    match stmt {
+        Stmt::Block(block) => spans.push(block.1),
+        Stmt::If(_, then, opt_else) => {
+            block_spans(then, spans);
+            if let Some(otherwise) = opt_else {
+                block_spans(otherwise, spans);
+            }
+        }
+        Stmt::While(_, body) => block_spans(body, spans),
+        _ => {}
+    }
+}
+
+/// Removes comments satisfying the given predicate, and returns them.
+fn take(comments: &mut Vec<Comment>, pred: impl Fn(&Comment) -> bool) -> Vec<Comment> {
+    let (taken, kept) = std::mem::take(comments).into_iter().partition(pred);
+    *comments = kept;
+    taken
+}
+
+/// Returns whether the given statement ends with an `if` without `else`.
+fn is_open(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::If(_, _, None) => true,
+        Stmt::If(_, _, Some(stmt)) | Stmt::While(_, stmt) => is_open(stmt),
+        _ => false,
+    }
+}
+
+impl fmt::Display for BType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BType::Int => write!(f, "int"),
+            BType::Void => write!(f, "void"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BinaryOp::*;
+        let op = match self {
+            Mul => "*",
+            Div => "/",
+            Rem => "%",
+            Add => "+",
+            Sub => "-",
+            Lt => "<",
+            Le => "<=",
+            Gt => ">",
+            Ge => ">=",
+            Eq => "==",
+            Ne => "!=",
+            And => "&&",
+            Or => "||",
+        };
+        f.write_str(op)
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Plus => write!(f, "+"),
+            UnaryOp::Minus => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl fmt::Display for Lit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Negative values only come from wrapping, like `2147483648`,
+            // which is how they are written back.
+            Lit::Int(value) => write!(f, "{}", *value as u32),
+            Lit::Str(bytes) => write!(f, "{}", string_lit(bytes)),
+        }
+    }
+}
+
+/// Returns the given bytes as a string literal, escaping where needed.
+fn string_lit(bytes: &[u8]) -> String {
+    let text = bytes
+        .iter()
+        .map(|&byte| match byte {
+            b'"' => "\\\"".to_string(),
+            b'\\' => "\\\\".to_string(),
+            b'\n' => "\\n".to_string(),
+            b'\t' => "\\t".to_string(),
+            0x20..=0x7e => (byte as char).to_string(),
+            _ => format!("\\{byte:03o}"), // Hex escapes don't stop at two digits.
+        })
+        .collect::<String>();
+    format!("\"{text}\"")
+}
@@ -0,0 +1,97 @@
+//! Fuzzing of the compiler: random programs are run through the AST
+//! interpreter, the Koopa interpreter and the RISC-V simulator, which
+//! must agree. Programs on which they diverge are reduced, keeping
+//! the first diverging stage, so that bugs are easy to look into.
+//! Stages run in this very process, with their panics caught and their
+//! endless loops cut short by a step limit, so that broken stages are contained.
+
+mod generator;
+mod reducer;
+
+use crate::frontend::{Lints, Program};
+use crate::interpreter::{self, Io, Options};
+use generator::Generator;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Runs analyzed translation units with the given options, input and output,
+/// returns what `main` returns.
+pub type Stage = fn(&[Program], Options, Io) -> i32;
+
+/// Stages in pipeline order, with their names.
+pub const STAGES: [(&str, Stage); 3] = [
+    ("AST interpreter", interpreter::run_ast),
+    ("Koopa interpreter", interpreter::run_koopa),
+    ("RISC-V simulator", interpreter::run_riscv),
+];
+
+// Edits may still make loops endless, which should be given up soon.
+// Generated programs run far fewer statements or instructions than this.
+const STEP_LIMIT: u64 = 1_000_000;
+
+/// Generates a random program from the given seed, as SysY text.
+pub fn generate(seed: u64) -> String {
+    Generator::generate(seed).to_string()
+}
+
+/// Reduces the given program while it diverges at the same stage,
+/// returns `None` if it doesn't diverge.
+pub fn reduce(prog: &str) -> Option<String> {
+    reducer::Reducer::run(prog)
+}
+
+/// Returns the first stage whose outcome differs from the previous one,
+/// or `None` if all agree, or the AST interpreter fails, in which case
+/// the program is invalid rather than miscompiled.
+pub fn divergence(prog: &str) -> Option<&'static str> {
+    let outcome = |stage| run(stage, prog, "<fuzz>", "", STEP_LIMIT);
+    let reference = outcome(STAGES[0].1).ok()?;
+    STAGES[1..]
+        .iter()
+        .find(|(_, stage)| outcome(*stage).as_ref() != Ok(&reference))
+        .map(|(name, _)| *name)
+}
+
+/// Analyzes the given program at the given path with all lints allowed,
+/// and runs it through the given stage on the given input, within the given
+/// number of steps. Returns the output in the format of `.out` files of the
+/// official test suite, i.e. standard output, then the exit code on its own line,
+/// or the message that the analysis or the stage panicked with.
+pub fn run(
+    stage: Stage,
+    prog: &str,
+    path: &str,
+    input: &str,
+    step_limit: u64,
+) -> Result<String, String> {
+    let mut out = vec![];
+    let code = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut lints = Lints::default();
+        lints.parse_flag("-Aall");
+        let progs = Program::from_sysy_units(&[(prog.to_string(), path.to_string())], &lints);
+        let opts = Options { step_limit: Some(step_limit), ..Options::default() };
+        let io = Io { input: Box::new(input.as_bytes()), output: Box::new(&mut out) };
+        stage(&progs, opts, io)
+    }))
+    .map_err(|payload| panic_message(&*payload))?;
+    let mut out = String::from_utf8_lossy(&out).into_owned();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    // Exit codes are truncated to a byte.
+    out.push_str(&format!("{}\n", code as u8));
+    Ok(out)
+}
+
+/// Returns the message of a caught panic.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg.to_string(),
+        None => payload.downcast_ref::<String>().cloned().unwrap_or_default(),
+    }
+}
+
+/// Parses the given program without semantic analysis.
+fn parse(prog: &str) -> Program {
+    Program::parse_sysy_text(prog, "<fuzz>")
+}
@@ -0,0 +1,692 @@
+//! Random generator of SysY programs, in the spirit of Csmith.
+//! Programs are well-typed, terminate, and are free of undefined behavior:
+//! - functions only call those generated before them, so nothing recurses;
+//! - loops are counted by variables their bodies never assign;
+//! - variables are always initialized, arrays with `{}` at least;
+//! - array indices are literals or reduced modulo sizes;
+//! - indices of assigned elements call no generated functions;
+//! - divisors are literals other than 0 and -1;
+//! - other arithmetic goes through the `safe_*` helpers, as in Csmith,
+//!   which return the left operand where the operation would overflow
+//!   or divide by zero, `INT_MIN / -1` included;
+//! - constant expressions are folded as they are generated, and operations
+//!   in them that would overflow are left out.
+//!
+//! Overflow is undefined in SysY as in C, though it wraps as implemented here,
+//! so a program that overflows could tell nothing about a miscompilation.
+//! Values are printed as they go, and globals at the end of `main`.
+
+use crate::frontend::ast::*;
+
+/// Helpers for arithmetic on arbitrary values.
+const PRELUDE: &str = "\
+int safe_neg(int a) {
+    if (a == -2147483647 - 1) return a;
+    return -a;
+}
+int safe_add(int a, int b) {
+    if (b > 0 && a > 2147483647 - b || b < 0 && a < -2147483647 - 1 - b) return a;
+    return a + b;
+}
+int safe_sub(int a, int b) {
+    if (b < 0 && a > 2147483647 + b || b > 0 && a < -2147483647 - 1 + b) return a;
+    return a - b;
+}
+int safe_mul(int a, int b) {
+    if (a > 0 && b > 0 && a > 2147483647 / b) return a;
+    if (a > 0 && b <= 0 && b < (-2147483647 - 1) / a) return a;
+    if (a <= 0 && b > 0 && a < (-2147483647 - 1) / b) return a;
+    if (a < 0 && b <= 0 && b < 2147483647 / a) return a;
+    return a * b;
+}
+int safe_div(int a, int b) {
+    if (b == 0 || a == -2147483647 - 1 && b == -1) return a;
+    return a / b;
+}
+int safe_mod(int a, int b) {
+    if (b == 0 || a == -2147483647 - 1 && b == -1) return a;
+    return a % b;
+}
+";
+
+const MAX_FUNCS: i64 = 3;
+const MAX_EXPR_DEPTH: usize = 3;
+const MAX_STMT_DEPTH: usize = 3;
+const MAX_LOOP_DEPTH: usize = 2;
+const MAX_LOOP_COUNT: i64 = 3;
+const MAX_CALLS: usize = 3; // Calls of generated functions per function body.
+
+/// SplitMix64, good enough for fuzzing.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `lo..=hi`.
+    fn range(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next() % (hi - lo + 1) as u64) as i64
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len() as i64 - 1) as usize]
+    }
+}
+
+#[derive(Clone)]
+struct Var {
+    name: String,
+    ty: Type, // `Int`, `Array`, or `Pointer` for array parameters.
+    len: i32, // Number of elements a pointer points to.
+    is_const: bool,
+    is_counter: bool, // Loop counters are never assigned in loop bodies.
+    value: i32,       // Of constant scalars, for constant expressions.
+}
+
+impl Var {
+    /// Returns the bounds of indices to reach an `int`, outermost first.
+    fn bounds(&self) -> Vec<i32> {
+        match &self.ty {
+            Type::Array(sizes) => sizes.clone(),
+            Type::Pointer(sizes) => [&[self.len], &sizes[..]].concat(),
+            _ => vec![],
+        }
+    }
+}
+
+struct Func {
+    name: String,
+    returns_int: bool,
+    params: Vec<Var>,
+}
+
+pub struct Generator {
+    rng: Rng,
+    exprs: Exprs,
+    scopes: Vec<Vec<Var>>, // Innermost last.
+    funcs: Vec<Func>,      // Generated so far, thus callable.
+    next_name: usize,
+    loop_depth: usize,
+    calls: usize, // Calls of generated functions in the current body.
+}
+
+impl Generator {
+    /// Generates a random program from the given seed.
+    pub fn generate(seed: u64) -> Program {
+        let prelude = Program::parse_sysy_text(PRELUDE, "<prelude>");
+        let mut gen = Self {
+            rng: Rng(seed),
+            exprs: prelude.1,
+            scopes: vec![vec![]],
+            funcs: vec![],
+            next_name: 0,
+            loop_depth: 0,
+            calls: 0,
+        };
+        let mut units = prelude.0;
+        (0..gen.rng.range(1, 4)).for_each(|_| units.push(CompUnit::VarDecl(gen.global())));
+        (0..gen.rng.range(0, MAX_FUNCS)).for_each(|_| units.push(gen.func(false)));
+        units.push(gen.func(true));
+        Program(units, gen.exprs, LineMap::default())
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.next_name += 1;
+        format!("{prefix}{}", self.next_name)
+    }
+
+    fn declare(&mut self, var: Var) {
+        self.scopes.last_mut().unwrap().push(var);
+    }
+
+    fn vars(&self) -> impl Iterator<Item = &Var> {
+        self.scopes.iter().flatten()
+    }
+
+    fn add(&mut self, expr: Expr) -> ExprId {
+        self.exprs.add(expr, 0, 0)
+    }
+
+    fn lit(&mut self, value: i32) -> ExprId {
+        self.add(Expr::Lit(Lit::Int(value)))
+    }
+
+    fn call(&mut self, name: &str, args: Vec<ExprId>) -> ExprId {
+        self.add(Expr::Call(name.to_string(), args))
+    }
+
+    // Declarations.
+
+    fn global(&mut self) -> VarDecl {
+        let is_const = self.rng.chance(30);
+        let def = if self.rng.chance(50) {
+            self.scalar_def(is_const, true)
+        } else {
+            self.array_def(is_const, true)
+        };
+        VarDecl {
+            is_global: true,
+            is_const,
+            is_extern: false,
+            btype: BType::Int,
+            var_defs: vec![def],
+            span: Span::default(),
+        }
+    }
+
+    fn local(&mut self) -> VarDecl {
+        let is_const = self.rng.chance(20);
+        let var_defs = if self.rng.chance(60) {
+            let cnt = self.rng.range(1, 2);
+            (0..cnt).map(|_| self.scalar_def(is_const, false)).collect()
+        } else {
+            vec![self.array_def(is_const, false)]
+        };
+        VarDecl {
+            is_global: false,
+            is_const,
+            is_extern: false,
+            btype: BType::Int,
+            var_defs,
+            span: Span::default(),
+        }
+    }
+
+    /// Defines a scalar, whose initializer must be constant if global.
+    fn scalar_def(&mut self, is_const: bool, is_global: bool) -> VarDef {
+        let name = self.fresh(if is_const { "c" } else if is_global { "g" } else { "v" });
+        let (init, value) = if is_const || is_global {
+            self.const_expr(2)
+        } else {
+            (self.expr(2), 0)
+        };
+        self.declare(Var {
+            name: name.clone(),
+            ty: Type::Int,
+            len: 0,
+            is_const,
+            is_counter: false,
+            value,
+        });
+        VarDef::Scalar(name, Some(init), Span::default())
+    }
+
+    fn array_def(&mut self, is_const: bool, is_global: bool) -> VarDef {
+        let name = self.fresh(if is_const { "ca" } else if is_global { "ga" } else { "a" });
+        let sizes = (0..self.rng.range(1, 3))
+            .map(|_| if self.rng.chance(10) { 10 } else { self.rng.range(1, 4) as i32 })
+            .collect::<Vec<_>>();
+        let init = self.init_list(&sizes, is_const || is_global);
+        let dims = sizes.iter().map(|size| self.lit(*size)).collect();
+        self.declare(Var {
+            name: name.clone(),
+            ty: Type::Array(sizes),
+            len: 0,
+            is_const,
+            is_counter: false,
+            value: 0,
+        });
+        VarDef::Array(name, dims, Some(init), Span::default())
+    }
+
+    /// Generates a partial initializer list for the given sizes.
+    /// Nested lists start at row boundaries, like the spec requires.
+    fn init_list(&mut self, sizes: &[i32], constant: bool) -> InitList {
+        let total = sizes.iter().product::<i32>();
+        let row = sizes[1..].iter().product::<i32>();
+        let filled = self.rng.range(0, total.min(8) as i64) as i32;
+        let mut inits = vec![];
+        let mut pos = 0;
+        while pos < filled {
+            if sizes.len() > 1 && pos % row == 0 && self.rng.chance(50) {
+                inits.push(self.init_list(&sizes[1..], constant));
+                pos += row;
+            } else {
+                let value = if constant { self.const_expr(1).0 } else { self.expr(1) };
+                inits.push(InitList::Exp(value));
+                pos += 1;
+            }
+        }
+        InitList::List(inits)
+    }
+
+    // Functions.
+
+    fn func(&mut self, is_main: bool) -> CompUnit {
+        let name = if is_main { "main".to_string() } else { self.fresh("f") };
+        let returns_int = is_main || self.rng.chance(60);
+        let cnt = match self.rng.chance(10) {
+            _ if is_main => 0,
+            true => self.rng.range(9, 10), // Passed on the stack too.
+            false => self.rng.range(0, 3),
+        };
+        let params = (0..cnt).map(|_| self.param()).collect::<Vec<_>>();
+        let fparams = params
+            .iter()
+            .map(|param| match &param.ty {
+                Type::Int => FuncFParam::Scalar(BType::Int, param.name.clone(), Span::default()),
+                Type::Pointer(sizes) => {
+                    let dims = sizes.iter().map(|size| self.lit(*size)).collect();
+                    FuncFParam::Array(BType::Int, param.name.clone(), dims, Span::default())
+                }
+                _ => panic!("Unexpected arm"),
+            })
+            .collect();
+
+        self.scopes.push(params.clone());
+        self.calls = 0;
+        let mut body = self.block(0, returns_int);
+        if is_main {
+            self.print_globals(&mut body);
+        }
+        if returns_int {
+            let exp = self.expr(2);
+            body.0.push(stmt(Stmt::Return(Some(exp))));
+        }
+        self.scopes.pop();
+
+        self.funcs.push(Func {
+            name: name.clone(),
+            returns_int,
+            params,
+        });
+        let btype = if returns_int { BType::Int } else { BType::Void };
+        CompUnit::FuncDef(FuncDef {
+            btype,
+            ident: name,
+            params: fparams,
+            block: body,
+            span: Span::default(),
+            name_span: Span::default(),
+        })
+    }
+
+    fn param(&mut self) -> Var {
+        let name = self.fresh("p");
+        let (ty, len) = if self.rng.chance(70) {
+            (Type::Int, 0)
+        } else {
+            let sizes = (0..self.rng.range(0, 1)).map(|_| self.rng.range(1, 3) as i32).collect();
+            (Type::Pointer(sizes), self.rng.range(1, 3) as i32)
+        };
+        Var {
+            name,
+            ty,
+            len,
+            is_const: false,
+            is_counter: false,
+            value: 0,
+        }
+    }
+
+    /// Prints non-constant global scalars, and rows of global arrays.
+    fn print_globals(&mut self, body: &mut Block) {
+        let globals = self.scopes[0].clone();
+        globals.iter().filter(|var| !var.is_const).for_each(|var| match &var.ty {
+            Type::Array(sizes) => {
+                let lead = &sizes[..sizes.len() - 1];
+                let rows = lead.iter().product::<i32>();
+                (0..rows).for_each(|row| {
+                    let mut arr = self.add(Expr::Var(var.name.clone()));
+                    let mut stride = rows;
+                    lead.iter().for_each(|size| {
+                        stride /= size;
+                        let index = self.lit(row / stride % size);
+                        arr = self.add(Expr::Index(arr, index));
+                    });
+                    let len = self.lit(*sizes.last().unwrap());
+                    let exp = self.call("putarray", vec![len, arr]);
+                    body.0.push(stmt(Stmt::Exp(exp)));
+                });
+            }
+            _ => {
+                let exp = self.add(Expr::Var(var.name.clone()));
+                self.print(exp, body);
+            }
+        });
+    }
+
+    /// Prints the given value and a newline.
+    fn print(&mut self, exp: ExprId, block: &mut Block) {
+        let call = self.call("putint", vec![exp]);
+        block.0.push(stmt(Stmt::Exp(call)));
+        let newline = self.lit(10);
+        let call = self.call("putch", vec![newline]);
+        block.0.push(stmt(Stmt::Exp(call)));
+    }
+
+    // Statements.
+
+    fn block(&mut self, depth: usize, returns_int: bool) -> Block {
+        self.scopes.push(vec![]);
+        let mut block = Block(vec![], Span::default());
+        (0..self.rng.range(1, 5)).for_each(|_| self.item(depth, returns_int, &mut block));
+        self.scopes.pop();
+        block
+    }
+
+    fn item(&mut self, depth: usize, returns_int: bool, block: &mut Block) {
+        let nested = depth < MAX_STMT_DEPTH;
+        match self.rng.range(0, 99) {
+            0..=19 => {
+                let decl = self.local();
+                block.0.push(BlockItem::VarDecl(decl));
+            }
+            20..=34 => {
+                let exp = self.expr(MAX_EXPR_DEPTH);
+                self.print(exp, block);
+            }
+            35..=46 if nested => {
+                let cond = self.cond();
+                let then = self.block(depth + 1, returns_int);
+                let otherwise = if self.rng.chance(50) {
+                    Some(Box::new(Stmt::Block(self.block(depth + 1, returns_int))))
+                } else {
+                    None
+                };
+                block.0.push(stmt(Stmt::If(cond, Box::new(Stmt::Block(then)), otherwise)));
+            }
+            47..=56 if nested && self.loop_depth < MAX_LOOP_DEPTH => {
+                let stmt = self.counted_loop(depth, returns_int);
+                block.0.push(stmt);
+            }
+            57..=61 if nested => {
+                let inner = self.block(depth + 1, returns_int);
+                block.0.push(stmt(Stmt::Block(inner)));
+            }
+            62..=67 => {
+                if let Some(exp) = self.func_call(false, 2) {
+                    block.0.push(stmt(Stmt::Exp(exp)));
+                }
+            }
+            68..=72 if self.loop_depth > 0 => {
+                let jump = if self.rng.chance(50) { Stmt::Break } else { Stmt::Continue };
+                let cond = self.cond();
+                block.0.push(stmt(Stmt::If(cond, Box::new(jump), None)));
+            }
+            73..=75 => {
+                let value = returns_int.then(|| self.expr(2));
+                let cond = self.cond();
+                block.0.push(stmt(Stmt::If(cond, Box::new(Stmt::Return(value)), None)));
+            }
+            _ => {
+                if let Some(assign) = self.assign() {
+                    block.0.push(stmt(assign));
+                }
+            }
+        }
+    }
+
+    /// Generates `{ int i = 0; while (i < n) { i = i + 1; ... } }`,
+    /// incrementing first, so that `continue` does too.
+    fn counted_loop(&mut self, depth: usize, returns_int: bool) -> BlockItem {
+        let name = self.fresh("i");
+        let zero = self.lit(0);
+        let decl = VarDecl {
+            is_global: false,
+            is_const: false,
+            is_extern: false,
+            btype: BType::Int,
+            var_defs: vec![VarDef::Scalar(name.clone(), Some(zero), Span::default())],
+            span: Span::default(),
+        };
+        let counter = self.add(Expr::Var(name.clone()));
+        let count = self.rng.range(1, MAX_LOOP_COUNT) as i32;
+        let count = self.lit(count);
+        let cond = self.add(Expr::Binary(BinaryOp::Lt, counter, count));
+
+        self.scopes.push(vec![Var {
+            name: name.clone(),
+            ty: Type::Int,
+            len: 0,
+            is_const: false,
+            is_counter: true,
+            value: 0,
+        }]);
+        self.loop_depth += 1;
+        let mut body = self.block(depth + 1, returns_int);
+        self.loop_depth -= 1;
+        self.scopes.pop();
+
+        let lhs = self.add(Expr::Var(name.clone()));
+        let var = self.add(Expr::Var(name));
+        let one = self.lit(1);
+        let inc = self.add(Expr::Binary(BinaryOp::Add, var, one));
+        body.0.insert(0, stmt(Stmt::Assign(lhs, inc)));
+        let stmt_while = stmt(Stmt::While(cond, Box::new(Stmt::Block(body))));
+        stmt(Stmt::Block(Block(vec![BlockItem::VarDecl(decl), stmt_while], Span::default())))
+    }
+
+    fn assign(&mut self) -> Option<Stmt> {
+        let targets = self
+            .vars()
+            .filter(|var| !var.is_const && !var.is_counter)
+            .cloned()
+            .collect::<Vec<_>>();
+        if targets.is_empty() {
+            return None;
+        }
+        let var = self.rng.pick(&targets).clone();
+        // Calls in indices may write what the value reads, so none are
+        // generated there, by spending the budget for the while.
+        let calls = std::mem::replace(&mut self.calls, MAX_CALLS);
+        let lval = self.lval(&var);
+        self.calls = calls;
+        let exp = self.expr(MAX_EXPR_DEPTH);
+        Some(Stmt::Assign(lval, exp))
+    }
+
+    // Expressions.
+
+    /// Generates a condition, likely with short-circuit operators.
+    fn cond(&mut self) -> ExprId {
+        let lhs = self.expr(2);
+        if self.rng.chance(40) {
+            return lhs;
+        }
+        let rhs = self.expr(2);
+        let op = if self.rng.chance(50) { BinaryOp::And } else { BinaryOp::Or };
+        self.add(Expr::Binary(op, lhs, rhs))
+    }
+
+    fn expr(&mut self, depth: usize) -> ExprId {
+        use BinaryOp::*;
+        if depth == 0 || self.rng.chance(25) {
+            return self.leaf();
+        }
+        match self.rng.range(0, 9) {
+            0..=2 => {
+                let name = *self.rng.pick(&["safe_add", "safe_sub", "safe_mul"]);
+                let args = vec![self.expr(depth - 1), self.expr(depth - 1)];
+                self.call(name, args)
+            }
+            3 => {
+                let op = *self.rng.pick(&[Lt, Le, Gt, Ge, Eq, Ne]);
+                self.binary(op, depth)
+            }
+            4 => {
+                let op = *self.rng.pick(&[And, Or]);
+                self.binary(op, depth)
+            }
+            5 => {
+                let exp = self.expr(depth - 1);
+                match self.rng.range(0, 2) {
+                    0 => self.add(Expr::Unary(UnaryOp::Plus, exp)),
+                    1 => self.add(Expr::Unary(UnaryOp::Not, exp)),
+                    _ => self.call("safe_neg", vec![exp]),
+                }
+            }
+            6 => {
+                let op = *self.rng.pick(&[Div, Rem]);
+                let lhs = self.expr(depth - 1);
+                let value = self.rng.range(1, 9) as i32;
+                let mut rhs = self.lit(value);
+                if value > 1 && self.rng.chance(30) {
+                    rhs = self.add(Expr::Unary(UnaryOp::Minus, rhs));
+                }
+                self.add(Expr::Binary(op, lhs, rhs))
+            }
+            7 => {
+                let name = *self.rng.pick(&["safe_div", "safe_mod"]);
+                let args = vec![self.expr(depth - 1), self.expr(depth - 1)];
+                self.call(name, args)
+            }
+            8 => self.func_call(true, depth - 1).unwrap_or_else(|| self.leaf()),
+            _ => self.leaf(),
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, depth: usize) -> ExprId {
+        let lhs = self.expr(depth - 1);
+        let rhs = self.expr(depth - 1);
+        self.add(Expr::Binary(op, lhs, rhs))
+    }
+
+    fn leaf(&mut self) -> ExprId {
+        let readable = self.vars().cloned().collect::<Vec<_>>();
+        if readable.is_empty() || self.rng.chance(40) {
+            let value = self.literal();
+            return self.lit(value);
+        }
+        let var = self.rng.pick(&readable).clone();
+        self.lval(&var)
+    }
+
+    /// Returns the value of a literal, small or on the edge of overflow.
+    fn literal(&mut self) -> i32 {
+        let value = match self.rng.range(0, 9) {
+            0 => self.rng.range(0, i32::MAX as i64),
+            1 => *self.rng.pick(&[i32::MAX as i64, 65536, 46341]),
+            _ => self.rng.range(0, 20),
+        };
+        value as i32
+    }
+
+    /// Returns the given variable indexed down to an `int`.
+    fn lval(&mut self, var: &Var) -> ExprId {
+        let mut id = self.add(Expr::Var(var.name.clone()));
+        for bound in var.bounds() {
+            let index = self.index(bound, var.is_const);
+            id = self.add(Expr::Index(id, index));
+        }
+        id
+    }
+
+    /// Returns an index below the given bound. Indices of constant arrays
+    /// are literals, so that they stay constant expressions.
+    fn index(&mut self, bound: i32, constant: bool) -> ExprId {
+        let value = self.rng.range(0, bound as i64 - 1) as i32;
+        if constant || self.rng.chance(70) {
+            return self.lit(value);
+        }
+        // ((e % n) + n) % n
+        let exp = self.expr(1);
+        let bound_ids = [self.lit(bound), self.lit(bound), self.lit(bound)];
+        let rem = self.add(Expr::Binary(BinaryOp::Rem, exp, bound_ids[0]));
+        let sum = self.add(Expr::Binary(BinaryOp::Add, rem, bound_ids[1]));
+        self.add(Expr::Binary(BinaryOp::Rem, sum, bound_ids[2]))
+    }
+
+    /// Generates a constant expression, of literals and constant scalars,
+    /// returns it along with its value.
+    fn const_expr(&mut self, depth: usize) -> (ExprId, i32) {
+        if depth == 0 || self.rng.chance(30) {
+            let consts = self.vars().filter(|var| var.is_const && var.ty == Type::Int);
+            let consts = consts.cloned().collect::<Vec<_>>();
+            if consts.is_empty() || self.rng.chance(50) {
+                let value = self.literal();
+                return (self.lit(value), value);
+            }
+            let var = self.rng.pick(&consts).clone();
+            return (self.lval(&var), var.value);
+        }
+        let (lhs, x) = self.const_expr(depth - 1);
+        if self.rng.chance(20) {
+            return match x.checked_neg() {
+                Some(value) => (self.add(Expr::Unary(UnaryOp::Minus, lhs)), value),
+                None => (lhs, x),
+            };
+        }
+        let op = *self.rng.pick(&[BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul]);
+        let (rhs, y) = self.const_expr(depth - 1);
+        let value = match op {
+            BinaryOp::Add => x.checked_add(y),
+            BinaryOp::Sub => x.checked_sub(y),
+            _ => x.checked_mul(y),
+        };
+        match value {
+            Some(value) => (self.add(Expr::Binary(op, lhs, rhs)), value),
+            None => (lhs, x),
+        }
+    }
+
+    /// Calls a generated function, returning `int` if asked to,
+    /// with arguments of the given depth. Returns `None` if there is
+    /// no such function, arrays to pass, or calls left in the budget.
+    fn func_call(&mut self, returns_int: bool, depth: usize) -> Option<ExprId> {
+        if self.calls >= MAX_CALLS {
+            return None;
+        }
+        let candidates = self
+            .funcs
+            .iter()
+            .enumerate()
+            .filter(|(_, func)| func.returns_int || !returns_int)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return None;
+        }
+        let func = *self.rng.pick(&candidates);
+        let params = self.funcs[func].params.clone();
+        let mut args = vec![];
+        for param in &params {
+            let arg = match &param.ty {
+                Type::Pointer(sizes) => self.array_arg(sizes, param.len)?,
+                _ => self.expr(depth),
+            };
+            args.push(arg);
+        }
+        self.calls += 1;
+        let name = self.funcs[func].name.clone();
+        Some(self.call(&name, args))
+    }
+
+    /// Returns an array, possibly indexed, that can be passed to a parameter
+    /// pointing to the given number of arrays of the given sizes.
+    fn array_arg(&mut self, sizes: &[i32], len: i32) -> Option<ExprId> {
+        // Variables and how many times they are indexed.
+        let mut candidates = vec![];
+        self.vars().filter(|var| !var.is_const).for_each(|var| {
+            let bounds = var.bounds();
+            (0..bounds.len()).for_each(|cnt| {
+                if bounds[cnt] >= len && bounds[cnt + 1..] == *sizes {
+                    candidates.push((var.clone(), cnt));
+                }
+            });
+        });
+        if candidates.is_empty() {
+            return None;
+        }
+        let (var, cnt) = self.rng.pick(&candidates).clone();
+        let mut id = self.add(Expr::Var(var.name.clone()));
+        for bound in &var.bounds()[..cnt] {
+            let index = self.index(*bound, false);
+            id = self.add(Expr::Index(id, index));
+        }
+        Some(id)
+    }
+}
+
+fn stmt(stmt: Stmt) -> BlockItem {
+    BlockItem::Stmt(stmt, Span::default())
+}
@@ -0,0 +1,181 @@
+//! Test case reducer, in the spirit of C-Reduce, but on ASTs.
+//! Edits are numbered in the order of a traversal, and tried one by one
+//! on a freshly parsed copy of the best program so far. An edit is kept
+//! if the program still diverges at the same stage; otherwise the next
+//! one is tried. Passes repeat until none of them helps.
+//! Edits never touch array indices or assignment targets, so that they
+//! don't introduce out-of-bounds accesses, which would make stages
+//! diverge for no fault of the compiler. For the same reason, the final
+//! `return` of a function returning `int` is never removed, or it could
+//! end without a value. Nor are the bodies of the `safe_*` helpers of the
+//! generator edited, or arithmetic could overflow. Invalid programs fail
+//! in the AST interpreter already, so they are never kept.
+
+use super::{divergence, parse};
+use crate::frontend::ast::*;
+
+pub struct Reducer {
+    target: usize, // Number of the edit to apply.
+    cnt: usize,    // Number of edits seen so far.
+}
+
+impl Reducer {
+    /// Reduces the given program while it diverges at the same stage,
+    /// returns `None` if it doesn't diverge.
+    pub fn run(prog: &str) -> Option<String> {
+        let stage = divergence(prog)?;
+        let mut best = parse(prog).to_string();
+        loop {
+            let len = best.len();
+            let mut target = 0;
+            while let Some(candidate) = Self::edit(&best, target) {
+                if divergence(&candidate) == Some(stage) {
+                    // The next edit now has the same number.
+                    best = candidate;
+                } else {
+                    target += 1;
+                }
+            }
+            if best.len() >= len {
+                return Some(best);
+            }
+        }
+    }
+
+    /// Applies the given edit to the given program,
+    /// returns `None` if there are not that many edits.
+    fn edit(prog: &str, target: usize) -> Option<String> {
+        let mut prog = parse(prog);
+        let mut reducer = Self { target, cnt: 0 };
+        reducer.edit_prog(&mut prog);
+        (reducer.cnt > target).then(|| prog.to_string())
+    }
+
+    /// Counts an edit, returns whether it is the one to apply.
+    fn hit(&mut self) -> bool {
+        self.cnt += 1;
+        self.cnt == self.target + 1
+    }
+
+    fn edit_prog(&mut self, prog: &mut Program) {
+        let Program(units, exprs, _) = prog;
+        if let Some(i) = (0..units.len()).find(|_| self.hit()) {
+            units.remove(i);
+            return;
+        }
+        units.iter_mut().for_each(|unit| match unit {
+            CompUnit::VarDecl(decl) => self.edit_decl(decl, exprs),
+            CompUnit::FuncDef(FuncDef { ident, .. }) if ident.starts_with("safe_") => {}
+            CompUnit::FuncDef(FuncDef { btype, block, .. }) => {
+                self.edit_block(block, exprs, *btype == BType::Int);
+            }
+            CompUnit::FuncDecl(..) => {}
+            CompUnit::Error(..) => panic!("Unexpected arm"),
+        });
+    }
+
+    fn edit_decl(&mut self, decl: &VarDecl, exprs: &mut Exprs) {
+        decl.var_defs.iter().for_each(|def| match def {
+            VarDef::Scalar(_, Some(exp), _) => self.edit_expr(*exp, exprs),
+            VarDef::Array(_, _, Some(init), _) => self.edit_init(init, exprs),
+            _ => {}
+        });
+    }
+
+    fn edit_init(&mut self, init: &InitList, exprs: &mut Exprs) {
+        match init {
+            InitList::Exp(exp) => self.edit_expr(*exp, exprs),
+            InitList::List(inits) => inits.iter().for_each(|init| self.edit_init(init, exprs)),
+            InitList::Flat(..) => panic!("Flattened initializer list"),
+        }
+    }
+
+    /// Edits the given block, which is the body of a function returning
+    /// `int` if `returns_int` is set, then its final `return` is kept.
+    fn edit_block(&mut self, block: &mut Block, exprs: &mut Exprs, returns_int: bool) {
+        let last = block.0.last();
+        let keep = returns_int && matches!(last, Some(BlockItem::Stmt(Stmt::Return(..), _)));
+        if let Some(i) = (0..block.0.len() - keep as usize).find(|_| self.hit()) {
+            block.0.remove(i);
+            return;
+        }
+        block.0.iter_mut().for_each(|item| match item {
+            BlockItem::VarDecl(decl) => self.edit_decl(decl, exprs),
+            BlockItem::Stmt(stmt, _) => self.edit_stmt(stmt, exprs),
+        });
+    }
+
+    fn edit_stmt(&mut self, stmt: &mut Stmt, exprs: &mut Exprs) {
+        use Stmt::*;
+        match stmt {
+            If(exp, then, opt_else) => {
+                if self.hit() {
+                    *stmt = std::mem::replace(&mut **then, Empty);
+                    return;
+                }
+                if let Some(otherwise) = opt_else {
+                    if self.hit() {
+                        *stmt = std::mem::replace(&mut **otherwise, Empty);
+                        return;
+                    }
+                    if self.hit() {
+                        *opt_else = None;
+                        return;
+                    }
+                }
+                self.edit_expr(*exp, exprs);
+                self.edit_stmt(then, exprs);
+                if let Some(otherwise) = opt_else {
+                    self.edit_stmt(otherwise, exprs);
+                }
+            }
+            // Conditions are left alone, or loops may never end.
+            While(_, body) => {
+                if self.hit() {
+                    *stmt = Empty;
+                    return;
+                }
+                self.edit_stmt(body, exprs);
+            }
+            Block(block) => self.edit_block(block, exprs, false),
+            Assign(_, exp) | Exp(exp) | Return(Some(exp)) => self.edit_expr(*exp, exprs),
+            Empty | Break | Continue | Return(None) => {}
+            Error => panic!("Unexpected arm"),
+        }
+    }
+
+    /// Replaces the given expression by 0, 1 or its operands, or edits
+    /// its operands.
+    fn edit_expr(&mut self, id: ExprId, exprs: &mut Exprs) {
+        let expr = exprs[id].clone();
+        if !matches!(expr, Expr::Lit(..)) {
+            for value in [0, 1] {
+                if self.hit() {
+                    exprs[id] = Expr::Lit(Lit::Int(value));
+                    return;
+                }
+            }
+        }
+        match expr {
+            Expr::Binary(_, lhs, rhs) => {
+                for operand in [lhs, rhs] {
+                    if self.hit() {
+                        exprs[id] = exprs[operand].clone();
+                        return;
+                    }
+                }
+                self.edit_expr(lhs, exprs);
+                self.edit_expr(rhs, exprs);
+            }
+            Expr::Unary(_, exp) => {
+                if self.hit() {
+                    exprs[id] = exprs[exp].clone();
+                    return;
+                }
+                self.edit_expr(exp, exprs);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| self.edit_expr(*arg, exprs)),
+            Expr::Index(..) | Expr::Var(..) | Expr::Lit(..) => {}
+        }
+    }
+}
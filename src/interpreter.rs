@@ -0,0 +1,83 @@
+//! Interpreters, giving reference semantics to check the compiler against.
+//! Programs run on the given input and output, usually stdin and stdout,
+//! with the runtime library built in.
+
+mod ast_interp;
+mod koopa_interp;
+mod riscv_sim;
+mod runtime;
+
+use crate::backend;
+use crate::frontend::Program;
+use ast_interp::AstInterp;
+use koopa_interp::KoopaInterp;
+use riscv_sim::RiscvSim;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::panic;
+use std::thread;
+
+/// Options of the interpreters.
+#[derive(Default)]
+pub struct Options {
+    pub step_limit: Option<u64>, // Of executed statements or instructions.
+    pub trace: bool,             // Whether to print each Koopa instruction to stderr.
+    pub stats: bool,             // Whether to print RISC-V instruction counts to stderr.
+}
+
+/// Input and output of the programs being run.
+pub struct Io<'a> {
+    pub input: Box<dyn BufRead + Send + 'a>,
+    pub output: Box<dyn Write + Send + 'a>, // Flushed on drop.
+}
+
+impl Io<'_> {
+    /// Returns stdin and stdout.
+    pub fn std() -> Self {
+        Self {
+            input: Box::new(BufReader::new(io::stdin())),
+            output: Box::new(BufWriter::new(io::stdout())),
+        }
+    }
+}
+
+/// Runs the given analyzed translation units as a whole,
+/// returns what `main` returns.
+pub fn run_ast(progs: &[Program], opts: Options, io: Io) -> i32 {
+    with_big_stack(|| AstInterp::run(progs, opts, io))
+}
+
+/// Compiles the given analyzed translation units to Koopa IR, and runs
+/// them as a whole, returns what `@main` returns.
+pub fn run_koopa(progs: &[Program], opts: Options, io: Io) -> i32 {
+    // Koopa programs are not `Send`, so they are built on the big stack too.
+    with_big_stack(|| {
+        let koopa = progs.iter().map(|prog| prog.to_koopa_program().0).collect::<Vec<_>>();
+        KoopaInterp::run(&koopa, opts, io)
+    })
+}
+
+/// Compiles the given analyzed translation units to RISC-V, and simulates
+/// them as a whole, returns what `main` returns.
+pub fn run_riscv(progs: &[Program], opts: Options, io: Io) -> i32 {
+    let riscv = progs
+        .iter()
+        .map(|prog| {
+            let (koopa, notes) = prog.to_koopa_program();
+            backend::riscv_from(&koopa, &notes)
+        })
+        .collect::<Vec<_>>();
+    RiscvSim::run(&riscv, opts, io)
+}
+
+/// Runs the given closure on a thread with a large stack, since
+/// interpreters recurse as deeply as the programs they run.
+fn with_big_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(1 << 30)
+            .spawn_scoped(scope, f)
+            .unwrap()
+            .join()
+            .unwrap_or_else(|err| panic::resume_unwind(err))
+    })
+}
@@ -0,0 +1,308 @@
+//! Tree-walking interpreter over analyzed ASTs.
+//! Memory is an array of `int`, addressed by index. Every variable,
+//! scalar or not, lives in memory, and arrays decay into their addresses.
+//! Locals are allocated on top, and freed when their blocks exit.
+
+use super::runtime::{Memory, Runtime};
+use super::{Io, Options};
+use crate::frontend::ast::*;
+use std::collections::HashMap;
+
+/// Memory of the interpreter. Address 0 is never allocated.
+struct Mem(Vec<i32>);
+
+impl Memory for Mem {
+    fn load(&self, ptr: i32, idx: i32) -> i32 {
+        let addr = ptr.wrapping_add(idx);
+        assert!(addr > 0 && (addr as usize) < self.0.len(), "Out-of-bounds load at {addr}");
+        self.0[addr as usize]
+    }
+
+    fn store(&mut self, ptr: i32, idx: i32, value: i32) {
+        let addr = ptr.wrapping_add(idx);
+        assert!(addr > 0 && (addr as usize) < self.0.len(), "Out-of-bounds store at {addr}");
+        self.0[addr as usize] = value;
+    }
+}
+
+/// How control leaves a statement.
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(i32),
+}
+
+pub struct AstInterp<'a> {
+    funcs: HashMap<&'a str, (&'a Exprs, &'a FuncDef)>,
+    globals: HashMap<&'a str, i32>, // Tokens to addresses.
+    env: HashMap<&'a str, i32>, // Locals of the current call, tokens are unique in a function.
+    exprs: &'a Exprs, // Of the translation unit being run.
+    mem: Mem,
+    runtime: Runtime<'a>,
+    opts: Options,
+    steps: u64, // Executed statements.
+}
+
+impl<'a> AstInterp<'a> {
+    /// Runs the given translation units as a whole, returns what `main` returns.
+    pub fn run(progs: &'a [Program], opts: Options, io: Io<'a>) -> i32 {
+        let mut interp = Self {
+            funcs: HashMap::new(),
+            globals: HashMap::new(),
+            env: HashMap::new(),
+            exprs: &progs[0].1,
+            mem: Mem(vec![0]),
+            runtime: Runtime::new(io),
+            opts,
+            steps: 0,
+        };
+        progs.iter().for_each(|prog| interp.load(prog));
+        assert!(interp.funcs.contains_key("main"), "Undefined function main");
+        interp.call("main", vec![])
+    }
+
+    /// Defines global variables and functions of the given program.
+    fn load(&mut self, prog: &'a Program) {
+        self.exprs = &prog.1;
+        prog.0.iter().for_each(|unit| match unit {
+            // Defined in other units.
+            CompUnit::VarDecl(decl) if decl.is_extern => {}
+            CompUnit::VarDecl(decl) => {
+                decl.var_defs.iter().for_each(|def| {
+                    if let Some(token) = self.define(decl, def) {
+                        let addr = self.env.remove(token).unwrap();
+                        self.globals.insert(token, addr);
+                    }
+                });
+            }
+            CompUnit::FuncDef(func) => {
+                self.funcs.insert(&func.ident, (&prog.1, func));
+            }
+            CompUnit::FuncDecl(..) => {}
+            CompUnit::Error(..) => panic!("Unexpected arm"),
+        });
+    }
+
+    /// Allocates the given variable and initializes it, returns its token.
+    /// Constant scalars have been replaced by their values, so they are skipped.
+    fn define(&mut self, decl: &VarDecl, def: &'a VarDef) -> Option<&'a str> {
+        match def {
+            VarDef::Scalar(..) if decl.is_const => None,
+            VarDef::Scalar(token, opt_exp, _) => {
+                let addr = self.alloc(1);
+                self.env.insert(token, addr);
+                if let Some(exp) = opt_exp {
+                    let value = self.eval(*exp);
+                    self.mem.store(addr, 0, value);
+                }
+                Some(token)
+            }
+            VarDef::Array(token, sizes, opt_init, _) => {
+                let len = sizes.iter().map(|size| self.exprs.value(*size) as usize).product();
+                let addr = self.alloc(len);
+                self.env.insert(token, addr);
+                // Fresh memory is zeroed, so are omitted values.
+                if let Some(InitList::Flat(runs)) = opt_init {
+                    runs.iter().for_each(|InitRun(offset, values)| {
+                        values.iter().enumerate().for_each(|(i, value)| {
+                            let value = self.eval(*value);
+                            self.mem.store(addr, (offset + i) as i32, value);
+                        });
+                    });
+                }
+                Some(token)
+            }
+        }
+    }
+
+    /// Allocates zeroed memory of the given number of `int`s on top.
+    fn alloc(&mut self, len: usize) -> i32 {
+        let addr = self.mem.0.len();
+        self.mem.0.resize(addr + len, 0);
+        addr as i32
+    }
+
+    /// Calls the given function, returns 0 for `void` ones.
+    fn call(&mut self, name: &str, args: Vec<i32>) -> i32 {
+        let Some(&(exprs, func)) = self.funcs.get(name) else {
+            return self.runtime.call(name, &args, &mut self.mem);
+        };
+        let env = std::mem::take(&mut self.env);
+        let caller = std::mem::replace(&mut self.exprs, exprs);
+        let top = self.mem.0.len();
+
+        // Array parameters hold pointers, just like scalars hold values.
+        func.params.iter().zip(args).for_each(|(param, arg)| {
+            let (FuncFParam::Scalar(_, token, _) | FuncFParam::Array(_, token, ..)) = param;
+            let addr = self.alloc(1);
+            self.mem.store(addr, 0, arg);
+            self.env.insert(token, addr);
+        });
+        let flow = self.exec_block(&func.block);
+
+        self.mem.0.truncate(top);
+        self.exprs = caller;
+        self.env = env;
+        match flow {
+            Flow::Return(value) => value,
+            _ => 0, // Falls off the end.
+        }
+    }
+
+    fn exec_block(&mut self, block: &'a Block) -> Flow {
+        let top = self.mem.0.len();
+        let mut flow = Flow::Next;
+        for item in &block.0 {
+            flow = match item {
+                BlockItem::Stmt(stmt, _) => self.exec(stmt),
+                BlockItem::VarDecl(decl) => {
+                    decl.var_defs.iter().for_each(|def| {
+                        self.define(decl, def);
+                    });
+                    Flow::Next
+                }
+            };
+            if !matches!(flow, Flow::Next) {
+                break;
+            }
+        }
+        self.mem.0.truncate(top);
+        flow
+    }
+
+    fn exec(&mut self, stmt: &'a Stmt) -> Flow {
+        use Stmt::*;
+        self.steps += 1;
+        if let Some(limit) = self.opts.step_limit {
+            assert!(self.steps <= limit, "Step limit of {limit} exceeded");
+        }
+        match stmt {
+            Assign(lval, exp) => {
+                // The value goes first, as in the compiled code.
+                let value = self.eval(*exp);
+                let addr = self.place(*lval);
+                self.mem.store(addr, 0, value);
+            }
+            Empty => {}
+            Exp(exp) => {
+                self.eval(*exp);
+            }
+            Block(block) => return self.exec_block(block),
+            If(exp, then, opt_else) => {
+                if self.eval(*exp) != 0 {
+                    return self.exec(then);
+                } else if let Some(otherwise) = opt_else {
+                    return self.exec(otherwise);
+                }
+            }
+            While(exp, body) => {
+                while self.eval(*exp) != 0 {
+                    match self.exec(body) {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Flow::Return(value),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            Break => return Flow::Break,
+            Continue => return Flow::Continue,
+            Return(opt_exp) => return Flow::Return(opt_exp.map_or(0, |exp| self.eval(exp))),
+            Error => panic!("Unexpected arm"),
+        }
+        Flow::Next
+    }
+
+    /// Evaluates the given expression. Arrays evaluate to their addresses.
+    fn eval(&mut self, id: ExprId) -> i32 {
+        match self.exprs[id].clone() {
+            Expr::Binary(BinaryOp::And, lhs, rhs) => {
+                (self.eval(lhs) != 0 && self.eval(rhs) != 0) as i32
+            }
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+                (self.eval(lhs) != 0 || self.eval(rhs) != 0) as i32
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let (x, y) = (self.eval(lhs), self.eval(rhs));
+                binary(op, x, y)
+            }
+            Expr::Unary(op, exp) => {
+                let x = self.eval(exp);
+                match op {
+                    UnaryOp::Plus => x,
+                    UnaryOp::Minus => x.wrapping_neg(),
+                    UnaryOp::Not => (x == 0) as i32,
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args.iter().map(|arg| self.eval(*arg)).collect();
+                self.call(&name, args)
+            }
+            Expr::Index(..) | Expr::Var(..) => {
+                let addr = self.place(id);
+                match self.exprs.ty(id) {
+                    Type::Int => self.mem.load(addr, 0),
+                    _ => addr,
+                }
+            }
+            Expr::Lit(Lit::Int(value)) => value,
+            Expr::Lit(Lit::Str(bytes)) => {
+                // Lives until the enclosing block exits.
+                let addr = self.alloc(bytes.len() + 1);
+                bytes.iter().enumerate().for_each(|(i, byte)| {
+                    self.mem.store(addr, i as i32, *byte as i32);
+                });
+                addr
+            }
+        }
+    }
+
+    /// Returns the address of the given variable or array element.
+    /// For an unindexed pointer, i.e. array parameter, this is the pointer itself.
+    fn place(&mut self, id: ExprId) -> i32 {
+        let exprs = self.exprs;
+        match &exprs[id] {
+            Expr::Var(token) => {
+                let addr = match self.env.get(token.as_str()) {
+                    Some(addr) => *addr,
+                    None => self.globals[token.as_str()],
+                };
+                match exprs.ty(id) {
+                    Type::Pointer(..) => self.mem.load(addr, 0),
+                    _ => addr,
+                }
+            }
+            &Expr::Index(arr, index) => {
+                let base = self.place(arr);
+                let idx = self.eval(index);
+                let size = match exprs.ty(id) {
+                    Type::Array(sizes) => sizes.iter().product(),
+                    _ => 1,
+                };
+                base.wrapping_add(idx.wrapping_mul(size))
+            }
+            _ => panic!("Unexpected arm"),
+        }
+    }
+}
+
+/// Applies a binary operator other than `&&` and `||`, wrapping on overflow
+/// like RISC-V. Division by zero is undefined, which panics.
+fn binary(op: BinaryOp, x: i32, y: i32) -> i32 {
+    use BinaryOp::*;
+    match op {
+        Div | Rem if y == 0 => panic!("Division by zero"),
+        Mul => x.wrapping_mul(y),
+        Div => x.wrapping_div(y),
+        Rem => x.wrapping_rem(y),
+        Add => x.wrapping_add(y),
+        Sub => x.wrapping_sub(y),
+        Lt => (x < y) as i32,
+        Le => (x <= y) as i32,
+        Gt => (x > y) as i32,
+        Ge => (x >= y) as i32,
+        Eq => (x == y) as i32,
+        Ne => (x != y) as i32,
+        And | Or => panic!("Unexpected arm"),
+    }
+}
@@ -0,0 +1,380 @@
+//! Interpreter over Koopa IR programs, as handed to the backend.
+//! Memory is an array of words, addressed by bytes like on a 32-bit machine,
+//! so `getptr` and `getelemptr` scale indices by type sizes, just like
+//! the backend does. Every value is a word, either an integer or an address.
+//! Translation units are linked by names: globals initialized with `undef`
+//! and functions without bodies refer to definitions in other units,
+//! or to the runtime library.
+
+use super::runtime::{Memory, Runtime};
+use super::{Io, Options};
+use koopa::ir::*;
+use std::collections::HashMap;
+
+/// Memory of the interpreter, in words. Address 0 is never allocated.
+struct Mem(Vec<i32>);
+
+impl Mem {
+    /// Returns the index of the word at the given address.
+    fn word(&self, addr: i32) -> usize {
+        let idx = (addr / 4) as usize;
+        assert!(addr > 0 && addr % 4 == 0 && idx < self.0.len(), "Invalid access at {addr}");
+        idx
+    }
+
+    /// Allocates zeroed memory of the given number of bytes on top.
+    fn alloc(&mut self, size: usize) -> i32 {
+        let top = self.0.len();
+        self.0.resize(top + size.div_ceil(4), 0);
+        (top * 4) as i32
+    }
+}
+
+impl Memory for Mem {
+    fn load(&self, ptr: i32, idx: i32) -> i32 {
+        self.0[self.word(ptr.wrapping_add(idx.wrapping_mul(4)))]
+    }
+
+    fn store(&mut self, ptr: i32, idx: i32, value: i32) {
+        let word = self.word(ptr.wrapping_add(idx.wrapping_mul(4)));
+        self.0[word] = value;
+    }
+
+    fn load_char(&self, ptr: i32, idx: i32) -> i32 {
+        let word = self.0[self.word(ptr.wrapping_add(idx & !3))];
+        (word >> (idx % 4 * 8)) & 0xff
+    }
+}
+
+/// A function being run.
+struct Frame<'a> {
+    unit: usize, // Index of the program containing the function.
+    func: &'a FunctionData,
+    env: HashMap<Value, i32>, // Parameters, block arguments and instruction results.
+}
+
+pub struct KoopaInterp<'a> {
+    progs: &'a [Program],
+    funcs: HashMap<&'a str, (usize, Function)>, // Defined functions by names.
+    globals: Vec<HashMap<Value, i32>>, // Addresses of global allocations, per unit.
+    mem: Mem,
+    runtime: Runtime<'a>,
+    opts: Options,
+    steps: u64,
+}
+
+impl<'a> KoopaInterp<'a> {
+    /// Runs the given programs as a whole, returns what `@main` returns.
+    pub fn run(progs: &'a [Program], opts: Options, io: Io<'a>) -> i32 {
+        let mut interp = Self {
+            progs,
+            funcs: HashMap::new(),
+            globals: vec![HashMap::new(); progs.len()],
+            mem: Mem(vec![0]),
+            runtime: Runtime::new(io),
+            opts,
+            steps: 0,
+        };
+        progs.iter().enumerate().for_each(|(unit, prog)| interp.load(unit, prog));
+        interp.link();
+        let Some(&(unit, main)) = interp.funcs.get("@main") else {
+            panic!("Undefined function @main");
+        };
+        interp.call(unit, main, vec![])
+    }
+
+    /// Defines global allocations and functions of the given program.
+    fn load(&mut self, unit: usize, prog: &'a Program) {
+        prog.funcs().iter().for_each(|(&func, data)| {
+            if data.layout().entry_bb().is_some() {
+                self.funcs.insert(data.name(), (unit, func));
+            }
+        });
+        prog.inst_layout().iter().for_each(|&value| {
+            let data = prog.borrow_value(value);
+            let ValueKind::GlobalAlloc(alloc) = data.kind() else {
+                panic!("Unexpected global value");
+            };
+            if let ValueKind::Undef(..) = prog.borrow_value(alloc.init()).kind() {
+                return; // Defined in other units.
+            }
+            let addr = self.mem.alloc(pointee(data.ty()).size());
+            self.init(prog, alloc.init(), addr);
+            self.globals[unit].insert(value, addr);
+        });
+    }
+
+    /// Writes the given initializer to the given address.
+    fn init(&mut self, prog: &Program, init: Value, addr: i32) {
+        let data = prog.borrow_value(init);
+        match data.kind() {
+            ValueKind::Integer(int) => self.mem.store(addr, 0, int.value()),
+            ValueKind::Aggregate(agg) => {
+                let mut addr = addr;
+                agg.elems().iter().for_each(|&elem| {
+                    self.init(prog, elem, addr);
+                    addr += prog.borrow_value(elem).ty().size() as i32;
+                });
+            }
+            // Fresh memory is zeroed.
+            ValueKind::ZeroInit(..) | ValueKind::Undef(..) => {}
+            _ => panic!("Unexpected initializer"),
+        }
+    }
+
+    /// Resolves global allocations initialized with `undef` by names.
+    fn link(&mut self) {
+        let defined = self
+            .progs
+            .iter()
+            .zip(&self.globals)
+            .flat_map(|(prog, globals)| {
+                globals.iter().map(|(&value, &addr)| {
+                    (prog.borrow_value(value).name().clone().unwrap(), addr)
+                })
+            })
+            .collect::<HashMap<_, _>>();
+        self.progs.iter().enumerate().for_each(|(unit, prog)| {
+            prog.inst_layout().iter().for_each(|&value| {
+                if self.globals[unit].contains_key(&value) {
+                    return;
+                }
+                let name = prog.borrow_value(value).name().clone().unwrap();
+                let Some(&addr) = defined.get(&name) else {
+                    panic!("Undefined variable {name}");
+                };
+                self.globals[unit].insert(value, addr);
+            });
+        });
+    }
+
+    /// Calls the given function of the given unit, returns 0 for `void` ones.
+    /// Functions without bodies are looked up in all units first,
+    /// then in the runtime library.
+    fn call(&mut self, unit: usize, func: Function, args: Vec<i32>) -> i32 {
+        let progs = self.progs;
+        let mut data = progs[unit].func(func);
+        let mut unit = unit;
+        if data.layout().entry_bb().is_none() {
+            match self.funcs.get(data.name()) {
+                Some(&(def_unit, def)) => {
+                    unit = def_unit;
+                    data = progs[unit].func(def);
+                }
+                None => return self.runtime.call(&data.name()[1..], &args, &mut self.mem),
+            }
+        }
+
+        let mut frame = Frame {
+            unit,
+            func: data,
+            env: data.params().iter().copied().zip(args).collect(),
+        };
+        let top = self.mem.0.len();
+        let mut bb = data.layout().entry_bb().unwrap();
+        let ret = 'run: loop {
+            let node = data.layout().bbs().node(&bb).unwrap();
+            for &inst in node.insts().keys() {
+                self.step(&frame, bb, inst);
+                let value = match data.dfg().value(inst).kind() {
+                    ValueKind::Jump(jump) => {
+                        self.pass(&mut frame, jump.target(), jump.args());
+                        bb = jump.target();
+                        continue 'run;
+                    }
+                    ValueKind::Branch(br) => {
+                        bb = if self.operand(&frame, br.cond()) != 0 {
+                            self.pass(&mut frame, br.true_bb(), br.true_args());
+                            br.true_bb()
+                        } else {
+                            self.pass(&mut frame, br.false_bb(), br.false_args());
+                            br.false_bb()
+                        };
+                        continue 'run;
+                    }
+                    ValueKind::Return(ret) => {
+                        break 'run ret.value().map_or(0, |value| self.operand(&frame, value));
+                    }
+                    _ => self.exec(&frame, inst),
+                };
+                frame.env.insert(inst, value);
+            }
+            panic!("Basic block without terminator");
+        };
+        self.mem.0.truncate(top);
+        ret
+    }
+
+    /// Binds arguments to parameters of the given basic block.
+    /// All arguments are evaluated before any is bound, since they may
+    /// refer to the parameters themselves in a loop.
+    fn pass(&mut self, frame: &mut Frame, bb: BasicBlock, args: &[Value]) {
+        let args = args.iter().map(|&arg| self.operand(frame, arg)).collect::<Vec<_>>();
+        let params = frame.func.dfg().bb(bb).params();
+        frame.env.extend(params.iter().copied().zip(args));
+    }
+
+    /// Executes the given non-terminator instruction, returns its result,
+    /// or 0 if it has none.
+    fn exec(&mut self, frame: &Frame, inst: Value) -> i32 {
+        let data = frame.func.dfg().value(inst);
+        match data.kind() {
+            ValueKind::Alloc(..) => self.mem.alloc(pointee(data.ty()).size()),
+            ValueKind::Load(load) => {
+                let addr = self.operand(frame, load.src());
+                self.mem.load(addr, 0)
+            }
+            ValueKind::Store(store) => {
+                let value = self.operand(frame, store.value());
+                let addr = self.operand(frame, store.dest());
+                self.mem.store(addr, 0, value);
+                0
+            }
+            ValueKind::GetPtr(ptr) => {
+                let size = pointee(&self.ty(frame, ptr.src())).size();
+                self.offset(frame, ptr.src(), ptr.index(), size)
+            }
+            ValueKind::GetElemPtr(ptr) => {
+                let size = match pointee(&self.ty(frame, ptr.src())).kind() {
+                    TypeKind::Array(elem, _) => elem.size(),
+                    _ => panic!("Unexpected arm"),
+                };
+                self.offset(frame, ptr.src(), ptr.index(), size)
+            }
+            ValueKind::Binary(bin) => {
+                let lhs = self.operand(frame, bin.lhs());
+                let rhs = self.operand(frame, bin.rhs());
+                binary(bin.op(), lhs, rhs)
+            }
+            ValueKind::Call(call) => {
+                let args = call.args().iter().map(|&arg| self.operand(frame, arg)).collect();
+                self.call(frame.unit, call.callee(), args)
+            }
+            _ => panic!("Unexpected instruction"),
+        }
+    }
+
+    /// Returns `src + index * size`, wrapping like address arithmetic.
+    fn offset(&self, frame: &Frame, src: Value, index: Value, size: usize) -> i32 {
+        let base = self.operand(frame, src);
+        let index = self.operand(frame, index);
+        base.wrapping_add(index.wrapping_mul(size as i32))
+    }
+
+    /// Returns the word the given value holds, i.e., an address for
+    /// global allocations.
+    fn operand(&self, frame: &Frame, value: Value) -> i32 {
+        if value.is_global() {
+            return self.globals[frame.unit][&value];
+        }
+        match frame.func.dfg().value(value).kind() {
+            ValueKind::Integer(int) => int.value(),
+            ValueKind::ZeroInit(..) | ValueKind::Undef(..) => 0,
+            _ => frame.env[&value],
+        }
+    }
+
+    fn ty(&self, frame: &Frame, value: Value) -> Type {
+        if value.is_global() {
+            self.progs[frame.unit].borrow_value(value).ty().clone()
+        } else {
+            frame.func.dfg().value(value).ty().clone()
+        }
+    }
+
+    /// Counts an instruction about to execute, traces it if asked to.
+    fn step(&mut self, frame: &Frame, bb: BasicBlock, inst: Value) {
+        self.steps += 1;
+        if let Some(limit) = self.opts.step_limit {
+            assert!(self.steps <= limit, "Step limit of {limit} exceeded");
+        }
+        if !self.opts.trace {
+            return;
+        }
+        let dfg = frame.func.dfg();
+        let data = dfg.value(inst);
+        let mnemonic = match data.kind() {
+            ValueKind::Alloc(..) => "alloc".to_string(),
+            ValueKind::Load(..) => "load".to_string(),
+            ValueKind::Store(..) => "store".to_string(),
+            ValueKind::GetPtr(..) => "getptr".to_string(),
+            ValueKind::GetElemPtr(..) => "getelemptr".to_string(),
+            ValueKind::Binary(bin) => bin.op().to_string(),
+            ValueKind::Branch(..) => "br".to_string(),
+            ValueKind::Jump(..) => "jump".to_string(),
+            ValueKind::Call(call) => {
+                let callee = self.progs[frame.unit].func(call.callee());
+                format!("call {}", callee.name())
+            }
+            ValueKind::Return(..) => "ret".to_string(),
+            _ => panic!("Unexpected instruction"),
+        };
+        // Operands are shown with their current words.
+        let operands = data
+            .kind()
+            .value_uses()
+            .map(|value| {
+                let word = self.operand(frame, value);
+                match self.name(frame, value) {
+                    Some(name) => format!("{name}({word})"),
+                    None => word.to_string(),
+                }
+            })
+            .chain(data.kind().bb_uses().map(|bb| dfg.bb(bb).name().clone().unwrap()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let dest = match data.name() {
+            Some(name) => format!("{name} = "),
+            None => String::new(),
+        };
+        let func = frame.func.name();
+        let bb = dfg.bb(bb).name().clone().unwrap();
+        let inst = format!("{dest}{mnemonic} {operands}");
+        eprintln!("[{}] {func} {bb}: {}", self.steps, inst.trim_end());
+    }
+
+    /// Returns the name of the given value, `None` for constants.
+    fn name(&self, frame: &Frame, value: Value) -> Option<String> {
+        if value.is_global() {
+            return self.progs[frame.unit].borrow_value(value).name().clone();
+        }
+        match frame.func.dfg().value(value).kind() {
+            ValueKind::Integer(..) | ValueKind::ZeroInit(..) | ValueKind::Undef(..) => None,
+            _ => frame.func.dfg().value(value).name().clone(),
+        }
+    }
+}
+
+/// Returns the base type of the given pointer type.
+fn pointee(ty: &Type) -> Type {
+    match ty.kind() {
+        TypeKind::Pointer(base) => base.clone(),
+        _ => panic!("Unexpected arm"),
+    }
+}
+
+/// Applies a binary operator, wrapping on overflow like RISC-V.
+/// Division by zero is undefined, which panics.
+fn binary(op: BinaryOp, x: i32, y: i32) -> i32 {
+    use BinaryOp::*;
+    match op {
+        Div | Mod if y == 0 => panic!("Division by zero"),
+        NotEq => (x != y) as i32,
+        Eq => (x == y) as i32,
+        Gt => (x > y) as i32,
+        Lt => (x < y) as i32,
+        Ge => (x >= y) as i32,
+        Le => (x <= y) as i32,
+        Add => x.wrapping_add(y),
+        Sub => x.wrapping_sub(y),
+        Mul => x.wrapping_mul(y),
+        Div => x.wrapping_div(y),
+        Mod => x.wrapping_rem(y),
+        And => x & y,
+        Or => x | y,
+        Xor => x ^ y,
+        Shl => x.wrapping_shl(y as u32),
+        Shr => (x as u32).wrapping_shr(y as u32) as i32,
+        Sar => x.wrapping_shr(y as u32),
+    }
+}
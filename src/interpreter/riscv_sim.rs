@@ -0,0 +1,373 @@
+//! Simulator of RV32IM, running in-memory RISCV programs from the backend.
+//! Translation units are laid out like a linker would: data of all units
+//! from `DATA_BASE` on, and the stack on top of them. Code lives apart,
+//! addressed by instruction indices, which is all `ra` ever holds.
+//! Calls to functions defined nowhere go to the runtime library,
+//! like system calls. Executed instructions are counted by mnemonics,
+//! to measure generated code without real hardware.
+
+use super::runtime::{Memory, Runtime};
+use super::{Io, Options};
+use crate::backend::riscv::{self, Inst, MemFill};
+use std::collections::HashMap;
+
+const DATA_BASE: usize = 0x10000; // Below is never mapped, catching null pointers.
+const STACK_SIZE: usize = 64 << 20;
+
+/// Registers in the order of their numbers, by ABI names.
+const REGS: [&str; 32] = [
+    "x0", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5",
+    "t6",
+];
+const RA: usize = 1;
+const SP: usize = 2;
+const A0: usize = 10;
+
+/// Returns the number of the given register.
+fn reg(name: &str) -> usize {
+    match name {
+        "zero" => 0,
+        "fp" => 8,
+        _ => REGS.iter().position(|reg| *reg == name).expect("Unknown register"),
+    }
+}
+
+/// Memory of the simulator, in little-endian bytes.
+struct Mem(Vec<u8>);
+
+impl Mem {
+    /// Returns the range of the given number of bytes at the given address.
+    fn range(&self, addr: i32, len: usize) -> std::ops::Range<usize> {
+        let begin = addr as u32 as usize;
+        assert!(begin >= DATA_BASE && begin + len <= self.0.len(), "Invalid access at {addr:#x}");
+        begin..begin + len
+    }
+}
+
+impl Memory for Mem {
+    fn load(&self, ptr: i32, idx: i32) -> i32 {
+        let range = self.range(ptr.wrapping_add(idx.wrapping_mul(4)), 4);
+        i32::from_le_bytes(self.0[range].try_into().unwrap())
+    }
+
+    fn store(&mut self, ptr: i32, idx: i32, value: i32) {
+        let range = self.range(ptr.wrapping_add(idx.wrapping_mul(4)), 4);
+        self.0[range].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn load_char(&self, ptr: i32, idx: i32) -> i32 {
+        self.0[self.range(ptr.wrapping_add(idx), 1).start] as i32
+    }
+}
+
+#[derive(Clone, Copy)]
+enum AluOp {
+    Add,
+    Sub,
+    Slt,
+    Sgt,
+    Seqz,
+    Snez,
+    Xor,
+    Or,
+    And,
+    Sll,
+    Srl,
+    Sra,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl AluOp {
+    /// Applies the operator like RV32IM, where division never traps.
+    fn apply(self, x: i32, y: i32) -> i32 {
+        use AluOp::*;
+        match self {
+            Add => x.wrapping_add(y),
+            Sub => x.wrapping_sub(y),
+            Slt => (x < y) as i32,
+            Sgt => (x > y) as i32,
+            Seqz => (x == 0) as i32,
+            Snez => (x != 0) as i32,
+            Xor => x ^ y,
+            Or => x | y,
+            And => x & y,
+            Sll => x.wrapping_shl(y as u32),
+            Srl => (x as u32).wrapping_shr(y as u32) as i32,
+            Sra => x.wrapping_shr(y as u32),
+            Mul => x.wrapping_mul(y),
+            Div if y == 0 => -1,
+            Rem if y == 0 => x,
+            Div => x.wrapping_div(y),
+            Rem => x.wrapping_rem(y),
+        }
+    }
+}
+
+/// Decoded instructions, with registers numbered and labels resolved.
+#[derive(Clone, Copy)]
+enum Op<'a> {
+    Beqz(usize, usize),
+    Bnez(usize, usize),
+    J(usize),
+    Call(usize),
+    Syscall(&'a str), // Call to the runtime library.
+    Ret,
+    Lw(usize, i32, usize),
+    Sw(usize, i32, usize),
+    Alu(AluOp, usize, usize, usize),
+    AluImm(AluOp, usize, usize, i32),
+    Li(usize, i32),
+}
+
+/// Labels visible in a translation unit.
+#[derive(Default)]
+struct Scope<'a> {
+    code: HashMap<&'a str, usize>,
+    data: HashMap<&'a str, i32>,
+}
+
+pub struct RiscvSim<'a> {
+    code: Vec<(Op<'a>, &'static str)>, // With mnemonics.
+    counts: Vec<u64>, // Executions of each instruction.
+    regs: [i32; 32],
+    mem: Mem,
+    stack_limit: usize,
+    runtime: Runtime<'a>,
+    opts: Options,
+}
+
+impl<'a> RiscvSim<'a> {
+    /// Runs the given programs as a whole, returns what `main` returns.
+    /// With statistics on, instruction counts are reported to stderr.
+    pub fn run(progs: &'a [riscv::Program], opts: Options, io: Io<'a>) -> i32 {
+        let mut sim = Self {
+            code: vec![],
+            counts: vec![],
+            regs: [0; 32],
+            mem: Mem(vec![0; DATA_BASE]),
+            stack_limit: 0,
+            runtime: Runtime::new(io),
+            opts,
+        };
+        let main = sim.load(progs);
+        let halt = sim.code.len();
+        sim.stack_limit = sim.mem.0.len();
+        sim.mem.0.resize(sim.stack_limit + STACK_SIZE, 0);
+        sim.regs[SP] = sim.mem.0.len() as i32;
+        sim.regs[RA] = halt as i32;
+
+        let mut pc = main;
+        let mut steps = 0;
+        while pc != halt {
+            steps += 1;
+            if let Some(limit) = sim.opts.step_limit {
+                assert!(steps <= limit, "Step limit of {limit} exceeded");
+            }
+            sim.counts[pc] += 1;
+            pc = sim.exec(pc);
+            assert!(pc <= halt, "Jump to invalid address {pc}");
+        }
+        if sim.opts.stats {
+            sim.report();
+        }
+        sim.regs[A0]
+    }
+
+    /// Lays out data and code of the given programs, returns where `main` is.
+    fn load(&mut self, progs: &'a [riscv::Program]) -> usize {
+        let mut global = Scope::default();
+        let mut scopes = progs.iter().map(|_| Scope::default()).collect::<Vec<_>>();
+        let mut insts = vec![];
+        progs.iter().zip(&mut scopes).enumerate().for_each(|(unit, (prog, scope))| {
+            prog.global_defs.iter().chain(&prog.rodata_defs).for_each(|def| {
+                let addr = self.alloc(def);
+                scope.data.insert(&def.name, addr);
+                if !def.is_local {
+                    global.data.insert(&def.name, addr);
+                }
+            });
+            prog.funcs.iter().for_each(|func| {
+                // Labels point to the next real instruction.
+                func.blocks.iter().for_each(|block| {
+                    scope.code.insert(&block.name, insts.len());
+                    block.insts.iter().filter(|inst| !is_pseudo(inst)).for_each(|inst| {
+                        insts.push((unit, inst));
+                    });
+                });
+                if !func.is_local {
+                    global.code.insert(&func.name, scope.code[func.name.as_str()]);
+                }
+            });
+        });
+
+        self.code = insts
+            .into_iter()
+            .map(|(unit, inst)| (decode(inst, &scopes[unit], &global), mnemonic(inst)))
+            .collect();
+        self.counts = vec![0; self.code.len()];
+        *global.code.get("main").expect("Undefined function main")
+    }
+
+    /// Allocates and initializes the given global, returns its address.
+    fn alloc(&mut self, def: &riscv::GlobalDef) -> i32 {
+        let mem = &mut self.mem.0;
+        mem.resize(mem.len().next_multiple_of(4), 0);
+        let addr = mem.len() as i32;
+        def.init.iter().for_each(|fill| match fill {
+            MemFill::Word(value) => mem.extend(value.to_le_bytes()),
+            MemFill::Zero(size) => mem.resize(mem.len() + size, 0),
+        });
+        addr
+    }
+
+    /// Executes the instruction at the given index, returns the next one.
+    fn exec(&mut self, pc: usize) -> usize {
+        match self.code[pc].0 {
+            Op::Beqz(rs, target) if self.regs[rs] == 0 => return target,
+            Op::Bnez(rs, target) if self.regs[rs] != 0 => return target,
+            Op::Beqz(..) | Op::Bnez(..) => {}
+            Op::J(target) => return target,
+            Op::Call(target) => {
+                self.set(RA, pc as i32 + 1);
+                return target;
+            }
+            Op::Syscall(func) => {
+                let args = self.regs[A0..A0 + Runtime::arity(func)].to_vec();
+                let value = self.runtime.call(func, &args, &mut self.mem);
+                self.set(A0, value);
+            }
+            Op::Ret => return self.regs[RA] as u32 as usize,
+            Op::Lw(rd, imm, rs) => {
+                let value = self.mem.load(self.regs[rs].wrapping_add(imm), 0);
+                self.set(rd, value);
+            }
+            Op::Sw(rs, imm, rd) => {
+                self.mem.store(self.regs[rd].wrapping_add(imm), 0, self.regs[rs]);
+            }
+            Op::Alu(op, rd, rs1, rs2) => self.set(rd, op.apply(self.regs[rs1], self.regs[rs2])),
+            Op::AluImm(op, rd, rs, imm) => self.set(rd, op.apply(self.regs[rs], imm)),
+            Op::Li(rd, imm) => self.set(rd, imm),
+        }
+        pc + 1
+    }
+
+    /// Writes the given register, where writes to `x0` are discarded.
+    fn set(&mut self, rd: usize, value: i32) {
+        if rd == SP {
+            assert!(value as u32 as usize >= self.stack_limit, "Stack overflow");
+        }
+        if rd != 0 {
+            self.regs[rd] = value;
+        }
+    }
+
+    /// Prints the total number of executed instructions, then the number
+    /// of each mnemonic, most executed first.
+    fn report(&self) {
+        let mut counts = HashMap::new();
+        self.code.iter().zip(&self.counts).for_each(|((_, mnemonic), cnt)| {
+            *counts.entry(*mnemonic).or_insert(0) += cnt;
+        });
+        let mut counts = counts.into_iter().filter(|(_, cnt)| *cnt > 0).collect::<Vec<_>>();
+        counts.sort_by(|lhs, rhs| rhs.1.cmp(&lhs.1).then(lhs.0.cmp(rhs.0)));
+        let total = self.counts.iter().sum::<u64>();
+        eprintln!("{total} instructions executed");
+        counts.iter().for_each(|(mnemonic, cnt)| eprintln!("{mnemonic:>8} {cnt}"));
+    }
+}
+
+/// Returns whether the given instruction is for debug information only.
+fn is_pseudo(inst: &Inst) -> bool {
+    use Inst::*;
+    matches!(
+        inst,
+        Loc { .. }
+            | CfiDefCfaOffset { .. }
+            | CfiOffset { .. }
+            | CfiRestore { .. }
+            | CfiRememberState
+            | CfiRestoreState
+    )
+}
+
+/// Decodes the given instruction, resolving labels in the unit first.
+fn decode<'a>(inst: &'a Inst, scope: &Scope, global: &Scope) -> Op<'a> {
+    use Inst::*;
+    let code = |label: &str| scope.code.get(label).or_else(|| global.code.get(label)).copied();
+    let target = |label: &str| code(label).unwrap_or_else(|| panic!("Undefined label {label}"));
+    match inst {
+        Beqz { rs, label } => Op::Beqz(reg(rs), target(label)),
+        Bnez { rs, label } => Op::Bnez(reg(rs), target(label)),
+        J { label } => Op::J(target(label)),
+        Call { label } => code(label).map_or(Op::Syscall(label), Op::Call),
+        Ret => Op::Ret,
+        Lw { rd, imm12, rs } => Op::Lw(reg(rd), *imm12, reg(rs)),
+        Sw { rs, imm12, rd } => Op::Sw(reg(rs), *imm12, reg(rd)),
+        Add { rd, rs1, rs2 } => Op::Alu(AluOp::Add, reg(rd), reg(rs1), reg(rs2)),
+        Addi { rd, rs, imm12 } => Op::AluImm(AluOp::Add, reg(rd), reg(rs), *imm12),
+        Sub { rd, rs1, rs2 } => Op::Alu(AluOp::Sub, reg(rd), reg(rs1), reg(rs2)),
+        Slt { rd, rs1, rs2 } => Op::Alu(AluOp::Slt, reg(rd), reg(rs1), reg(rs2)),
+        Sgt { rd, rs1, rs2 } => Op::Alu(AluOp::Sgt, reg(rd), reg(rs1), reg(rs2)),
+        Seqz { rd, rs } => Op::AluImm(AluOp::Seqz, reg(rd), reg(rs), 0),
+        Snez { rd, rs } => Op::AluImm(AluOp::Snez, reg(rd), reg(rs), 0),
+        Xor { rd, rs1, rs2 } => Op::Alu(AluOp::Xor, reg(rd), reg(rs1), reg(rs2)),
+        Xori { rd, rs, imm12 } => Op::AluImm(AluOp::Xor, reg(rd), reg(rs), *imm12),
+        Or { rd, rs1, rs2 } => Op::Alu(AluOp::Or, reg(rd), reg(rs1), reg(rs2)),
+        Ori { rd, rs, imm12 } => Op::AluImm(AluOp::Or, reg(rd), reg(rs), *imm12),
+        And { rd, rs1, rs2 } => Op::Alu(AluOp::And, reg(rd), reg(rs1), reg(rs2)),
+        Andi { rd, rs, imm12 } => Op::AluImm(AluOp::And, reg(rd), reg(rs), *imm12),
+        Sll { rd, rs1, rs2 } => Op::Alu(AluOp::Sll, reg(rd), reg(rs1), reg(rs2)),
+        Srl { rd, rs, rs2 } => Op::Alu(AluOp::Srl, reg(rd), reg(rs), reg(rs2)),
+        Sra { rd, rs, rs2 } => Op::Alu(AluOp::Sra, reg(rd), reg(rs), reg(rs2)),
+        Mul { rd, rs1, rs2 } => Op::Alu(AluOp::Mul, reg(rd), reg(rs1), reg(rs2)),
+        Div { rd, rs1, rs2 } => Op::Alu(AluOp::Div, reg(rd), reg(rs1), reg(rs2)),
+        Rem { rd, rs1, rs2 } => Op::Alu(AluOp::Rem, reg(rd), reg(rs1), reg(rs2)),
+        Li { rd, imm } => Op::Li(reg(rd), *imm),
+        La { rd, label } => {
+            let data = scope.data.get(label.as_str()).or_else(|| global.data.get(label.as_str()));
+            Op::Li(reg(rd), *data.unwrap_or_else(|| panic!("Undefined label {label}")))
+        }
+        Mv { rd, rs } => Op::AluImm(AluOp::Add, reg(rd), reg(rs), 0),
+        _ => panic!("Unexpected arm"),
+    }
+}
+
+/// Returns the mnemonic of the given instruction, as in assembly.
+fn mnemonic(inst: &Inst) -> &'static str {
+    use Inst::*;
+    match inst {
+        Beqz { .. } => "beqz",
+        Bnez { .. } => "bnez",
+        J { .. } => "j",
+        Call { .. } => "call",
+        Ret => "ret",
+        Lw { .. } => "lw",
+        Sw { .. } => "sw",
+        Add { .. } => "add",
+        Addi { .. } => "addi",
+        Sub { .. } => "sub",
+        Slt { .. } => "slt",
+        Sgt { .. } => "sgt",
+        Seqz { .. } => "seqz",
+        Snez { .. } => "snez",
+        Xor { .. } => "xor",
+        Xori { .. } => "xori",
+        Or { .. } => "or",
+        Ori { .. } => "ori",
+        And { .. } => "and",
+        Andi { .. } => "andi",
+        Sll { .. } => "sll",
+        Srl { .. } => "srl",
+        Sra { .. } => "sra",
+        Mul { .. } => "mul",
+        Div { .. } => "div",
+        Rem { .. } => "rem",
+        Li { .. } => "li",
+        La { .. } => "la",
+        Mv { .. } => "mv",
+        _ => panic!("Unexpected arm"),
+    }
+}
@@ -0,0 +1,133 @@
+//! The SysY runtime library, over the given input and output.
+//! It behaves like `sylib.c` of the official runtime, except that
+//! timer functions do nothing.
+
+use super::Io;
+use std::io::{BufRead, Write};
+
+/// Memory of a program, seen by the runtime library through pointers to `int`.
+/// Interpreters have their own ideas of what a pointer is.
+pub trait Memory {
+    /// Loads the `idx`-th `int` from the given pointer.
+    fn load(&self, ptr: i32, idx: i32) -> i32;
+
+    /// Stores to the `idx`-th `int` from the given pointer.
+    fn store(&mut self, ptr: i32, idx: i32, value: i32);
+
+    /// Loads the `idx`-th character of a string from the given pointer.
+    /// String literals are arrays of `int`, one per byte, in the AST,
+    /// and packed into words in Koopa, laid out like bytes in RISC-V.
+    fn load_char(&self, ptr: i32, idx: i32) -> i32 {
+        self.load(ptr, idx)
+    }
+}
+
+pub struct Runtime<'a> {
+    // Read only as far as `get*` asks, so that programs reading nothing
+    // don't wait for the end of stdin.
+    input: Box<dyn BufRead + Send + 'a>,
+    output: Box<dyn Write + Send + 'a>,
+}
+
+impl<'a> Runtime<'a> {
+    pub fn new(io: Io<'a>) -> Self {
+        Self { input: io.input, output: io.output }
+    }
+
+    /// Calls the given runtime library function, returns 0 for `void` ones.
+    pub fn call(&mut self, func: &str, args: &[i32], mem: &mut impl Memory) -> i32 {
+        match (func, args) {
+            ("getint", []) => self.getint(),
+            ("getch", []) => self.getch(),
+            ("getarray", &[ptr]) => {
+                let len = self.getint();
+                (0..len).for_each(|idx| {
+                    let value = self.getint();
+                    mem.store(ptr, idx, value);
+                });
+                len
+            }
+            ("putint", &[value]) => {
+                write!(self.output, "{value}").unwrap();
+                0
+            }
+            ("putch", &[value]) => {
+                self.output.write_all(&[value as u8]).unwrap();
+                0
+            }
+            ("putarray", &[len, ptr]) => {
+                write!(self.output, "{len}:").unwrap();
+                (0..len).for_each(|idx| write!(self.output, " {}", mem.load(ptr, idx)).unwrap());
+                writeln!(self.output).unwrap();
+                0
+            }
+            // `putstr` is ours, lowered to `putf` of a format only, see the midend.
+            ("putstr", &[ptr]) => {
+                let mut idx = 0;
+                while let byte @ 1.. = mem.load_char(ptr, idx) {
+                    self.output.write_all(&[byte as u8]).unwrap();
+                    idx += 1;
+                }
+                0
+            }
+            ("putf", &[ptr]) => {
+                let mut idx = 0;
+                while let byte @ 1.. = mem.load_char(ptr, idx) {
+                    idx += 1;
+                    if byte == b'%' as i32 {
+                        let spec = mem.load_char(ptr, idx);
+                        assert_eq!(spec, b'%' as i32, "Unsupported format in putf");
+                        idx += 1;
+                    }
+                    self.output.write_all(&[byte as u8]).unwrap();
+                }
+                0
+            }
+            ("_sysy_starttime" | "_sysy_stoptime", &[_]) => 0,
+            _ => panic!("Undefined function {func}"),
+        }
+    }
+
+    /// Returns the number of parameters of the given runtime library function.
+    pub fn arity(func: &str) -> usize {
+        match func {
+            "getint" | "getch" => 0,
+            "getarray" | "putint" | "putch" | "putstr" | "putf" => 1,
+            "_sysy_starttime" | "_sysy_stoptime" => 1,
+            "putarray" => 2,
+            _ => panic!("Undefined function {func}"),
+        }
+    }
+
+    /// Reads a decimal integer, like `scanf("%d")`. Returns 0 at the end of input.
+    fn getint(&mut self) -> i32 {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.input.consume(1);
+        }
+        let mut text = String::new();
+        if let Some(c @ (b'-' | b'+')) = self.peek() {
+            text.push(c as char);
+            self.input.consume(1);
+        }
+        while let Some(c @ b'0'..=b'9') = self.peek() {
+            text.push(c as char);
+            self.input.consume(1);
+        }
+        text.parse::<i64>().map_or(0, |value| value as i32)
+    }
+
+    /// Reads a byte, like `getchar()`. Returns -1 at the end of input.
+    fn getch(&mut self) -> i32 {
+        let Some(byte) = self.peek() else {
+            return -1;
+        };
+        self.input.consume(1);
+        byte as i32
+    }
+
+    /// Returns the next byte of input without reading past it,
+    /// waiting for more if there is none yet.
+    fn peek(&mut self) -> Option<u8> {
+        self.input.fill_buf().unwrap().first().copied()
+    }
+}
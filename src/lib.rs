@@ -0,0 +1,10 @@
+//! We assume the input program is both syntactically and semantically correct.
+//! Otherwise, anything could happen, probably just panic somewhere.
+//! This applies to all modules in this crate.
+
+pub mod backend;
+pub mod frontend;
+pub mod fuzz;
+pub mod interpreter;
+pub mod lsp;
+pub mod midend;
@@ -0,0 +1,440 @@
+//! Language server of SysY, speaking the language server protocol
+//! over standard input and output. Documents are checked by the frontend
+//! whenever they change, reporting errors and warnings as diagnostics,
+//! and the index of symbols answers hover, go-to-definition,
+//! find-references and document symbol requests.
+//! Documents are synchronized as a whole, and positions are in bytes,
+//! i.e. the `utf-8` position encoding, which the server announces.
+//! Clients stuck with UTF-16 are still right on ASCII lines, and SysY has
+//! other characters only in comments and strings.
+
+mod json;
+
+pub use json::Json;
+
+use crate::frontend::ast::{CompUnit, Span, VarDef};
+use crate::frontend::{Index, Program, Warning};
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Runs the server on the given streams until the client exits it.
+/// Returns whether it was shut down before exiting.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> bool {
+    let mut server = Server {
+        docs: HashMap::new(),
+        is_shutdown: false,
+    };
+    while let Some(msg) = read_message(&mut input) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(err) => {
+                // There is no ID to reply to.
+                log_message(&mut output, &format!("Skipped message: {err}"));
+                continue;
+            }
+        };
+        let msg = match Json::parse(&msg) {
+            Ok(msg) => msg,
+            Err(err) => {
+                let reply = Json::object([("id", Json::Null), ("error", error(-32700, err))]);
+                write_message(&mut output, reply);
+                continue;
+            }
+        };
+        let method = msg.get("method").as_str().unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        let params = msg.get("params");
+        let id = msg.get("id");
+        if *id == Json::Null {
+            server.notify(method, params, &mut output);
+            continue;
+        }
+        let reply = match server.request(method, params) {
+            Some(result) => Json::object([("id", id.clone()), ("result", result)]),
+            None => {
+                let error = error(-32601, format!("Unsupported method {method}"));
+                Json::object([("id", id.clone()), ("error", error)])
+            }
+        };
+        write_message(&mut output, reply);
+    }
+    server.is_shutdown
+}
+
+/// Returns an error of a reply with the given code.
+fn error(code: i32, msg: String) -> Json {
+    Json::object([("code", Json::Number(code as f64)), ("message", msg.into())])
+}
+
+/// Reads a message framed by a `Content-Length` header, returns `None`
+/// at the end of input, or an error if the message is to be skipped.
+fn read_message(input: &mut impl BufRead) -> Option<Result<String, String>> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        // The body of a message without the header runs into the next header.
+        let header = line.to_ascii_lowercase().find("content-length:");
+        if let Some(pos) = header {
+            len = Some(line[pos + "content-length:".len()..].trim().parse::<usize>());
+        }
+    }
+    let len = match len {
+        Some(Ok(len)) => len,
+        Some(Err(err)) => return Some(Err(format!("Invalid Content-Length: {err}"))),
+        None => return Some(Err("Missing Content-Length".to_string())),
+    };
+    let mut body = vec![0; len];
+    input.read_exact(&mut body).ok()?;
+    Some(String::from_utf8(body).map_err(|err| err.to_string()))
+}
+
+/// Writes the given message, adding the `jsonrpc` field and the header.
+fn write_message(output: &mut impl Write, msg: Json) {
+    let Json::Object(mut pairs) = msg else {
+        panic!("Unexpected arm");
+    };
+    pairs.insert(0, ("jsonrpc".to_string(), "2.0".into()));
+    let body = Json::Object(pairs).to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+    output.flush().unwrap();
+}
+
+/// An open document, along with the result of checking it, if it passes.
+struct Document {
+    path: String,
+    analysis: Option<(Program, Index)>,
+}
+
+struct Server {
+    docs: HashMap<String, Document>, // Keyed by URIs.
+    is_shutdown: bool,
+}
+
+impl Server {
+    /// Handles a notification, which has no reply.
+    fn notify(&mut self, method: &str, params: &Json, output: &mut impl Write) {
+        let doc = params.get("textDocument");
+        let Some(uri) = doc.get("uri").as_str() else {
+            return;
+        };
+        let text = match method {
+            "textDocument/didOpen" => doc.get("text"),
+            "textDocument/didChange" => {
+                // Only full synchronization is supported, so the last change is the text.
+                let Json::Array(changes) = params.get("contentChanges") else {
+                    return;
+                };
+                changes.last().map_or(&Json::Null, |change| change.get("text"))
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(uri);
+                publish_diagnostics(output, uri, vec![]);
+                return;
+            }
+            _ => return,
+        };
+        let Some(text) = text.as_str() else {
+            return;
+        };
+        let path = uri_to_path(uri);
+        let (analysis, diagnostics) = check(text, &path);
+        self.docs.insert(uri.to_string(), Document { path, analysis });
+        publish_diagnostics(output, uri, diagnostics);
+    }
+
+    /// Handles a request, returns the result, or `None` if it is not supported.
+    fn request(&mut self, method: &str, params: &Json) -> Option<Json> {
+        let result = match method {
+            "initialize" => {
+                let capabilities = Json::object([
+                    ("textDocumentSync", Json::Number(1.0)), // Full.
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    ("referencesProvider", true.into()),
+                    ("documentSymbolProvider", true.into()),
+                    ("positionEncoding", "utf-8".into()),
+                ]);
+                let info = Json::object([("name", "sysy-lsp".into())]);
+                Json::object([("capabilities", capabilities), ("serverInfo", info)])
+            }
+            "shutdown" => {
+                self.is_shutdown = true;
+                Json::Null
+            }
+            "textDocument/hover" => self.hover(params).unwrap_or(Json::Null),
+            "textDocument/definition" => self.definition(params).unwrap_or(Json::Null),
+            "textDocument/references" => self.references(params).unwrap_or(Json::Null),
+            "textDocument/documentSymbol" => self.document_symbols(params).unwrap_or(Json::Null),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// Returns the analyzed document of the given request,
+    /// along with the offset in preprocessed text of its position, if any.
+    fn analysis(&self, params: &Json) -> Option<(&Document, &Program, &Index, Option<usize>)> {
+        let doc = self.docs.get(params.get("textDocument").get("uri").as_str()?)?;
+        let (prog, index) = doc.analysis.as_ref()?;
+        let pos = params.get("position");
+        let offset = pos.get("line").as_usize().zip(pos.get("character").as_usize());
+        let offset = offset.and_then(|(line, column)| prog.2.offset(&doc.path, line + 1, column));
+        Some((doc, prog, index, offset))
+    }
+
+    /// Shows the declaration of the symbol at the position, or else
+    /// the type and the folded value of the innermost expression there.
+    fn hover(&self, params: &Json) -> Option<Json> {
+        let (_, prog, index, offset) = self.analysis(params)?;
+        let offset = offset?;
+        let text = match index.key_at(offset) {
+            Some(key) => format!("```sysy\n{}\n```", index.symbols[key].detail),
+            None => {
+                let info = prog
+                    .1
+                    .infos()
+                    .filter(|info| info.ty.is_some())
+                    .filter(|info| info.span.begin <= offset && offset < info.span.end)
+                    .min_by_key(|info| info.span.end - info.span.begin)?;
+                let ty = info.ty.as_ref().unwrap();
+                match info.value {
+                    Some(value) => format!("```sysy\n{ty}\n```\nValue: `{value}`"),
+                    None => format!("```sysy\n{ty}\n```"),
+                }
+            }
+        };
+        let contents = Json::object([("kind", "markdown".into()), ("value", text.into())]);
+        Some(Json::object([("contents", contents)]))
+    }
+
+    fn definition(&self, params: &Json) -> Option<Json> {
+        let (_, prog, index, offset) = self.analysis(params)?;
+        let key = index.key_at(offset?)?;
+        location(prog, index.symbols[key].decl?)
+    }
+
+    fn references(&self, params: &Json) -> Option<Json> {
+        let (_, prog, index, offset) = self.analysis(params)?;
+        let key = index.key_at(offset?)?;
+        let with_decl = params.get("context").get("includeDeclaration").as_bool();
+        let decl = index.symbols[key].decl.map(|span| span.begin);
+        let refs = index
+            .refs_of(key)
+            .filter(|span| with_decl.unwrap_or(true) || Some(span.begin) != decl)
+            .filter_map(|span| location(prog, span))
+            .collect::<Vec<_>>();
+        Some(refs.into())
+    }
+
+    /// Lists functions and global variables declared in the document itself.
+    fn document_symbols(&self, params: &Json) -> Option<Json> {
+        let (doc, prog, index, _) = self.analysis(params)?;
+        let symbol = |span: Span, name: Span, kind: usize| {
+            let info = &index.symbols[index.key_at(name.begin)?];
+            let range = range(prog, span).filter(|(file, _)| *file == doc.path)?.1;
+            let selection = range_of(prog, name)?;
+            Some(Json::object([
+                ("name", info.name.as_str().into()),
+                ("detail", info.detail.as_str().into()),
+                ("kind", kind.into()),
+                ("range", range),
+                ("selectionRange", selection),
+            ]))
+        };
+        let symbols = prog
+            .0
+            .iter()
+            .flat_map(|unit| match unit {
+                CompUnit::VarDecl(decl) => {
+                    // Constants are `Constant`, others are `Variable`.
+                    let kind = if decl.is_const { 14 } else { 13 };
+                    let defs = decl.var_defs.iter().map(|def| {
+                        let (VarDef::Scalar(.., name) | VarDef::Array(.., name)) = def;
+                        symbol(decl.span, *name, kind)
+                    });
+                    defs.collect::<Vec<_>>()
+                }
+                CompUnit::FuncDef(def) => vec![symbol(def.span, def.name_span, 12)], // `Function`.
+                CompUnit::FuncDecl(decl) => vec![symbol(decl.span, decl.name_span, 12)],
+                CompUnit::Error(..) => vec![],
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        Some(symbols.into())
+    }
+}
+
+/// Checks the given document, returns the analyzed program with its index
+/// if it passes, along with the diagnostics.
+fn check(text: &str, path: &str) -> (Option<(Program, Index)>, Vec<Json>) {
+    // Errors panic in the frontend, silence them while catching.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let parsed = panic::catch_unwind(|| Program::parse_sysy_text_recovering(text, path));
+    let (prog, mut warnings, mut errors) = parsed.unwrap_or_else(|payload| {
+        (None, vec![], vec![panic_msg(payload)])
+    });
+    // The partial AST of a program with syntax errors is still analyzed,
+    // for semantic errors and navigation, but its warnings may be spurious.
+    let is_partial = !errors.is_empty();
+    let analysis = prog.and_then(|prog| {
+        match panic::catch_unwind(AssertUnwindSafe(|| prog.analyze_sem_with_index())) {
+            Ok((prog, sem_warnings, index)) => {
+                warnings.extend(sem_warnings);
+                Some((prog, index))
+            }
+            Err(payload) => {
+                errors.push(panic_msg(payload));
+                None
+            }
+        }
+    });
+    panic::set_hook(hook);
+
+    let mut diagnostics = errors
+        .iter()
+        .map(|msg| error_diagnostic(text, path, msg))
+        .collect::<Vec<_>>();
+    if let (Some((prog, _)), false) = (&analysis, is_partial) {
+        diagnostics.extend(
+            warnings.iter().filter_map(|warning| warning_diagnostic(prog, text, path, warning)),
+        );
+    }
+    (analysis, diagnostics)
+}
+
+/// Returns the message a panic is raised with.
+fn panic_msg(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|msg| msg.to_string()))
+        .unwrap_or("Unknown error".to_string())
+}
+
+/// Turns an error message into a diagnostic over the line it is located at,
+/// like `file:line: msg`. Errors elsewhere, e.g. in included files,
+/// are put on the first line, located as they are.
+fn error_diagnostic(text: &str, path: &str, msg: &str) -> Json {
+    let located = msg
+        .strip_prefix(path)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|rest| rest.split_once(": "))
+        .and_then(|(line, msg)| Some((line.parse::<usize>().ok()?.checked_sub(1)?, msg)));
+    let (line, msg) = located.unwrap_or((0, msg));
+    let len = text.lines().nth(line).map_or(0, str::len);
+    let range = Json::object([("start", position(line, 0)), ("end", position(line, len))]);
+    diagnostic(range, 1, msg, None)
+}
+
+/// Turns a warning into a diagnostic over the word it is located at,
+/// or `None` if it is not in the document.
+fn warning_diagnostic(prog: &Program, text: &str, path: &str, warning: &Warning) -> Option<Json> {
+    let (file, line, column) = prog.2.position(warning.offset);
+    if file != path {
+        return None;
+    }
+    let line = line - 1;
+    let rest = text.lines().nth(line).and_then(|s| s.get(column..)).unwrap_or_default();
+    let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+    let end = column + len.max(1);
+    let range = Json::object([("start", position(line, column)), ("end", position(line, end))]);
+    Some(diagnostic(range, 2, &warning.msg, Some(warning.lint.name())))
+}
+
+fn diagnostic(range: Json, severity: usize, msg: &str, code: Option<&str>) -> Json {
+    let mut diagnostic = Json::object([
+        ("range", range),
+        ("severity", severity.into()),
+        ("source", "sysy".into()),
+        ("message", msg.into()),
+    ]);
+    if let (Some(code), Json::Object(pairs)) = (code, &mut diagnostic) {
+        pairs.push(("code".to_string(), code.into()));
+    }
+    diagnostic
+}
+
+fn publish_diagnostics(output: &mut impl Write, uri: &str, diagnostics: Vec<Json>) {
+    let params = Json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]);
+    let msg = Json::object([
+        ("method", "textDocument/publishDiagnostics".into()),
+        ("params", params),
+    ]);
+    write_message(output, msg);
+}
+
+/// Logs the given message as an error in the client.
+fn log_message(output: &mut impl Write, msg: &str) {
+    let params = Json::object([("type", 1.into()), ("message", msg.into())]);
+    let msg = Json::object([("method", "window/logMessage".into()), ("params", params)]);
+    write_message(output, msg);
+}
+
+fn position(line: usize, character: usize) -> Json {
+    Json::object([("line", line.into()), ("character", character.into())])
+}
+
+/// Returns the file the given span is in, along with its range there.
+fn range(prog: &Program, span: Span) -> Option<(&str, Json)> {
+    let (file, line, column) = prog.2.position(span.begin);
+    let (end_file, end_line, end_column) = prog.2.position(span.end);
+    if file != end_file {
+        return None;
+    }
+    let (start, end) = (position(line - 1, column), position(end_line - 1, end_column));
+    Some((file, Json::object([("start", start), ("end", end)])))
+}
+
+fn range_of(prog: &Program, span: Span) -> Option<Json> {
+    range(prog, span).map(|(_, range)| range)
+}
+
+fn location(prog: &Program, span: Span) -> Option<Json> {
+    let (file, range) = range(prog, span)?;
+    Some(Json::object([("uri", path_to_uri(file).into()), ("range", range)]))
+}
+
+/// Converts a `file:` URI to a path, decoding escaped bytes.
+fn uri_to_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&c, tail)) = rest.split_first() {
+        let hex = tail.get(..2).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if c == b'%' => {
+                bytes.push(byte);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(c);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Converts a path to a `file:` URI, escaping bytes other than unreserved ones.
+fn path_to_uri(path: &str) -> String {
+    let path = path.bytes().fold(String::new(), |mut uri, c| {
+        if c.is_ascii_alphanumeric() || b"/-._~".contains(&c) {
+            uri.push(c as char);
+        } else {
+            uri.push_str(&format!("%{c:02X}"));
+        }
+        uri
+    });
+    format!("file://{path}")
+}
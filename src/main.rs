@@ -1,39 +1,140 @@
-//! We assume the input program is both syntactically and semantically correct.
-//! Otherwise, anything could happen, probably just panic somewhere.
-//! This applies to all modules in this crate.
+//! Command line driver of the compiler, see the library for the stages.
 
-mod backend;
-mod frontend;
-mod midend;
-
-use frontend::Program;
+use sysy_compiler::frontend::{Lints, Program};
+use sysy_compiler::{backend, fuzz, interpreter};
 use std::env;
 use std::fs;
 use std::io::Write;
+use std::panic;
+use std::path::Path;
+use std::process;
 
 fn main() {
-    let [_, ref mode, ref input, _, ref output] = env::args().collect::<Vec<_>>()[..] else {
-        panic!("Incorrect command line arguments");
+    // Usage: sysy-compiler <mode> <input>... -o <output> [<lint flag>...]
+    // With multiple inputs, `output` is a directory, where each
+    // translation unit is written to a file named after its input.
+    // Run modes (`-run-ast`, `-run-koopa`, `-run-riscv`) take no output,
+    // but run the program on stdin and stdout, and exit with what `main` returns.
+    // Lint flags (`-W<lint>`, `-A<lint>`, `-D<lint>`) may appear anywhere,
+    // so may `-g`, which emits debug information in RISC-V assembly,
+    // and `-steps=<n>`, which limits executed statements or instructions of run modes,
+    // `-trace`, which traces `-run-koopa`, and `-stats`, which reports instruction
+    // counts of `-run-riscv`.
+    let mut lints = Lints::default();
+    let mut debug = false;
+    let mut opts = interpreter::Options::default();
+    let args = env::args()
+        .filter(|arg| {
+            if let Some(limit) = arg.strip_prefix("-steps=") {
+                opts.step_limit = Some(limit.parse().expect("Invalid step limit"));
+                return false;
+            }
+            debug |= arg == "-g";
+            opts.trace |= arg == "-trace";
+            opts.stats |= arg == "-stats";
+            !["-g", "-trace", "-stats"].contains(&&arg[..]) && !lints.parse_flag(arg)
+        })
+        .collect::<Vec<_>>();
+    let (mode, inputs, output) = match &args[..] {
+        [_, mode, inputs @ .., o, output] if o == "-o" && !inputs.is_empty() => {
+            (mode, inputs, Some(output))
+        }
+        [_, mode, inputs @ ..] if mode.starts_with("-run") && !inputs.is_empty() => {
+            (mode, inputs, None)
+        }
+        _ => panic!("Incorrect command line arguments"),
     };
 
-    // let mut args = env::args();
-    // let _cmd = args.next().unwrap();
-    // let mode = args.next().unwrap();
-    // let input = args.next().unwrap();
-    // assert_eq!(args.next().unwrap(), "-o");
-    // let output = args.next().unwrap();
-
-    let input = fs::read_to_string(input).unwrap();
-    let mut output = fs::File::create(output).unwrap();
-
-    let res = match &mode[..] {
-        "-koopa" => Program::from_sysy_text(&input).to_koopa_text(),
-        "-riscv" | "-perf" => {
-            let koopa = Program::from_sysy_text(&input).to_koopa_program();
-            backend::riscv_text_from(&koopa)
+    // Fuzzing modes, see `fuzz`.
+    // `-gen <seed> -o <output>` writes a random program,
+    // `-reduce <input> -o <output>` reduces a program on which stages diverge,
+    // `-fuzz <seed> <count> -o <dir>` does both on many random programs.
+    if let Some(output) = output {
+        match (&mode[..], inputs) {
+            ("-gen", [seed]) => {
+                fs::write(output, fuzz::generate(seed.parse().unwrap())).unwrap();
+                return;
+            }
+            ("-reduce", [input]) => {
+                let prog = fs::read_to_string(input).unwrap();
+                let reduced = quietly(|| fuzz::reduce(&prog)).expect("The program doesn't diverge");
+                fs::write(output, reduced).unwrap();
+                return;
+            }
+            ("-fuzz", [seed, cnt]) => {
+                let (seed, cnt): (u64, u64) = (seed.parse().unwrap(), cnt.parse().unwrap());
+                fs::create_dir_all(output).unwrap();
+                let failed = (seed..seed + cnt)
+                    .filter(|&seed| {
+                        let prog = fuzz::generate(seed);
+                        let Some(stage) = quietly(|| fuzz::divergence(&prog)) else {
+                            return false;
+                        };
+                        eprintln!("Seed {seed}: {stage} diverges, reducing");
+                        fs::write(Path::new(output).join(format!("{seed}.sy")), &prog).unwrap();
+                        let reduced = quietly(|| fuzz::reduce(&prog)).unwrap();
+                        eprintln!("Reduced from {} to {} bytes", prog.len(), reduced.len());
+                        let path = Path::new(output).join(format!("{seed}.min.sy"));
+                        fs::write(path, reduced).unwrap();
+                        true
+                    })
+                    .count();
+                eprintln!("{failed} of {cnt} programs diverged");
+                process::exit((failed > 0) as i32);
+            }
+            _ => {}
         }
+    }
+
+    let units = inputs
+        .iter()
+        .map(|path| (fs::read_to_string(path).unwrap(), path.clone()))
+        .collect::<Vec<_>>();
+    let progs = Program::from_sysy_units(&units, &lints);
+
+    let Some(output) = output else {
+        let code = match &mode[..] {
+            "-run-ast" => interpreter::run_ast(&progs, opts, interpreter::Io::std()),
+            "-run-koopa" => interpreter::run_koopa(&progs, opts, interpreter::Io::std()),
+            "-run-riscv" => interpreter::run_riscv(&progs, opts, interpreter::Io::std()),
+            _ => panic!("Unknown mode: {mode}"),
+        };
+        process::exit(code);
+    };
+
+    let ext = match &mode[..] {
+        "-koopa" => "koopa",
+        "-riscv" | "-perf" => "s",
         _ => panic!("Unknown mode: {mode}"),
     };
 
-    output.write_all(res.as_bytes()).unwrap();
+    progs.iter().zip(inputs).for_each(|(prog, input)| {
+        let res = match &mode[..] {
+            "-koopa" => prog.to_koopa_text(),
+            _ => {
+                let (koopa, notes) = prog.to_koopa_program();
+                backend::riscv_text_from(&koopa, &notes, debug)
+            }
+        };
+
+        let output = if inputs.len() == 1 {
+            Path::new(output).to_path_buf()
+        } else {
+            fs::create_dir_all(output).unwrap();
+            let name = Path::new(input).with_extension(ext);
+            Path::new(output).join(name.file_name().unwrap())
+        };
+        let mut output = fs::File::create(output).unwrap();
+        output.write_all(res.as_bytes()).unwrap();
+    });
+}
+
+/// Runs the given closure without printing panics, as `fuzz` catches
+/// those of broken stages and tells about them in its own way.
+fn quietly<T>(f: impl FnOnce() -> T) -> T {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let res = f();
+    panic::set_hook(hook);
+    res
 }
@@ -11,6 +11,7 @@ use crate::frontend::ast;
 use koopa::front::Driver;
 use koopa::ir::{entities, Type};
 use koopa_text_builder::KoopaTextBuilder;
+use std::collections::HashMap;
 
 impl ast::Program {
     /// Converts an AST to Koopa text.
@@ -18,12 +19,141 @@ impl ast::Program {
         KoopaTextBuilder::build(self)
     }
 
-    /// Converts an AST to Koopa in-memory program.
-    pub fn to_koopa_program(&self) -> entities::Program {
+    /// Converts an AST to Koopa in-memory program,
+    /// along with annotations for the backend.
+    pub fn to_koopa_program(&self) -> (entities::Program, Annotations) {
         let text = self.to_koopa_text();
+        let notes = Annotations::from_koopa_text(&text);
         Type::set_ptr_size(4); // Simulating 32-bit machine.
-        Driver::from(text)
+        let mut prog = Driver::from(text)
             .generate_program()
-            .expect("Invalid Koopa text")
+            .expect("Invalid Koopa text");
+        resolve_forward_decls(&mut prog, &notes.forwards);
+        (prog, notes)
     }
 }
+
+/// What the backend needs to know besides a Koopa program.
+/// The Koopa library drops comments, so these are recovered from
+/// comments in Koopa text before parsing.
+#[derive(Default)]
+pub struct Annotations {
+    pub locs: SourceLocs,
+    // Globals placed in read-only data, marked with `// rodata` before them,
+    // and whether they are local to the unit, marked with `// rodata, local`.
+    // Names are without `@`.
+    pub rodata: HashMap<String, bool>,
+    // The helper zeroing arrays, if defined, marked with `// zero`.
+    pub zero_func: Option<String>,
+    // Aliases of functions declared before defined, to the functions,
+    // marked with `// forward @<name>`, see `resolve_forward_decls`.
+    forwards: HashMap<String, String>,
+}
+
+impl Annotations {
+    /// Scans the given Koopa text, built by `KoopaTextBuilder`.
+    /// Markers are comments right before what they mark.
+    fn from_koopa_text(text: &str) -> Self {
+        let mut notes = Self {
+            locs: SourceLocs::from_koopa_text(text),
+            ..Default::default()
+        };
+        let mut marker = None;
+        text.lines().for_each(|line| {
+            if let Some(note) = line.strip_prefix("// ") {
+                marker = Some(note);
+                return;
+            }
+            let Some(note) = marker.take() else {
+                return;
+            };
+            let (_, name) = line.split_once('@').unwrap();
+            let name = name[..name.find([' ', '(']).unwrap()].to_string();
+            if let Some(real) = note.strip_prefix("forward @") {
+                notes.forwards.insert(name, real.to_string());
+            } else if note == "zero" {
+                notes.zero_func = Some(name);
+            } else if let Some(linkage) = note.strip_prefix("rodata") {
+                notes.rodata.insert(name, linkage == ", local");
+            } else {
+                panic!("Unexpected marker");
+            }
+        });
+        notes
+    }
+}
+
+/// Source locations of instructions in a Koopa program.
+/// The Koopa library drops comments, so locations are recovered from
+/// the `// file:line` comments in Koopa text before parsing. For each
+/// function and basic block, the location changes before instructions
+/// at the recorded indices.
+#[derive(Default)]
+pub struct SourceLocs(HashMap<(String, String), Vec<LocMark>>);
+
+/// Index of an instruction in its block, and the file and line it comes from.
+pub type LocMark = (usize, String, u32);
+
+impl SourceLocs {
+    /// Scans the given Koopa text, built by `KoopaTextBuilder`.
+    fn from_koopa_text(text: &str) -> Self {
+        let mut locs = HashMap::<_, Vec<_>>::new();
+        let (mut func, mut block) = (String::new(), String::new());
+        let (mut cur, mut pending) = (None, None);
+        let mut idx = 0;
+        text.lines().map(str::trim).for_each(|line| {
+            if let Some(sig) = line.strip_prefix("fun ") {
+                func = sig[..sig.find('(').unwrap()].to_string();
+                cur = None;
+            } else if let Some(label) = line.strip_suffix(':') {
+                // Blocks may be reordered, so each one starts with a location.
+                block = label.to_string();
+                idx = 0;
+                pending = cur.clone();
+            } else if func.is_empty() {
+                // Globals, see `Annotations`.
+            } else if let Some(loc) = line.strip_prefix("// ") {
+                let (file, line) = loc.rsplit_once(':').unwrap();
+                cur = Some((file.to_string(), line.parse().unwrap()));
+                pending = cur.clone();
+            } else if !line.is_empty() && line != "}" {
+                if let Some((file, line)) = pending.take() {
+                    let key = (func.clone(), block.clone());
+                    locs.entry(key).or_default().push((idx, file, line));
+                }
+                idx += 1;
+            }
+            if line == "}" {
+                func.clear();
+            }
+        });
+        Self(locs)
+    }
+
+    /// Returns the locations in the given basic block of the given function,
+    /// both named with prefixes, like `@main` and `%entry`.
+    pub fn get(&self, func: &str, block: &str) -> &[LocMark] {
+        self.0
+            .get(&(func.to_string(), block.to_string()))
+            .map_or(&[], |locs| &locs[..])
+    }
+}
+
+/// Resolves forward declarations to the real functions.
+/// Koopa requires functions be defined before used, and forbids declaring
+/// a function that is defined later, so prototypes of functions defined
+/// in the same program are emitted as aliases, like `@<name>__fwd`,
+/// and all calls go through them (see `KoopaTextBuilder`).
+/// Here we move the real function into the alias, so calls need no patching.
+/// HACK: Koopa loses `used_by` of replaced values, so we can't replace calls.
+fn resolve_forward_decls(prog: &mut entities::Program, forwards: &HashMap<String, String>) {
+    let funcs = prog
+        .funcs()
+        .iter()
+        .map(|(&func, data)| (data.name()[1..].to_string(), func))
+        .collect::<HashMap<_, _>>();
+    forwards.iter().for_each(|(alias, real)| {
+        let real = prog.remove_func(funcs[real]).unwrap();
+        *prog.func_mut(funcs[alias]) = real;
+    });
+}
@@ -5,7 +5,7 @@ mod token_generator;
 
 use crate::frontend::ast;
 use build_from::BuildFrom;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use token_generator::TokenGenerator;
 
 /// Loop metadata.
@@ -21,28 +21,94 @@ pub struct KoopaTextBuilder {
     text: String,
     loop_meta: VecDeque<LoopMeta>, // Actually a stack.
     token_gen: HashMap<&'static str, TokenGenerator>,
-    arrays: HashMap<String, usize>,
-    pointers: HashMap<String, usize>,
+    strings: Vec<(String, Vec<u8>)>,
+    consts: String, // Definitions of local constant arrays, hoisted to globals.
+    defined: HashSet<String>,
+    declared: HashSet<String>,
+    globals: HashSet<String>, // Tokens of global symbols, see `internal_token`.
+    forwards: HashMap<String, String>, // Functions always called through aliases.
+    zero_func: Option<String>, // Token of `ZERO_FUNC`, once called.
+    exprs: ast::Exprs,
+    line_map: ast::LineMap,
+    last_loc: String, // Last source location emitted in the current function.
 }
 
 impl KoopaTextBuilder {
     const TAB: &str = "    ";
 
+    /// Runtime library functions and their signatures.
+    const LIB_FUNCS: [(&'static str, &'static str); 9] = [
+        ("getint", "(): i32"),
+        ("getch", "(): i32"),
+        ("getarray", "(*i32): i32"),
+        ("putint", "(i32)"),
+        ("putch", "(i32)"),
+        ("putarray", "(i32, *i32)"),
+        ("putf", "(*i32)"), // Only ever called with a format, see `string_token`.
+        ("_sysy_starttime", "(i32)"),
+        ("_sysy_stoptime", "(i32)"),
+    ];
+
+    /// Body of the helper zeroing the given number of words from the given
+    /// pointer, named like `@__zero`, see `internal_token`. This is emitted
+    /// only if used, and the backend replaces its body with a tight loop,
+    /// see `RiscvBuilder::build_zero_func` and `Annotations` for the marker.
+    const ZERO_FUNC: &'static str = "\
+(%ptr: *i32, %len: i32) {
+%entry:
+    %i = alloc i32
+    store %len, %i
+    jump %__zero_cond
+%__zero_cond:
+    %0 = load %i
+    br %0, %__zero_body, %__zero_end
+%__zero_body:
+    %1 = load %i
+    %2 = sub %1, 1
+    store %2, %i
+    %3 = getptr %ptr, %2
+    store 0, %3
+    jump %__zero_cond
+%__zero_end:
+    ret
+}
+
+";
+
     /// Builds Koopa text from the given AST.
     pub fn build(prog: &ast::Program) -> String {
-        let mut builder = Self::new();
+        let mut builder = Self::new(prog.1.clone(), prog.2.clone());
         builder.build_from(prog, false);
         builder.text
     }
 
     /// Creates a new builder.
-    fn new() -> Self {
+    fn new(exprs: ast::Exprs, line_map: ast::LineMap) -> Self {
         Self {
             text: String::new(),
             loop_meta: VecDeque::new(),
             token_gen: HashMap::new(),
-            arrays: HashMap::new(),
-            pointers: HashMap::new(),
+            strings: Vec::new(),
+            consts: String::new(),
+            defined: HashSet::new(),
+            declared: HashSet::new(),
+            globals: HashSet::new(),
+            forwards: HashMap::new(),
+            zero_func: None,
+            exprs,
+            line_map,
+            last_loc: String::new(),
+        }
+    }
+
+    /// Emits the source location of the given offset as a comment,
+    /// unless it is the same as the last one. Instructions that follow
+    /// come from there, see `SourceLocs` for how the backend gets it.
+    fn build_loc(&mut self, offset: usize) {
+        let loc = self.line_map.locate(offset);
+        if loc != self.last_loc {
+            self.text.push_str(&format!("{}// {loc}\n", Self::TAB));
+            self.last_loc = loc;
         }
     }
 
@@ -124,11 +190,47 @@ impl KoopaTextBuilder {
         self.token_gen.get_mut("%ptr_").and_then(f);
     }
 
-    fn is_array(&self, name: &str) -> bool {
-        self.arrays.contains_key(name)
+    /// Returns the token of the global array holding the given string literal.
+    /// Identical literals share the same array.
+    /// String literals are only passed to `putstr`, which libsysy lacks,
+    /// so it is called as `putf` of libsysy, with `%` doubled in the format.
+    fn string_token(&mut self, bytes: &[u8]) -> String {
+        let mut escaped = vec![];
+        bytes.iter().for_each(|&byte| {
+            escaped.push(byte);
+            if byte == b'%' {
+                escaped.push(byte);
+            }
+        });
+        let bytes = escaped;
+        if let Some((token, _)) = self.strings.iter().find(|(_, s)| *s == bytes) {
+            return token.clone();
+        }
+        let token = self.internal_token(&format!("__str{}", self.strings.len()));
+        self.strings.push((token.clone(), bytes));
+        token
+    }
+
+    /// Returns a token for a global symbol of the midend's own, named after
+    /// the given name, with underscores prepended until it collides with no
+    /// other global symbol. Local tokens end with `_N` (or `_N_f`), see the
+    /// semantic analyzer, so names must not end so to keep clear of them.
+    fn internal_token(&mut self, name: &str) -> String {
+        let mut token = format!("@{name}");
+        while !self.globals.insert(token.clone()) {
+            token.insert(1, '_');
+        }
+        token
+    }
+
+    /// Returns whether the given function belongs to the runtime library.
+    fn is_lib_func(name: &str) -> bool {
+        Self::LIB_FUNCS.iter().any(|(func, _)| *func == name)
     }
 
-    fn is_pointer(&self, name: &str) -> bool {
-        self.pointers.contains_key(name)
+    /// Records a declaration of the given global token, and returns whether
+    /// it needs to be emitted, i.e., neither defined nor declared before.
+    fn declare(&mut self, token: &str) -> bool {
+        !self.defined.contains(token) && self.declared.insert(token.to_string())
     }
 }
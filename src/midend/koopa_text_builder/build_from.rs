@@ -3,6 +3,7 @@
 
 use super::KoopaTextBuilder;
 use crate::frontend::ast::*;
+use std::collections::BTreeMap;
 
 // A shortcut. I don't want to type `KoopaTextBuilder::TAB` every time.
 // Besides, `format!` cannot capture such expression.
@@ -32,20 +33,68 @@ pub trait BuildFrom<T> {
 impl BuildFrom<Program> for KoopaTextBuilder {
     fn build_from(&mut self, prog: &Program, _: bool) -> String {
         // Declare library functions.
-        push_text!(
-            self,
-            "\
-            decl @getint(): i32\n\
-            decl @getch(): i32\n\
-            decl @getarray(*i32): i32\n\
-            decl @putint(i32)\n\
-            decl @putch(i32)\n\
-            decl @putarray(i32, *i32)\n\
-            decl @starttime()\n\
-            decl @stoptime()\n\n"
-        );
-
-        prog.0.iter().for_each(|def| { self.build_from(def, false); });
+        KoopaTextBuilder::LIB_FUNCS.iter().for_each(|(name, sig)| {
+            push_text!(self, "decl @{name}{sig}\n");
+        });
+        push_text!(self, "\n");
+
+        // Record symbols defined in this translation unit,
+        // so that their declarations are not emitted,
+        // and all global symbols, to keep clear of them.
+        prog.0.iter().for_each(|unit| match unit {
+            CompUnit::VarDecl(decl) => {
+                decl.var_defs.iter().for_each(|def| {
+                    let (VarDef::Scalar(ident, ..) | VarDef::Array(ident, ..)) = def;
+                    if !decl.is_extern {
+                        self.defined.insert(ident.clone());
+                    }
+                    self.globals.insert(ident.clone());
+                });
+            }
+            CompUnit::FuncDef(FuncDef { ident, .. }) => {
+                self.defined.insert(format!("@{ident}"));
+                self.globals.insert(format!("@{ident}"));
+            }
+            CompUnit::FuncDecl(FuncDecl { ident, .. }) => {
+                self.globals.insert(format!("@{ident}"));
+            }
+            CompUnit::Error(..) => {}
+        });
+
+        // Koopa requires symbols be defined before used.
+        // Global initializers are constants, so it is safe to
+        // place all global variables before functions.
+        let units_begin = self.text.len();
+        let (vars, funcs): (Vec<_>, Vec<_>) = prog.0
+            .iter()
+            .partition(|unit| matches!(unit, CompUnit::VarDecl(..)));
+        vars.into_iter().chain(funcs).for_each(|unit| {
+            self.build_from(unit, false);
+        });
+
+        // String literals are only known after traversal.
+        // Koopa has no `i8`, so characters are packed into `i32`s in
+        // little-endian order, the same bytes as a C string in RISC-V.
+        // See `Annotations` for the marker.
+        let strings = self.strings
+            .iter()
+            .map(|(token, bytes)| {
+                let mut bytes = bytes.clone();
+                bytes.resize(bytes.len() / 4 * 4 + 4, 0); // Null terminated.
+                let words = bytes
+                    .chunks(4)
+                    .map(|word| i32::from_le_bytes(word.try_into().unwrap()).to_string())
+                    .collect::<Vec<_>>();
+                let (len, init) = (words.len(), words.join(", "));
+                format!("// rodata, local\nglobal {token} = alloc [i32, {len}], {{{init}}}\n\n")
+            })
+            .collect::<String>();
+        self.text.insert_str(units_begin, &self.consts);
+        self.text.insert_str(units_begin, &strings);
+        if let Some(token) = &self.zero_func {
+            let func = format!("// zero\nfun {token}{}", KoopaTextBuilder::ZERO_FUNC);
+            self.text.insert_str(units_begin, &func);
+        }
 
         null!()
     }
@@ -61,6 +110,10 @@ impl BuildFrom<CompUnit> for KoopaTextBuilder {
             FuncDef(func_def) => {
                 self.build_from(func_def, false);
             }
+            FuncDecl(func_decl) => {
+                self.build_from(func_decl, false);
+            }
+            Error(..) => panic!("Unexpected arm"),
         }
         null!()
     }
@@ -75,8 +128,25 @@ impl BuildFrom<VarDecl> for KoopaTextBuilder {
         let is_const = decl.is_const;
 
         decl.var_defs.iter().for_each(|def| {
+            if decl.is_extern {
+                // Defined in another translation unit, or later in this one.
+                // Koopa has no extern variables, we use `undef` initializer
+                // to tell the backend not to allocate storage.
+                let (Scalar(ident, ..) | Array(ident, ..)) = def;
+                if !self.declare(ident) {
+                    return;
+                }
+                let ty = match def {
+                    Scalar(..) => self.build_from(&decl.btype, true),
+                    Array(_, sizes, ..) => self.nest_type(&decl.btype, sizes),
+                };
+                push_text!(self, "global {ident} = alloc {ty}, undef\n");
+                push_text!(self, "\n");
+                return;
+            }
+
             match def {
-                Scalar(ident, opt_exp) => {
+                Scalar(ident, opt_exp, _) => {
                     if is_const {
                         return;
                     }
@@ -100,30 +170,42 @@ impl BuildFrom<VarDecl> for KoopaTextBuilder {
                     }
                 }
 
-                Array(ident, sizes, opt_list) => {
-                    self.arrays.insert(ident.to_string(), sizes.len());
-
+                Array(ident, sizes, opt_list, _) => {
                     let ty = self.nest_type(&decl.btype, sizes);
 
                     let sizes = sizes
                         .iter()
-                        .map(|size| size.value() as usize)
+                        .map(|size| self.exprs.value(*size) as usize)
                         .collect::<Vec<_>>();
 
-                    if is_global {
+                    // Constant arrays are never written, so local ones are
+                    // hoisted to globals, which are placed in read-only data.
+                    // Global ones are exported like other globals.
+                    // See `Annotations` for the marker.
+                    if is_global || is_const {
                         let init = if let Some(list) = opt_list {
                             let list = if let InitList::Flat(list) = list {
                                 list
                             } else {
                                 panic!("Unexpected arm");
                             };
-                            self.nest_list(list, &sizes, 0)
+                            let values = self.exprs.run_values(list);
+                            self.nest_list(&values, &sizes, 0)
                         } else {
                             "zeroinit".to_string()
                         };
 
-                        push_text!(self, "global {ident} = alloc {ty}, {init}\n");
-                        push_text!(self, "\n");
+                        let marker = match (is_const, is_global) {
+                            (false, _) => "",
+                            (true, true) => "// rodata\n",
+                            (true, false) => "// rodata, local\n",
+                        };
+                        let text = format!("{marker}global {ident} = alloc {ty}, {init}\n\n");
+                        if is_global {
+                            self.text.push_str(&text);
+                        } else {
+                            self.consts.push_str(&text);
+                        }
                         return;
                     }
 
@@ -158,7 +240,7 @@ impl BuildFrom<BType> for KoopaTextBuilder {
 
 // This part is awful. It shouldn't have become that complex.
 impl KoopaTextBuilder {
-    fn nest_type(&mut self, btype: &BType, sizes: &[Exp]) -> String {
+    fn nest_type(&mut self, btype: &BType, sizes: &[ExprId]) -> String {
         let btype = self.build_from(btype, true);
         if sizes.is_empty() {
             return btype;
@@ -170,22 +252,26 @@ impl KoopaTextBuilder {
             + sizes
                 .iter()
                 .rev()
-                .map(|size| size.value().to_string())
+                .map(|size| self.exprs.value(*size).to_string())
                 .collect::<Vec<_>>()
                 .join("], ")
                 .as_ref()
             + "]"
     }
 
-    fn nest_list(&self, list: &[Exp], sizes: &[usize], begin: usize) -> String {
+    // Sub-arrays without any non-zero value are `zeroinit`.
+    fn nest_list(&self, values: &BTreeMap<usize, i32>, sizes: &[usize], begin: usize) -> String {
         if sizes.is_empty() {
-            return list[begin].value().to_string();
+            return values.get(&begin).copied().unwrap_or(0).to_string();
         }
 
         let size = sizes.iter().skip(1).product::<usize>();
+        if values.range(begin..begin + size * sizes[0]).next().is_none() {
+            return "zeroinit".to_string();
+        }
         "{".to_string()
             + (0..sizes[0])
-                .map(|i| self.nest_list(list, &sizes[1..], begin + i * size))
+                .map(|i| self.nest_list(values, &sizes[1..], begin + i * size))
                 .collect::<Vec<_>>()
                 .join(", ")
                 .as_ref()
@@ -194,34 +280,51 @@ impl KoopaTextBuilder {
 
     // Due to my register allocation policy (use-once),
     // I have to choose an awkward way to initialize arrays.
-    fn init_list(&mut self, list: &[Exp], sizes: &[usize], ident: &str) {
-        //? How to make it lazy? I tried but failed.
-        (0..sizes.len())
-            .fold(vec![vec![]], |tuples, i| {
-                tuples
-                    .into_iter()
-                    .flat_map(|tuple| {
-                        (0..sizes[i]).map(move |j| {
-                            let mut new_tuple = tuple.clone();
-                            new_tuple.push(j);
-                            new_tuple
-                        })
+    // If some elements are omitted, the whole array is zeroed by `ZERO_FUNC`
+    // first, then only the non-zero values are stored.
+    fn init_list(&mut self, runs: &[InitRun], sizes: &[usize], ident: &str) {
+        let len = sizes.iter().product::<usize>();
+        let zeroed = runs.iter().map(|InitRun(_, values)| values.len()).sum::<usize>() < len;
+        if zeroed {
+            let ptr = sizes.iter().fold(ident.to_string(), |arr, _| {
+                let ptr = self.make_ptr();
+                push_text!(self, "{TAB}{ptr} = getelemptr {arr}, 0\n");
+                ptr
+            });
+            if self.zero_func.is_none() {
+                self.zero_func = Some(self.internal_token("__zero"));
+            }
+            let zero = self.zero_func.as_ref().unwrap();
+            push_text!(self, "{TAB}call {zero}({ptr}, {len})\n");
+        }
+
+        runs.iter().for_each(|InitRun(offset, values)| {
+            values.iter().enumerate().for_each(|(i, value)| {
+                if zeroed && self.exprs.info(*value).value == Some(0) {
+                    return;
+                }
+                // e.g. 5 in [2][3] => (1, 2)
+                let mut idx = offset + i;
+                let mut tuple = sizes
+                    .iter()
+                    .rev()
+                    .map(|size| {
+                        let j = idx % size;
+                        idx /= size;
+                        j
                     })
-                    .collect()
-            })
-            .into_iter()
-            .for_each(|tuple| {
+                    .collect::<Vec<_>>();
+                tuple.reverse();
+
                 let ptr = tuple.iter().fold(ident.to_string(), |arr, idx| {
                     let ptr = self.make_ptr();
                     push_text!(self, "{TAB}{ptr} = getelemptr {arr}, {idx}\n");
                     ptr
                 });
-                let idx = tuple.iter().enumerate().fold(0, |acc, (i, idx)| {
-                    acc * sizes[i] + idx
-                });
-                let value = self.build_from(&list[idx], true);
+                let value = self.build_from(value, true);
                 push_text!(self, "{TAB}store {value}, {ptr}\n");
             });
+        });
     }
 }
 
@@ -231,14 +334,16 @@ impl BuildFrom<FuncDef> for KoopaTextBuilder {
     fn build_from(&mut self, func_def: &FuncDef, _: bool) -> String {
         use FuncFParam::*;
         self.reset_local_tokens();
+        self.last_loc.clear();
 
         // Print function signature.
-        let mut ty = self.build_from(&func_def.0, false);
-        if ty != "" {
+        let mut ty = self.build_from(&func_def.btype, false);
+        if !ty.is_empty() {
             ty = ": ".to_string() + &ty;
         }
-        let name = &func_def.1;
-        let params = func_def.2
+        let name = &func_def.ident;
+        self.declared.insert(format!("@{name}"));
+        let params = func_def.params
             .iter()
             .map(|param| self.build_from(param, false))
             .collect::<Vec<_>>()
@@ -248,36 +353,35 @@ impl BuildFrom<FuncDef> for KoopaTextBuilder {
         push_text!(self, "%entry:\n");
 
         // Localize parameters.
-        self.pointers.clear();
-        func_def.2.iter().for_each(|param| {
+        func_def.params.iter().for_each(|param| {
             match param {
-                Scalar(btype, ident) => {
+                Scalar(btype, ident, _) => {
                     let btype = self.build_from(btype, true);
                     push_text!(self, "{TAB}{ident} = alloc {btype}\n");
                     push_text!(self, "{TAB}store {ident}_f, {ident}\n");
                 }
-                Array(btype, ident, sizes) => {
-                    let ty = self.nest_type(&btype, sizes);
+                Array(btype, ident, sizes, _) => {
+                    let ty = self.nest_type(btype, sizes);
                     push_text!(self, "{TAB}{ident} = alloc *{ty}\n");
                     push_text!(self, "{TAB}store {ident}_f, {ident}\n");
-                    self.pointers.insert(ident.to_string(), sizes.len() + 1);
                 }
             }
         });
-        if !func_def.2.is_empty() {
+        if !func_def.params.is_empty() {
             push_text!(self, "\n");
         }
 
         // Build function body.
-        self.build_from(&func_def.3, false);
+        self.build_from(&func_def.block, false);
 
         // HACK:
         // In my implementation, the last return in a function always leaves
         // a dangling label. As I generate Koopa text in one pass, it is hard
         // to address this problem normally. Here is the workaround:
         // just add an extra, unreachable instruction.
-        // Koopa library will emit a warning and ignore unreachable blocks.
-        match func_def.0 {
+        // Koopa library will ignore unreachable blocks, and with `no-front-logger`,
+        // it won't warn about them, as they are none of the user's business.
+        match func_def.btype {
             BType::Int => push_text!(self, "{TAB}ret 114514\n"),
             BType::Void => push_text!(self, "{TAB}ret\n"),
         }
@@ -288,19 +392,54 @@ impl BuildFrom<FuncDef> for KoopaTextBuilder {
     }
 }
 
+impl BuildFrom<FuncDecl> for KoopaTextBuilder {
+    fn build_from(&mut self, func_decl: &FuncDecl, _: bool) -> String {
+        let ident = &func_decl.ident;
+        let mut name = format!("@{ident}");
+        if KoopaTextBuilder::is_lib_func(ident) || !self.declared.insert(name.clone()) {
+            return null!();
+        }
+        // Koopa forbids declaring a function defined later, so we declare
+        // an alias, which is resolved after Koopa text is parsed.
+        // See `Annotations` for the marker.
+        if self.defined.contains(&name) {
+            push_text!(self, "// forward {name}\n");
+            name = self.internal_token(&format!("{ident}__fwd"));
+            self.forwards.insert(ident.clone(), name.clone());
+        }
+
+        let mut ty = self.build_from(&func_decl.btype, false);
+        if !ty.is_empty() {
+            ty = ": ".to_string() + &ty;
+        }
+        let params = func_decl.params
+            .iter()
+            .map(|param| self.param_type(param))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        push_text!(self, "decl {name}({params}){ty}\n");
+        push_text!(self, "\n");
+        null!()
+    }
+}
+
 impl BuildFrom<FuncFParam> for KoopaTextBuilder {
     fn build_from(&mut self, param: &FuncFParam, _: bool) -> String {
         use FuncFParam::*;
 
+        let (Scalar(_, ident, _) | Array(_, ident, ..)) = param;
+        let ty = self.param_type(param);
+        format!("{ident}_f: {ty}")
+    }
+}
+
+impl KoopaTextBuilder {
+    fn param_type(&mut self, param: &FuncFParam) -> String {
+        use FuncFParam::*;
         match param {
-            Scalar(btype, ident) => {
-                let btype = self.build_from(btype, true);
-                format!("{ident}_f: {btype}")
-            }
-            Array(btype, ident, sizes) => {
-                let ty = self.nest_type(btype, sizes);
-                format!("{ident}_f: *{ty}")
-            }
+            Scalar(btype, _, _) => self.build_from(btype, true),
+            Array(btype, _, sizes, _) => format!("*{}", self.nest_type(btype, sizes)),
         }
     }
 }
@@ -318,8 +457,15 @@ impl BuildFrom<BlockItem> for KoopaTextBuilder {
     fn build_from(&mut self, block_item: &BlockItem, _: bool) -> String {
         use BlockItem::*;
         match block_item {
-            Stmt(stmt) => self.build_from(stmt, false),
-            VarDecl(decl) => self.build_from(decl, false),
+            Stmt(stmt, span) => {
+                self.build_loc(span.begin);
+                self.build_from(stmt, false)
+            }
+            VarDecl(decl) => {
+                let (VarDef::Scalar(.., span) | VarDef::Array(.., span)) = &decl.var_defs[0];
+                self.build_loc(span.begin);
+                self.build_from(decl, false)
+            }
         }
     }
 }
@@ -329,35 +475,11 @@ impl BuildFrom<BlockItem> for KoopaTextBuilder {
 impl BuildFrom<Stmt> for KoopaTextBuilder {
     fn build_from(&mut self, stmt: &Stmt, _: bool) -> String {
         use Stmt::*;
-        use LVal::*;
         match stmt {
             Assign(lval, exp) => {
                 let src = self.build_from(exp, true);
-                match lval {
-                    Ident(ident) => {
-                        push_text!(self, "{TAB}store {src}, {ident}\n");
-                    }
-                    // This part is awful.
-                    ArrayElem(ident, indices) => {
-                        let mut arr = ident.clone();
-                        if self.is_pointer(ident) {
-                            arr = self.make_ptr();
-                            push_text!(self, "{TAB}{arr} = load {ident}\n");
-                        }
-                        let ptr = indices.iter().enumerate().fold(arr, |arr, (i, idx)| {
-                            let idx = self.build_from(idx, true);
-                            let ptr = self.make_ptr();
-                            let op = if i == 0 && self.is_pointer(ident) {
-                                "getptr"
-                            } else {
-                                "getelemptr"
-                            };
-                            push_text!(self, "{TAB}{ptr} = {op} {arr}, {idx}\n");
-                            ptr
-                        });
-                        push_text!(self, "{TAB}store {src}, {ptr}\n");
-                    }
-                }
+                let ptr = self.place(*lval);
+                push_text!(self, "{TAB}store {src}, {ptr}\n");
             }
 
             Empty => {}
@@ -453,6 +575,7 @@ impl BuildFrom<Stmt> for KoopaTextBuilder {
                 let label = self.make_koopa();
                 push_text!(self, "{label}:\n");
             }
+            Error => panic!("Unexpected arm"),
         }
         null!()
     }
@@ -460,288 +583,236 @@ impl BuildFrom<Stmt> for KoopaTextBuilder {
 
 // Expression.
 
-impl BuildFrom<Exp> for KoopaTextBuilder {
-    fn build_from(&mut self, exp: &Exp, used: bool) -> String {
-        use Exp::*;
-        match exp {
-            LOrExp(exp) => self.build_from(exp, used),
-            Number(number) => self.build_from(number, used),
-        }
-    }
-}
-
-// This part is the most awful one.
-impl BuildFrom<LVal> for KoopaTextBuilder {
-    fn build_from(&mut self, lval: &LVal, used: bool) -> String {
-        use LVal::*;
-
-        let ident = match lval {
-            Ident(ident) => ident,
-            ArrayElem(ident, _) => ident,
-        };
-
-        let is_array = self.is_array(ident);
-        let is_pointer = self.is_pointer(ident);
-        let size;
-
-        if !is_array && !is_pointer {
-            if !used {
-                return null!();
-            }
-            let dst = self.make_num();
-            push_text!(self, "{TAB}{dst} = load {ident}\n");
-            return dst;
-        }
-
-        let mut arr = ident.clone();
-        if is_pointer {
-            arr = self.make_ptr();
-            push_text!(self, "{TAB}{arr} = load {ident}\n");
-        }
-
-        match lval {
-            Ident(..) => {
-                let dst = self.make_token("%");
-                let op = if is_pointer { "getptr" } else { "getelemptr" };
-                push_text!(self, "{TAB}{dst} = {op} {arr}, 0\n");
-                return dst;
-            }
-            ArrayElem(_, indices) => {
-                arr = indices.iter().enumerate().fold(arr, |arr, (i, index)| {
-                    let idx = self.build_from(index, true);
-                    let ptr = self.make_ptr();
-                    let op = if i == 0 && is_pointer {
-                        "getptr"
-                    } else {
-                        "getelemptr"
-                    };
-                    push_text!(self, "{TAB}{ptr} = {op} {arr}, {idx}\n");
-                    ptr
-                });
-                size = indices.len();
-            }
-        }
-
-        let dst = self.make_token("%");
-        let cond = self.pointers.get(ident) == Some(&size)
-            || self.arrays.get(ident) == Some(&size);
-        if cond {
-            push_text!(self, "{TAB}{dst} = load {arr}\n");
-        } else {
-            push_text!(self, "{TAB}{dst} = getelemptr {arr}, 0\n");
-        }
-
-        dst
-    }
-}
-
-impl BuildFrom<PrimaryExp> for KoopaTextBuilder {
-    fn build_from(&mut self, primary_exp: &PrimaryExp, used: bool) -> String {
-        use PrimaryExp::*;
-        match primary_exp {
-            BracketedExp(bexp) => self.build_from(bexp.as_ref(), used),
-            Number(number) => self.build_from(number, used),
-            LVal(lval) => self.build_from(lval, used),
-        }
-    }
-}
-
-impl BuildFrom<Number> for KoopaTextBuilder {
-    fn build_from(&mut self, number: &Number, _: bool) -> String {
-        number.0.to_string()
-    }
-}
-
-impl BuildFrom<UnaryExp> for KoopaTextBuilder {
-    fn build_from(&mut self, unary_exp: &UnaryExp, used: bool) -> String {
-        use UnaryExp::*;
-        use UnaryOp::*;
-        match unary_exp {
-            Primary(bexp) => self.build_from(bexp.as_ref(), used),
-            FuncCall(ident, exps) => {
-                let args = exps
-                    .iter()
-                    .map(|exp| self.build_from(exp, true))
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                if used {
-                    let dst = self.make_num();
-                    push_text!(self, "{TAB}{dst} = call @{ident}({args})\n");
-                    dst
-                } else {
-                    push_text!(self, "{TAB}call @{ident}({args})\n");
-                    null!()
+impl BuildFrom<ExprId> for KoopaTextBuilder {
+    fn build_from(&mut self, id: &ExprId, used: bool) -> String {
+        let id = *id;
+        match self.exprs[id].clone() {
+            Expr::Binary(BinaryOp::And, lhs, rhs) => self.build_land(lhs, rhs, used),
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => self.build_lor(lhs, rhs, used),
+            Expr::Binary(op, lhs, rhs) => {
+                let src1 = self.build_from(&lhs, used);
+                let src2 = self.build_from(&rhs, used);
+                if !used {
+                    return null!();
                 }
+                let dst = self.make_num();
+                let op = match op {
+                    BinaryOp::Mul => "mul",
+                    BinaryOp::Div => "div",
+                    BinaryOp::Rem => "mod",
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::Lt => "lt",
+                    BinaryOp::Le => "le",
+                    BinaryOp::Gt => "gt",
+                    BinaryOp::Ge => "ge",
+                    BinaryOp::Eq => "eq",
+                    BinaryOp::Ne => "ne",
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                };
+                push_text!(self, "{TAB}{dst} = {op} {src1}, {src2}\n");
+                dst
             }
-            OpUnary(op, bexp) => {
-                let src = self.build_from(bexp.as_ref(), used);
+            Expr::Unary(op, exp) => {
+                let src = self.build_from(&exp, used);
                 if !used {
                     return null!();
                 }
                 let dst;
                 match op {
-                    Plus => dst = src,
-                    Minus => {
+                    UnaryOp::Plus => dst = src,
+                    UnaryOp::Minus => {
                         dst = self.make_num();
                         push_text!(self, "{TAB}{dst} = sub 0, {src}\n");
                     }
-                    Not => {
+                    UnaryOp::Not => {
                         dst = self.make_num();
                         push_text!(self, "{TAB}{dst} = eq 0, {src}\n");
                     }
                 }
                 dst
             }
-        }
-    }
-}
-
-macro_rules! impl_build_from_binary_op {
-    ($self:tt, $T:ty, $arm1:tt, $arm2:tt, $O:ty,
-        op_rule: $($l:tt => $r:tt),*) => {
-        impl BuildFrom<$T> for KoopaTextBuilder {
-            fn build_from(&mut self, exp: &$T, used: bool) -> String {
-                use $T::*;
-                use $O::*;
-                match exp {
-                    $arm1(bexp) => self.build_from(bexp.as_ref(), used),
-                    $arm2(bexps, op, bexp) => {
-                        let src1 = self.build_from(bexps.as_ref(), used);
-                        let src2 = self.build_from(bexp.as_ref(), used);
+            Expr::Call(ident, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.build_from(arg, true))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let ident = if let Some(alias) = self.forwards.get(&ident) {
+                    alias[1..].to_string()
+                } else if ident == "putstr" {
+                    "putf".to_string() // See `string_token`.
+                } else {
+                    ident
+                };
+                if used {
+                    let dst = self.make_num();
+                    push_text!(self, "{TAB}{dst} = call @{ident}({args})\n");
+                    dst
+                } else {
+                    push_text!(self, "{TAB}call @{ident}({args})\n");
+                    null!()
+                }
+            }
+            // Elements of constant arrays at constant indices are immediates.
+            Expr::Index(..) if self.exprs.info(id).value.is_some() => {
+                self.exprs.info(id).value.unwrap().to_string()
+            }
+            // This part is the most awful one.
+            Expr::Index(..) | Expr::Var(..) => {
+                let ptr = self.place(id);
+                match self.exprs.ty(id) {
+                    Type::Int => {
                         if !used {
                             return null!();
                         }
                         let dst = self.make_num();
-                        let op = match op {
-                            $($l => $r,)*
-                        };
-                        push_text!(self, "{TAB}{dst} = {op} {src1}, {src2}\n");
+                        push_text!(self, "{TAB}{dst} = load {ptr}\n");
+                        dst
+                    }
+                    // Arrays decay into pointers to their first elements.
+                    Type::Array(..) => {
+                        let dst = self.make_num();
+                        push_text!(self, "{TAB}{dst} = getelemptr {ptr}, 0\n");
                         dst
                     }
+                    // Only an unindexed pointer, whose value is already loaded.
+                    Type::Pointer(..) => ptr,
+                    Type::Void => panic!("Unexpected type"),
                 }
             }
-        }
-    };
-}
-
-impl_build_from_binary_op!(self, MulExp, Unary, MulOpUnary, MulOp,
-    op_rule: Mul => "mul", Div => "div", Rem => "mod");
-
-impl_build_from_binary_op!(self, AddExp, Mul, AddOpMul, AddOp,
-    op_rule: Add => "add", Sub => "sub");
-
-impl_build_from_binary_op!(self, RelExp, Add, RelOpAdd, RelOp,
-    op_rule: Le => "le", Lt => "lt", Ge => "ge", Gt => "gt");
-
-impl_build_from_binary_op!(self, EqExp, Rel, EqOpRel, EqOp,
-    op_rule: Eq => "eq", Ne => "ne");
-
-impl BuildFrom<LAndExp> for KoopaTextBuilder {
-    fn build_from(&mut self, land_exp: &LAndExp, used: bool) -> String {
-        use LAndExp::*;
-        match land_exp {
-            Eq(bexp) => self.build_from(bexp.as_ref(), used),
-            LAndEq(bexps, bexp) => {
-                let then = self.make_token("%then_");
-                let _ = self.make_token("%else_");
-                let endif = self.make_token("%endif_");
-                if !used {
-                    //     (calc lhs)
-                    //     br lhs then endif
-                    // then:
-                    //     (calc rhs)
-                    //     jump endif
-                    // endif:
-                    let src1 = self.build_from(bexps.as_ref(), true);
-                    push_text!(self, "{TAB}br {src1}, {then}, {endif}\n");
-                    push_text!(self, "{then}:\n");
-                    self.build_from(bexp.as_ref(), false);
-                    push_text!(self, "{TAB}jump {endif}\n");
-                    push_text!(self, "{endif}:\n");
-                    return null!();
-                }
-                //     var = alloc i32
-                //     store 0, var
-                //     (calc lhs)
-                //     br lhs then endif
-                // then:
-                //     (calc rhs)
-                //     tmp = ne 0, rhs
-                //     store tmp, var
-                //     jump endif
-                // endif:
-                //     dst = load var
-                let var = self.make_tmp();
-                push_text!(self, "{TAB}{var} = alloc i32\n");
-                push_text!(self, "{TAB}store 0, {var}\n");
-                let src1 = self.build_from(bexps.as_ref(), true);
-                push_text!(self, "{TAB}br {src1}, {then}, {endif}\n");
-                push_text!(self, "{then}:\n");
-                let src2 = self.build_from(bexp.as_ref(), true);
-                let temp = self.make_num();
-                push_text!(self, "{TAB}{temp} = ne 0, {src2}\n");
-                push_text!(self, "{TAB}store {temp}, {var}\n");
-                push_text!(self, "{TAB}jump {endif}\n");
-                push_text!(self, "{endif}:\n");
-                let dst = self.make_num();
-                push_text!(self, "{TAB}{dst} = load {var}\n");
+            Expr::Lit(Lit::Int(value)) => value.to_string(),
+            Expr::Lit(Lit::Str(bytes)) => {
+                let token = self.string_token(&bytes);
+                let dst = self.make_ptr();
+                push_text!(self, "{TAB}{dst} = getelemptr {token}, 0\n");
                 dst
             }
         }
     }
 }
 
-impl BuildFrom<LOrExp> for KoopaTextBuilder {
-    fn build_from(&mut self, lor_exp: &LOrExp, used: bool) -> String {
-        use LOrExp::*;
-        match lor_exp {
-            LAnd(bexp) => self.build_from(bexp.as_ref(), used),
-            LOrLAnd(bexps, bexp) => {
-                let then = self.make_token("%then_");
-                let _ = self.make_token("%else_");
-                let endif = self.make_token("%endif_");
-                if !used {
-                    //     (calc lhs)
-                    //     br lhs endif then
-                    // then:
-                    //     (calc rhs)
-                    //     jump endif
-                    // endif:
-                    let src1 = self.build_from(bexps.as_ref(), true);
-                    push_text!(self, "{TAB}br {src1}, {endif}, {then}\n");
-                    push_text!(self, "{then}:\n");
-                    self.build_from(bexp.as_ref(), false);
-                    push_text!(self, "{TAB}jump {endif}\n");
-                    push_text!(self, "{endif}:\n");
-                    return null!();
+impl KoopaTextBuilder {
+    /// Builds the address of the given variable or array element.
+    /// For an unindexed pointer, i.e. array parameter, this is the pointer
+    /// itself rather than its address.
+    fn place(&mut self, id: ExprId) -> String {
+        match self.exprs[id].clone() {
+            Expr::Var(ident) => {
+                if !matches!(self.exprs.ty(id), Type::Pointer(..)) {
+                    return ident;
                 }
-                //     var = alloc i32
-                //     store 1, var
-                //     (calc lhs)
-                //     br lhs endif then
-                // then:
-                //     (calc rhs)
-                //     tmp = ne 0, rhs
-                //     store tmp, var
-                //     jump endif
-                // endif:
-                //     dst = load var
-                let var = self.make_tmp();
-                push_text!(self, "{TAB}{var} = alloc i32\n");
-                push_text!(self, "{TAB}store 1, {var}\n");
-                let src1 = self.build_from(bexps.as_ref(), true);
-                push_text!(self, "{TAB}br {src1}, {endif}, {then}\n");
-                push_text!(self, "{then}:\n");
-                let src2 = self.build_from(bexp.as_ref(), true);
-                let temp = self.make_num();
-                push_text!(self, "{TAB}{temp} = ne 0, {src2}\n");
-                push_text!(self, "{TAB}store {temp}, {var}\n");
-                push_text!(self, "{TAB}jump {endif}\n");
-                push_text!(self, "{endif}:\n");
-                let dst = self.make_num();
-                push_text!(self, "{TAB}{dst} = load {var}\n");
-                dst
+                let ptr = self.make_ptr();
+                push_text!(self, "{TAB}{ptr} = load {ident}\n");
+                ptr
+            }
+            Expr::Index(arr, index) => {
+                let arr_ptr = self.place(arr);
+                let idx = self.build_from(&index, true);
+                let ptr = self.make_ptr();
+                let op = if matches!(self.exprs.ty(arr), Type::Pointer(..)) {
+                    "getptr"
+                } else {
+                    "getelemptr"
+                };
+                push_text!(self, "{TAB}{ptr} = {op} {arr_ptr}, {idx}\n");
+                ptr
             }
+            _ => panic!("Unexpected arm"),
+        }
+    }
+
+    fn build_land(&mut self, lhs: ExprId, rhs: ExprId, used: bool) -> String {
+        let then = self.make_token("%then_");
+        let _ = self.make_token("%else_");
+        let endif = self.make_token("%endif_");
+        if !used {
+            //     (calc lhs)
+            //     br lhs then endif
+            // then:
+            //     (calc rhs)
+            //     jump endif
+            // endif:
+            let src1 = self.build_from(&lhs, true);
+            push_text!(self, "{TAB}br {src1}, {then}, {endif}\n");
+            push_text!(self, "{then}:\n");
+            self.build_from(&rhs, false);
+            push_text!(self, "{TAB}jump {endif}\n");
+            push_text!(self, "{endif}:\n");
+            return null!();
         }
+        //     var = alloc i32
+        //     store 0, var
+        //     (calc lhs)
+        //     br lhs then endif
+        // then:
+        //     (calc rhs)
+        //     tmp = ne 0, rhs
+        //     store tmp, var
+        //     jump endif
+        // endif:
+        //     dst = load var
+        let var = self.make_tmp();
+        push_text!(self, "{TAB}{var} = alloc i32\n");
+        push_text!(self, "{TAB}store 0, {var}\n");
+        let src1 = self.build_from(&lhs, true);
+        push_text!(self, "{TAB}br {src1}, {then}, {endif}\n");
+        push_text!(self, "{then}:\n");
+        let src2 = self.build_from(&rhs, true);
+        let temp = self.make_num();
+        push_text!(self, "{TAB}{temp} = ne 0, {src2}\n");
+        push_text!(self, "{TAB}store {temp}, {var}\n");
+        push_text!(self, "{TAB}jump {endif}\n");
+        push_text!(self, "{endif}:\n");
+        let dst = self.make_num();
+        push_text!(self, "{TAB}{dst} = load {var}\n");
+        dst
+    }
+
+    fn build_lor(&mut self, lhs: ExprId, rhs: ExprId, used: bool) -> String {
+        let then = self.make_token("%then_");
+        let _ = self.make_token("%else_");
+        let endif = self.make_token("%endif_");
+        if !used {
+            //     (calc lhs)
+            //     br lhs endif then
+            // then:
+            //     (calc rhs)
+            //     jump endif
+            // endif:
+            let src1 = self.build_from(&lhs, true);
+            push_text!(self, "{TAB}br {src1}, {endif}, {then}\n");
+            push_text!(self, "{then}:\n");
+            self.build_from(&rhs, false);
+            push_text!(self, "{TAB}jump {endif}\n");
+            push_text!(self, "{endif}:\n");
+            return null!();
+        }
+        //     var = alloc i32
+        //     store 1, var
+        //     (calc lhs)
+        //     br lhs endif then
+        // then:
+        //     (calc rhs)
+        //     tmp = ne 0, rhs
+        //     store tmp, var
+        //     jump endif
+        // endif:
+        //     dst = load var
+        let var = self.make_tmp();
+        push_text!(self, "{TAB}{var} = alloc i32\n");
+        push_text!(self, "{TAB}store 1, {var}\n");
+        let src1 = self.build_from(&lhs, true);
+        push_text!(self, "{TAB}br {src1}, {endif}, {then}\n");
+        push_text!(self, "{then}:\n");
+        let src2 = self.build_from(&rhs, true);
+        let temp = self.make_num();
+        push_text!(self, "{TAB}{temp} = ne 0, {src2}\n");
+        push_text!(self, "{TAB}store {temp}, {var}\n");
+        push_text!(self, "{TAB}jump {endif}\n");
+        push_text!(self, "{endif}:\n");
+        let dst = self.make_num();
+        push_text!(self, "{TAB}{dst} = load {var}\n");
+        dst
     }
 }
@@ -0,0 +1,115 @@
+//! Fixture shared by the tests running the compiler binary,
+//! with their inputs written to a temporary directory.
+
+#![allow(dead_code)] // Each test uses only some of the helpers.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What the compiler binary printed, and the code it exited with.
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: i32,
+}
+
+/// Writes the given files to a fresh directory named after the test,
+/// returns their paths.
+pub fn write_files(test: &str, files: &[(&str, &str)]) -> Vec<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("sysy-{}-{test}", env!("CARGO_CRATE_NAME")));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    files
+        .iter()
+        .map(|(name, text)| {
+            fs::write(dir.join(name), text).unwrap();
+            dir.join(name)
+        })
+        .collect()
+}
+
+/// Runs the compiler binary with the given arguments and no input,
+/// returns what it printed, even if it panicked.
+pub fn run_compiler(args: &[&OsStr]) -> Output {
+    let res = Command::new(env!("CARGO_BIN_EXE_sysy-compiler"))
+        .args(args)
+        .env("RUST_BACKTRACE", "0")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    Output {
+        stdout: String::from_utf8(res.stdout).unwrap(),
+        stderr: String::from_utf8(res.stderr).unwrap(),
+        code: res.status.code().unwrap(),
+    }
+}
+
+/// Runs the compiler binary in a run mode, e.g. `-run-ast`, with the given arguments
+/// and input, returns what the program printed, or what the compiler did if it panicked.
+pub fn run(args: &[&OsStr], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sysy-compiler"))
+        .args(args)
+        .env("RUST_BACKTRACE", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let res = child.wait_with_output().unwrap();
+    Output {
+        stdout: String::from_utf8(res.stdout).unwrap(),
+        stderr: String::from_utf8(res.stderr).unwrap(),
+        code: res.status.code().unwrap(),
+    }
+}
+
+/// Runs the compiler binary with the given arguments and no input,
+/// returns what it printed, or the message it panicked with.
+pub fn sysy_compiler(args: &[&OsStr]) -> Result<Output, String> {
+    let res = run_compiler(args);
+    match panic_message(&res.stderr) {
+        Some(msg) => Err(msg),
+        None => Ok(res),
+    }
+}
+
+/// Returns the message the compiler panicked with, if it did, from what it printed.
+/// Panics exit with 101, which is a valid exit code of programs too.
+pub fn panic_message(stderr: &str) -> Option<String> {
+    // The message follows `panicked at`, and precedes the note on backtraces.
+    let mut lines = stderr.lines().skip_while(|line| !line.contains("panicked at"));
+    lines.next()?;
+    let msg = lines.take_while(|line| !line.starts_with("note: "));
+    Some(msg.collect::<Vec<_>>().join("\n"))
+}
+
+/// Compiles the given input in the given mode, `-koopa` or `-riscv`,
+/// returns the output, or the message the compiler panicked with.
+pub fn compile(mode: &str, input: &Path) -> Result<String, String> {
+    let output = input.with_extension(&mode[1..]);
+    sysy_compiler(&[mode.as_ref(), input.as_ref(), "-o".as_ref(), output.as_ref()])?;
+    Ok(fs::read_to_string(output).unwrap())
+}
+
+/// Compiles the given translation units together in the given mode,
+/// returns the output of each, or the message the compiler panicked with.
+pub fn compile_units(mode: &str, inputs: &[PathBuf]) -> Result<Vec<String>, String> {
+    if let [input] = inputs {
+        return Ok(vec![compile(mode, input)?]);
+    }
+    let ext = if mode == "-koopa" { "koopa" } else { "s" };
+    let dir = inputs[0].parent().unwrap().join(&mode[1..]);
+    let mut args = vec![mode.as_ref()];
+    args.extend(inputs.iter().map(|input| input.as_os_str()));
+    args.extend(["-o".as_ref(), dir.as_os_str()]);
+    sysy_compiler(&args)?;
+    let outputs = inputs.iter().map(|input| {
+        let name = input.with_extension(ext);
+        fs::read_to_string(dir.join(name.file_name().unwrap())).unwrap()
+    });
+    Ok(outputs.collect())
+}
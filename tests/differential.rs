@@ -0,0 +1,113 @@
+//! Differential tests, run in-crate through the stages of `fuzz`.
+//! Each `.sy` program in `tests/sysy`, or in the directory given by
+//! `SYSY_TEST_DIR`, runs on its `.in` file, if any, through the AST
+//! interpreter, the Koopa interpreter and the RISC-V simulator in turn.
+//! The first stage whose output diverges from the previous one, or from
+//! the `.out` file for the AST interpreter, is reported.
+//! Outputs follow the official test suite: standard output, then the
+//! exit code on its own line.
+
+mod common;
+
+use common::write_files;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysy_compiler::fuzz::{self, STAGES};
+
+/// Keeps a broken stage from hanging the tests.
+const STEP_LIMIT: u64 = 1_000_000_000;
+
+/// Returns the first line where the given outputs differ, for reporting.
+fn first_diff(expected: &str, actual: &str) -> String {
+    let mut lines = expected.lines().zip(actual.lines()).enumerate();
+    match lines.find(|(_, (lhs, rhs))| lhs != rhs) {
+        Some((i, (lhs, rhs))) => format!("line {}: expected {lhs:?}, got {rhs:?}", i + 1),
+        None => format!(
+            "expected {} lines, got {}",
+            expected.lines().count(),
+            actual.lines().count()
+        ),
+    }
+}
+
+/// Runs the given program through all stages, returns where it diverges.
+fn check(prog: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(prog).unwrap();
+    let input = fs::read_to_string(prog.with_extension("in")).unwrap_or_default();
+    let expected = fs::read_to_string(prog.with_extension("out")).ok();
+    let mut prev: Option<(&str, String)> = None;
+    for (stage, run) in STAGES {
+        let out = fuzz::run(run, &text, &prog.to_string_lossy(), &input, STEP_LIMIT)
+            .map_err(|msg| format!("{stage} failed: {msg}"))?;
+        match &prev {
+            None => {
+                if let Some(expected) = &expected {
+                    if *expected != out {
+                        let diff = first_diff(expected, &out);
+                        return Err(format!("{stage} diverges from .out, {diff}"));
+                    }
+                }
+            }
+            Some((prev_stage, prev_out)) => {
+                if *prev_out != out {
+                    let diff = first_diff(prev_out, &out);
+                    return Err(format!("{stage} diverges from {prev_stage}, {diff}"));
+                }
+            }
+        }
+        prev = Some((stage, out));
+    }
+    Ok(())
+}
+
+#[test]
+fn differential() {
+    let dir = match env::var("SYSY_TEST_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sysy"),
+    };
+    let mut progs = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sy"))
+        .collect::<Vec<_>>();
+    progs.sort();
+    assert!(!progs.is_empty(), "No .sy programs in {}", dir.display());
+
+    let failures = progs
+        .iter()
+        .filter_map(|prog| {
+            let name = prog.file_name().unwrap().to_string_lossy();
+            check(prog).err().map(|err| format!("{name}: {err}"))
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        failures.is_empty(),
+        "{} of {} programs failed:\n{}",
+        failures.len(),
+        progs.len(),
+        failures.join("\n")
+    );
+}
+
+/// Random programs from the generator of `-gen`, see `fuzz`.
+/// `SYSY_FUZZ_SEEDS` gives the number of programs, 10 by default.
+#[test]
+fn random_programs() {
+    let cnt = env::var("SYSY_FUZZ_SEEDS").map_or(10, |cnt| cnt.parse().unwrap());
+    let progs = (0..cnt).map(|seed| (format!("{seed}.sy"), fuzz::generate(seed)));
+    let progs = progs.collect::<Vec<_>>();
+    let files = progs.iter().map(|(name, prog)| (&name[..], &prog[..])).collect::<Vec<_>>();
+    let failures = write_files("random", &files)
+        .iter()
+        .zip(0..)
+        .filter_map(|(prog, seed)| check(prog).err().map(|err| format!("seed {seed}: {err}")))
+        .collect::<Vec<_>>();
+    assert!(
+        failures.is_empty(),
+        "{} of {cnt} programs failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
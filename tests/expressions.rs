@@ -0,0 +1,100 @@
+//! Tests of expressions, from parsing to Koopa, run on the compiler binary.
+
+mod common;
+
+use common::{compile, sysy_compiler, write_files};
+
+#[test]
+fn precedence_and_associativity() {
+    let prog = "int main() {
+  int x = 2;
+  return 10 - x - 3 * 4 / (5 + x) % 3 < -+!x == 1;
+}
+";
+    let input = &write_files("precedence", &[("x.c", prog)])[0];
+    let koopa = compile("-koopa", input).unwrap();
+    let expected = "    %0 = load @x_0
+    %1 = sub 10, %0
+    %2 = mul 3, 4
+    %3 = load @x_0
+    %4 = add 5, %3
+    %5 = div %2, %4
+    %6 = mod %5, 3
+    %7 = sub %1, %6
+    %8 = load @x_0
+    %9 = eq 0, %8
+    %10 = sub 0, %9
+    %11 = lt %7, %10
+    %12 = eq %11, 1
+    ret %12
+";
+    assert!(koopa.contains(expected), "{koopa}");
+}
+
+#[test]
+fn const_eval_wraps() {
+    // Like the generated code, and short circuits never evaluate `1 / 0`.
+    let prog = "const int a = 2147483647 + 1;
+const int b = a - 1;
+const int c = 65536 * 65536 + 0 && 1 / 0;
+const int d = 1 || 1 % 0;
+int arr[a - b + 2147483647 * 0 + 2];
+int main() {
+  return a + b * 2 + c + d;
+}
+";
+    let input = &write_files("const-wraps", &[("x.c", prog)])[0];
+    let koopa = compile("-koopa", input).unwrap();
+    assert!(koopa.contains("global @arr = alloc [i32, 3], zeroinit\n"));
+    assert!(koopa.contains("%0 = mul 2147483647, 2\n    %1 = add -2147483648, %0\n"));
+    assert!(koopa.contains("%2 = add %1, 0\n    %3 = add %2, 1\n"));
+}
+
+#[test]
+fn const_eval_errors() {
+    let cases = [
+        (
+            "const int a = 1;\nconst int b = a / (a - 1);\n",
+            "x.c:2: division by zero in constant expression",
+        ),
+        (
+            "int main() {\n  const int m = -2147483647 - 1;\n  const int x = m / -1;\n}\n",
+            "x.c:3: integer overflow in division in constant expression",
+        ),
+        ("int a[1 % 0];\n", "x.c:1: division by zero in constant expression"),
+    ];
+    cases.iter().enumerate().for_each(|(i, (prog, msg))| {
+        let input = &write_files(&format!("const-error-{i}"), &[("x.c", prog)])[0];
+        let err = compile("-koopa", input).unwrap_err();
+        assert!(err.ends_with(msg), "{err}");
+    });
+}
+
+#[test]
+fn integer_literals() {
+    // Literals are 32-bit patterns, so `0xFFFFFFFF` is `-1`.
+    let prog = "int main() {
+  int a = -2147483648;
+  int b = 0xFFFFFFFF;
+  int c = 0x100000001;
+  int d = 037777777777;
+  return a + b + c + d + 4294967295;
+}
+";
+    let input = &write_files("literals", &[("x.c", prog)])[0];
+    let output = input.with_extension("koopa");
+    let args = ["-koopa".as_ref(), input.as_os_str(), "-o".as_ref(), output.as_os_str()];
+    let res = sysy_compiler(&args).unwrap();
+    let warning = format!(
+        "{}:4: warning: integer literal 0x100000001 is too large, truncated to 1",
+        input.display()
+    );
+    assert!(res.stderr.contains(&warning), "{}", res.stderr);
+    assert_eq!(res.stderr.matches("warning").count(), 1);
+
+    let koopa = std::fs::read_to_string(output).unwrap();
+    assert!(koopa.contains("%0 = sub 0, -2147483648\n"));
+    ["store -1, @b_0", "store 1, @c_0", "store -1, @d_0", "add %7, -1"].iter().for_each(|inst| {
+        assert!(koopa.contains(inst), "{koopa}");
+    });
+}
@@ -0,0 +1,107 @@
+//! Tests of array initializers, run on the compiler binary.
+
+mod common;
+
+use common::{compile, write_files};
+
+/// Returns the body of the given function in the given Koopa text.
+fn func_body<'a>(koopa: &'a str, name: &str) -> &'a str {
+    let begin = koopa.find(&format!("fun @{name}(")).unwrap();
+    let end = begin + koopa[begin..].find("\n}\n").unwrap();
+    &koopa[begin..end]
+}
+
+#[test]
+fn sparse_initializers() {
+    let prog = "int g[1000][1000] = {1};
+int h[3][2] = {{}, {1, 2}};
+int main() {
+  int a[1000][1000] = {{1}, 2, 3};
+  return a[1][1] + g[0][0] + h[1][1];
+}
+";
+    let input = &write_files("sparse", &[("x.c", prog)])[0];
+
+    // Rows of zeros are left to `zeroinit`.
+    let koopa = compile("-koopa", input).unwrap();
+    assert!(koopa.len() < 20000);
+    let g = koopa.lines().find(|line| line.starts_with("global @g")).unwrap();
+    assert!(g.starts_with("global @g = alloc [[i32, 1000], 1000], {{1, 0, 0, "));
+    assert_eq!(g.matches("zeroinit").count(), 999);
+    assert!(koopa.contains("global @h = alloc [[i32, 2], 3], {zeroinit, {1, 2}, zeroinit}\n"));
+
+    // Only the given elements of local arrays are stored one by one.
+    let main = func_body(&koopa, "main");
+    assert_eq!(main.matches("store 0, ").count(), 0);
+    ["store 1, ", "store 2, ", "store 3, "].iter().for_each(|store| {
+        assert_eq!(main.matches(store).count(), 1, "{main}");
+    });
+
+    // And so are they in data.
+    let riscv = compile("-riscv", input).unwrap();
+    assert!(riscv.contains("g:\n    .word 1\n    .zero 3999996\n"));
+    assert!(riscv.contains("h:\n    .zero 8\n    .word 1\n    .word 2\n    .zero 8\n"));
+}
+
+#[test]
+fn zeroing_helper() {
+    // Named clear of the user function `__zero`.
+    let prog = "int __zero(int x) {
+  return x + 1;
+}
+int main() {
+  int a[100][100] = {{1}, 2};
+  int b[2] = {3, 4};
+  return a[1][0] + b[1] + __zero(1);
+}
+";
+    let input = &write_files("zeroing", &[("x.c", prog)])[0];
+
+    // Only partially initialized arrays are zeroed first.
+    let koopa = compile("-koopa", input).unwrap();
+    assert!(koopa.contains("// zero\nfun @___zero(%ptr: *i32, %len: i32) {\n"));
+    let main = func_body(&koopa, "main");
+    assert_eq!(main.matches("call @___zero(").count(), 1);
+    assert!(main.contains(", 10000)\n"));
+    assert!(main.contains("call @__zero(1)\n"));
+
+    // The helper becomes a local loop.
+    let riscv = compile("-riscv", input).unwrap();
+    assert!(riscv.contains("___zero:\n"));
+    assert!(!riscv.contains(".globl ___zero\n"));
+    assert!(riscv.contains("bnez t0, .L___zero_body\n"));
+    assert!(riscv.contains("call ___zero\n"));
+    assert!(riscv.contains(".globl __zero\n__zero:\n"));
+    assert!(riscv.lines().count() < 200);
+}
+
+#[test]
+fn const_arrays() {
+    let prog = "const int tab[3] = {7, 8, 9};
+int main() {
+  const int a[2][2] = {{1}, {0, 4}};
+  int i = 1;
+  return tab[1] + a[1][1] + a[i][0] + tab[i];
+}
+";
+    let input = &write_files("const-arrays", &[("x.c", prog)])[0];
+
+    // Local ones are hoisted, and known elements are immediates.
+    let koopa = compile("-koopa", input).unwrap();
+    assert!(koopa.contains("// rodata\nglobal @tab = alloc [i32, 3], {7, 8, 9}\n"));
+    assert!(koopa.contains("// rodata, local\nglobal @a_0 = alloc [[i32, 2], 2], {{1, 0}, {0, 4}}\n"));
+    let main = func_body(&koopa, "main");
+    assert!(!main.contains("alloc [[i32, 2], 2]"));
+    assert!(main.contains("%0 = add 8, 4\n"));
+    assert!(main.contains("getelemptr @a_0, %1\n"));
+    assert!(main.contains("getelemptr @tab, %4\n"));
+
+    // Only global ones are exported from read-only data.
+    let riscv = compile("-riscv", input).unwrap();
+    let rodata = riscv.find(".section .rodata\n").unwrap();
+    let text = riscv.find(".text\n").unwrap();
+    let a = riscv.find("\na_0:\n    .word 1\n    .zero 8\n    .word 4\n").unwrap();
+    let tab = riscv.find("    .globl tab\ntab:\n    .word 7\n").unwrap();
+    assert!(rodata < a && a < text && rodata < tab && tab < text);
+    assert!(!riscv.contains(".globl a_0\n"));
+}
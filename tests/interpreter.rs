@@ -0,0 +1,114 @@
+//! Tests of the interpreters, run on the compiler binary.
+
+mod common;
+
+use common::{run, write_files};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn runtime_library() {
+    let prog = "int a[4];
+int main() {
+  int n = getint(), c = getch();
+  int k = getarray(a);
+  putint(n);
+  putch(c);
+  putarray(k, a);
+  putstr(\"done\\n\");
+  putf(\"%d%% %c\\n\", n, 65);
+  starttime();
+  stoptime();
+  int x = 2147483647;
+  return x + 2;
+}
+";
+    let input = &write_files("runtime", &[("x.c", prog)])[0];
+    ["-run-ast", "-run-koopa", "-run-riscv"].iter().for_each(|mode| {
+        let res = run(&[mode.as_ref(), input.as_os_str()], "42x3 7 8 9");
+        assert_eq!(res.stdout, "42x3: 7 8 9\ndone\n42% A\n", "{mode}");
+        // Arithmetic wraps, and the exit code is the low byte of what `main` returns.
+        assert_eq!(res.code, 1, "{mode}");
+    });
+}
+
+#[test]
+fn stdin_left_open() {
+    // Input is read only as far as asked, like a terminal nobody types in after `5`.
+    let prog = "int main() {\n  return getint() + 1;\n}\n";
+    let input = &write_files("stdin-open", &[("x.c", prog)])[0];
+    ["-run-ast", "-run-koopa", "-run-riscv"].iter().for_each(|mode| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_sysy-compiler"))
+            .args([mode.as_ref(), input.as_os_str()])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(b"5\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let status = loop {
+            if let Some(status) = child.try_wait().unwrap() {
+                break status;
+            }
+            if Instant::now() > deadline {
+                child.kill().unwrap();
+                panic!("{mode} waits for the end of stdin");
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        drop(stdin);
+        assert_eq!(status.code(), Some(6), "{mode}");
+    });
+}
+
+#[test]
+fn runtime_errors() {
+    let cases = [
+        ("int main() {\n  int i = 0;\n  return 1 / i;\n}\n", "Division by zero"),
+        ("int main() {\n  int a[2];\n  return a[-100000];\n}\n", "Out-of-bounds load"),
+    ];
+    cases.iter().enumerate().for_each(|(i, (prog, msg))| {
+        let input = &write_files(&format!("runtime-error-{i}"), &[("x.c", prog)])[0];
+        let res = run(&["-run-ast".as_ref(), input.as_os_str()], "");
+        assert_ne!(res.code, 0);
+        assert!(res.stderr.contains(msg), "{}", res.stderr);
+    });
+}
+
+#[test]
+fn koopa_steps_and_trace() {
+    let prog = "int main() {\n  int i = 0;\n  while (1)\n    i = i + 1;\n  return 0;\n}\n";
+    let input = &write_files("koopa-steps", &[("x.c", prog)])[0];
+    let res = run(&["-run-koopa".as_ref(), input.as_os_str(), "-steps=100".as_ref()], "");
+    assert!(res.stderr.contains("Step limit of 100 exceeded"), "{}", res.stderr);
+    // The AST interpreter counts statements instead.
+    let res = run(&["-run-ast".as_ref(), input.as_os_str(), "-steps=100".as_ref()], "");
+    assert!(res.stderr.contains("Step limit of 100 exceeded"), "{}", res.stderr);
+
+    let prog = "int main() {\n  int x = 3;\n  return x;\n}\n";
+    let input = &write_files("koopa-trace", &[("x.c", prog)])[0];
+    let res = run(&["-run-koopa".as_ref(), input.as_os_str(), "-trace".as_ref()], "");
+    assert_eq!(res.code, 3);
+    assert!(res.stderr.contains("[2] @main %entry: store 3, @x_0("), "{}", res.stderr);
+    assert!(res.stderr.contains("[4] @main %entry: ret 3\n"), "{}", res.stderr);
+}
+
+#[test]
+fn riscv_instruction_counts() {
+    let prog = "int main() {\n  int i = 0;\n  while (i < 10)\n    i = i + 1;\n  return i;\n}\n";
+    let input = &write_files("riscv-counts", &[("x.c", prog)])[0];
+    let res = run(&["-run-riscv".as_ref(), input.as_os_str()], "");
+    assert_eq!(res.code, 10);
+    assert!(!res.stderr.contains("instructions executed"), "{}", res.stderr);
+
+    // Counts are only reported when asked for.
+    let res = run(&["-run-riscv".as_ref(), input.as_os_str(), "-stats".as_ref()], "");
+    assert_eq!(res.code, 10);
+    assert!(res.stderr.contains(" instructions executed\n"), "{}", res.stderr);
+    let slt = res.stderr.lines().find(|line| line.trim_start().starts_with("slt "));
+    assert_eq!(slt.map(str::trim), Some("slt 11"), "{}", res.stderr);
+}
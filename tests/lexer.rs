@@ -0,0 +1,124 @@
+//! Tests of lexical diagnostics: lexical errors are located,
+//! reported along with parse errors, and do not stop parsing.
+
+use sysy_compiler::frontend::Program;
+
+/// Returns the errors in the given program, without the path.
+fn errors(prog: &str) -> Vec<String> {
+    let (_, _, errors) = Program::parse_sysy_text_recovering(prog, "l.sy");
+    errors.iter().map(|err| err.strip_prefix("l.sy:").unwrap().to_string()).collect()
+}
+
+#[test]
+fn numeric_literals() {
+    let prog = "int main() {
+  return 0x1F + 017 + 09 + 0x + 12ab;
+}
+";
+    assert_eq!(
+        errors(prog),
+        vec![
+            "2: parse error: invalid digit `9` in octal literal `09`",
+            "2: parse error: hexadecimal literal `0x` has no digits",
+            "2: parse error: invalid digit `a` in decimal literal `12ab`",
+        ]
+    );
+}
+
+#[test]
+fn invalid_characters() {
+    let prog = "int main() {
+  int a = 1 @ 2;
+  if (a & 1 | 2) return 1;
+  putch('\\q');
+  putstr(\"ok\\n\");
+  return 0;
+}
+";
+    assert_eq!(
+        errors(prog),
+        vec![
+            "2: parse error: unexpected character '@'",
+            "2: parse error: unexpected `2`, expected one of `!=`, `%`, `&&`, `*`, `+`, `,`, \
+             `-`, `/`, `;`, `<`, `<=`, `==`, `>`, `>=`, `||`",
+            "3: parse error: unexpected `&`, did you mean `&&`?",
+            "3: parse error: unexpected `|`, did you mean `||`?",
+            "4: parse error: unknown escape sequence \\q",
+        ]
+    );
+}
+
+#[test]
+fn unterminated_literals() {
+    let prog = "int main() {
+  putstr(\"abc);
+  return 'a;
+}
+";
+    assert_eq!(
+        errors(prog),
+        vec![
+            "2: parse error: unterminated string literal",
+            "3: parse error: unexpected `return`, expected one of `)`, `,`",
+            "3: parse error: unterminated character literal",
+        ]
+    );
+}
+
+#[test]
+fn comments() {
+    // Comments end at the first `*/`, `/*` in them has no effect but a warning.
+    let prog = "int main() {
+  /* a /* b */
+  return 1 */**/ 2;
+}
+";
+    assert_eq!(errors(prog), Vec::<String>::new());
+    let (_, warnings, _) = Program::parse_sysy_text_recovering(prog, "l.sy");
+    let warnings = warnings.iter().map(|w| (w.lint.name(), w.offset, &w.msg[..]));
+    let warnings = warnings.collect::<Vec<_>>();
+    assert_eq!(warnings, vec![("nested-comment", 20, "`/*` within block comment")]);
+
+    let prog = "int main() {
+  /* a /* b */ c */
+  return 0;
+}
+";
+    assert_eq!(
+        errors(prog),
+        vec![
+            "2: parse error: `*/` outside of a comment, block comments do not nest",
+            "3: parse error: unexpected `return`, expected one of `!=`, `%`, `&&`, `(`, `*`, `+`, \
+             `-`, `/`, `;`, `<`, `<=`, `=`, `==`, `>`, `>=`, `[`, `||`",
+        ]
+    );
+
+    // The rest of the program is in an unterminated comment.
+    let prog = "int main() {
+  return 0;
+}
+/* int f() {
+  return 1;
+}
+";
+    assert_eq!(errors(prog), vec!["4: parse error: unterminated block comment"]);
+}
+
+#[test]
+fn directives() {
+    // `#` only begins a directive at the start of a line, whitespace aside.
+    let prog = "int main() {
+  #ifdef N
+  #endif
+  return 1 # 2;
+}
+";
+    assert_eq!(
+        errors(prog),
+        vec![
+            "4: parse error: unexpected character '#'",
+            "4: parse error: unexpected `2`, expected one of `!=`, `%`, `&&`, `*`, `+`, `-`, \
+             `/`, `;`, `<`, `<=`, `==`, `>`, `>=`, `||`",
+        ]
+    );
+}
@@ -0,0 +1,210 @@
+//! Tests of linking translation units, run on the compiler binary,
+//! with units written to a temporary directory.
+
+mod common;
+
+use common::{compile_units, run, write_files};
+use std::path::PathBuf;
+
+/// Checks that the given units give the given output and exit code in all run modes.
+fn check(units: &[PathBuf], expected: (&str, i32)) {
+    ["-run-ast", "-run-koopa", "-run-riscv"].iter().for_each(|mode| {
+        let mut args = vec![mode.as_ref()];
+        args.extend(units.iter().map(|unit| unit.as_os_str()));
+        let res = run(&args, "");
+        assert_eq!((res.stdout.as_str(), res.code), expected, "{mode}: {}", res.stderr);
+    });
+}
+
+#[test]
+fn extern_variables() {
+    let x1 = "extern int n;\nint main() {\n  return n;\n}\n";
+    let x2 = "int n = 3;\n";
+    let units = write_files("extern-vars", &[("x1.c", x1), ("x2.c", x2)]);
+
+    let koopa = compile_units("-koopa", &units).unwrap();
+    assert!(koopa[0].contains("global @n = alloc i32, undef\n"));
+    assert!(koopa[1].contains("global @n = alloc i32, 3\n"));
+
+    // Storage is only allocated where the variable is defined.
+    let riscv = compile_units("-riscv", &units).unwrap();
+    assert!(!riscv[0].contains("\nn:"));
+    assert!(riscv[0].contains("la t1, n\n"));
+    assert!(riscv[1].contains(".globl n\nn:\n    .word 3\n"));
+}
+
+#[test]
+fn conflicting_globals() {
+    let x1 = "extern int tab[4];\nint main() {\n  return tab[0];\n}\n";
+    let x2 = "int tab[3] = {7, 8, 9};\n";
+    let units = write_files("conflicting-types", &[("x1.c", x1), ("x2.c", x2)]);
+    let err = compile_units("-koopa", &units).unwrap_err();
+    let msg = format!(
+        "{}: conflicting types for `tab`: `int[3]` here, `int[4]` in {}",
+        units[1].display(),
+        units[0].display()
+    );
+    assert_eq!(err, msg);
+
+    let x1 = "int main() {\n  return 0;\n}\n";
+    let x2 = "int main() {\n  return 1;\n}\n";
+    let units = write_files("multiple-defs", &[("x1.c", x1), ("x2.c", x2)]);
+    let err = compile_units("-koopa", &units).unwrap_err();
+    let msg = format!(
+        "{}: multiple definitions of `main`, previous one in {}",
+        units[1].display(),
+        units[0].display()
+    );
+    assert_eq!(err, msg);
+}
+
+#[test]
+fn reserved_names() {
+    // Globals keep their names, so locals and string literals keep clear of them.
+    let x1 = "int x_0;
+int __str0;
+int main() {
+  int x = 1;
+  putstr(\"hi\");
+  return x + x_0 + __str0;
+}
+";
+    let units = write_files("reserved-names", &[("x1.c", x1)]);
+    let koopa = &compile_units("-koopa", &units).unwrap()[0];
+    assert!(koopa.contains("global @x_0 = alloc i32, zeroinit\n"));
+    assert!(koopa.contains("@x_1 = alloc i32\n"));
+    assert!(koopa.contains("global @__str0 = alloc i32, zeroinit\n"));
+    assert!(koopa.contains("global @___str0 = alloc [i32, 1], {26984}\n"));
+    assert!(koopa.contains("call @putf(%ptr_0)\n"));
+}
+
+#[test]
+fn function_prototypes() {
+    let x1 = "int f(int x);\nint main() {\n  return f(1);\n}\n";
+    let x2 = "int f(int x) {\n  return x + 1;\n}\n";
+    let units = write_files("prototypes", &[("x1.c", x1), ("x2.c", x2)]);
+    let koopa = compile_units("-koopa", &units).unwrap();
+    assert!(koopa[0].contains("decl @f(i32): i32\n"));
+    assert!(koopa[0].contains("call @f(1)\n"));
+    assert!(!koopa[1].contains("decl @f("));
+
+    let x2 = "void f(int a[]) {}\n";
+    let units = write_files("conflicting-prototype", &[("x1.c", x1), ("x2.c", x2)]);
+    let err = compile_units("-koopa", &units).unwrap_err();
+    let msg = format!(
+        "{}: conflicting types for `f`: `void(int[])` here, `int(int)` in {}",
+        units[1].display(),
+        units[0].display()
+    );
+    assert_eq!(err, msg);
+
+    let x1 = "int f(int x);\nvoid f(int a[]) {}\n";
+    let units = write_files("conflicting-function", &[("x1.c", x1)]);
+    let err = compile_units("-koopa", &units).unwrap_err();
+    assert_eq!(err, format!("{}:2: Conflicting types for function f", units[0].display()));
+
+    let x1 = "int main() {\n  return g();\n}\n";
+    let units = write_files("implicit-declaration", &[("x1.c", x1)]);
+    let err = compile_units("-koopa", &units).unwrap_err();
+    assert_eq!(err, format!("{}:2: Implicit declaration of function g", units[0].display()));
+}
+
+#[test]
+fn forward_declarations() {
+    // Calls before the definition go through a marked alias,
+    // named clear of the user global `f__fwd`.
+    let x1 = "int f();
+int f__fwd;
+int g() {
+  return f() + f__fwd;
+}
+int f() {
+  return 7;
+}
+";
+    let units = write_files("forward-decls", &[("x1.c", x1)]);
+    let koopa = &compile_units("-koopa", &units).unwrap()[0];
+    assert!(koopa.contains("// forward @f\ndecl @_f__fwd(): i32\n"));
+    assert!(koopa.contains("call @_f__fwd()\n"));
+    assert!(koopa.contains("load @f__fwd\n"));
+
+    let riscv = &compile_units("-riscv", &units).unwrap()[0];
+    assert!(riscv.contains("call f\n"));
+    assert!(!riscv.contains("_f__fwd"));
+}
+
+#[test]
+fn extern_const_arrays() {
+    // Constant arrays link like other arrays, but local ones never clash.
+    let x1 = "extern int tab[3];
+int f();
+int main() {
+  const int a[2] = {1, 2};
+  return tab[a[1]] + f();
+}
+";
+    let x2 = "const int tab[3] = {7, 8, 9};
+int f() {
+  const int a[2] = {3, 4};
+  int i = 1;
+  return a[i];
+}
+";
+    let units = write_files("extern-const", &[("x1.c", x1), ("x2.c", x2)]);
+    let riscv = compile_units("-riscv", &units).unwrap();
+    assert!(riscv[0].contains("\na_0:\n"));
+    assert!(!riscv[0].contains(".globl a_0\n"));
+    assert!(!riscv[0].contains("\ntab:\n"));
+    assert!(riscv[1].contains("\na_0:\n"));
+    assert!(!riscv[1].contains(".globl a_0\n"));
+    let rodata = riscv[1].find(".section .rodata\n").unwrap();
+    assert!(riscv[1].find("    .globl tab\ntab:\n").unwrap() > rodata);
+
+    let x1 = "extern int tab[4];\nint main() {\n  return tab[0];\n}\n";
+    let units = write_files("conflicting-const", &[("x1.c", x1), ("x2.c", x2)]);
+    let err = compile_units("-koopa", &units).unwrap_err();
+    let msg = format!(
+        "{}: conflicting types for `tab`: `int[3]` here, `int[4]` in {}",
+        units[1].display(),
+        units[0].display()
+    );
+    assert_eq!(err, msg);
+}
+
+#[test]
+fn extern_const_array() {
+    let x1 = "extern int tab[3];
+int main() {
+  int i = 0, s = 0;
+  while (i < 3) {
+    s = s * 10 + tab[i];
+    i = i + 1;
+  }
+  return s % 256;
+}
+";
+    let x2 = "const int tab[3] = {7, 8, 9};\n";
+    let units = write_files("run-extern-const", &[("x1.c", x1), ("x2.c", x2)]);
+    check(&units, ("", 789 % 256));
+}
+
+#[test]
+fn local_read_only_data() {
+    // Local constant arrays and strings are named alike in both units.
+    let x1 = "int f();
+int main() {
+  const int a[2] = {1, 2};
+  putstr(\"x1\\n\");
+  return a[f()] * 10 + f();
+}
+";
+    let x2 = "int f() {
+  const int a[2] = {3, 4};
+  int i = 1;
+  putstr(\"x2\\n\");
+  return a[i] - 3;
+}
+";
+    let units = write_files("local-rodata", &[("x1.c", x1), ("x2.c", x2)]);
+    check(&units, ("x1\nx2\nx2\n", 21));
+}
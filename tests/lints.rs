@@ -0,0 +1,197 @@
+//! Tests of lints, run on the compiler binary.
+//! Each test compiles a small program and checks the reported warnings.
+
+mod common;
+
+use common::{run_compiler, write_files};
+use std::ffi::OsStr;
+
+/// Compiles the given program to Koopa IR with the given extra flags,
+/// returns whether it succeeded, along with the standard error.
+fn compile(name: &str, prog: &str, flags: &[&str]) -> (bool, String) {
+    let input = &write_files(name, &[("x.c", prog)])[0];
+    let output = input.with_extension("koopa");
+    let mut args = vec!["-koopa".as_ref(), input.as_os_str(), "-o".as_ref(), output.as_os_str()];
+    args.extend(flags.iter().map(OsStr::new));
+    let res = run_compiler(&args);
+    (res.code == 0, res.stderr)
+}
+
+/// Returns the warnings of the given lint, as `line: message`.
+fn warnings(stderr: &str, lint: &str) -> Vec<String> {
+    let suffix = format!(" [-W{lint}]");
+    stderr
+        .lines()
+        .filter_map(|line| line.strip_suffix(&suffix))
+        .map(|line| {
+            let (loc, msg) = line.split_once(": warning: ").unwrap();
+            format!("{}: {msg}", loc.rsplit(':').next().unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn unused_variable() {
+    let prog = r#"
+int g;
+int main() {
+  int a;
+  int b = 1;
+  int c[2];
+  const int d = 2;
+  int e;
+  e = 3;
+  return b + d;
+}
+"#;
+    let (ok, stderr) = compile("unused_variable", prog, &[]);
+    assert!(ok);
+    assert_eq!(
+        warnings(&stderr, "unused-variable"),
+        ["4: unused variable a", "6: unused variable c", "8: unused variable e"]
+    );
+}
+
+#[test]
+fn uninitialized() {
+    let prog = r#"
+int main() {
+  int a;
+  int b;
+  int c;
+  int d;
+  int e;
+  int f;
+  if (getint()) a = 1;
+  if (getint()) b = 1; else b = 2;
+  while (getint()) { c = 1; }
+  d = 1;
+  while (1) { e = 1; break; }
+  while (1) { if (getint()) break; f = 1; if (getint()) continue; break; }
+  return a + b + c + d + e + f;
+}
+"#;
+    let (ok, stderr) = compile("uninitialized", prog, &[]);
+    assert!(ok);
+    assert_eq!(
+        warnings(&stderr, "uninitialized"),
+        [
+            "15: variable a may be used uninitialized",
+            "15: variable c may be used uninitialized",
+            "15: variable f may be used uninitialized",
+        ]
+    );
+}
+
+#[test]
+fn shadow_global() {
+    let prog = r#"
+int g;
+int h[2];
+int f() { return 0; }
+int main() {
+  int g = 1;
+  { int h = 2; g = g + h; }
+  int f = 3;
+  return g + f;
+}
+"#;
+    let (ok, stderr) = compile("shadow_global", prog, &[]);
+    assert!(ok);
+    assert_eq!(
+        warnings(&stderr, "shadow-global"),
+        [
+            "6: declaration of g shadows a global variable",
+            "7: declaration of h shadows a global variable",
+        ]
+    );
+}
+
+#[test]
+fn unreachable_code() {
+    let prog = r#"
+int main() {
+  int a = getint();
+  while (a) {
+    break;
+    a = a - 1;
+  }
+  if (a) return 1; else return 2;
+  ;
+  putint(a);
+  return 0;
+}
+int f() {
+  while (1) { putint(1); }
+  return 0;
+}
+"#;
+    let (ok, stderr) = compile("unreachable_code", prog, &[]);
+    assert!(ok);
+    assert_eq!(
+        warnings(&stderr, "unreachable-code"),
+        ["6: unreachable code", "10: unreachable code", "15: unreachable code"]
+    );
+}
+
+#[test]
+fn constant_condition() {
+    let prog = r#"
+const int N = 4;
+int main() {
+  int a = getint();
+  if (N > 2) a = 1;
+  while (1) { if (a) break; }
+  while (N - 4) { a = 2; }
+  if (a > 2 && 0) a = 3;
+  return a;
+}
+"#;
+    let (ok, stderr) = compile("constant_condition", prog, &[]);
+    assert!(ok);
+    assert_eq!(
+        warnings(&stderr, "constant-condition"),
+        ["5: condition is always true", "7: condition is always false"]
+    );
+}
+
+#[test]
+fn overflowing_literals() {
+    let prog = r#"
+int main() {
+  int a = -2147483648;
+  int b = 0xFFFFFFFF;
+  int c = 4294967297;
+  return a + b + c;
+}
+"#;
+    let (ok, stderr) = compile("overflowing_literals", prog, &[]);
+    assert!(ok);
+    assert_eq!(
+        warnings(&stderr, "overflowing-literals"),
+        ["5: integer literal 4294967297 is too large, truncated to 1"]
+    );
+}
+
+#[test]
+fn levels() {
+    let prog = r#"
+int main() {
+  int a;
+  int b;
+  return 0;
+}
+"#;
+    let (ok, stderr) = compile("allow", prog, &["-Aunused-variable"]);
+    assert!(ok);
+    assert!(!stderr.contains("unused variable"));
+
+    let (ok, stderr) = compile("deny", prog, &["-Dunused-variable"]);
+    assert!(!ok);
+    assert!(stderr.contains("3: error: unused variable a [-Dunused-variable]"));
+
+    // Later flags override earlier ones.
+    let (ok, stderr) = compile("deny_all", prog, &["-Dall", "-Wunused-variable"]);
+    assert!(ok);
+    assert_eq!(warnings(&stderr, "unused-variable").len(), 2);
+}
@@ -0,0 +1,54 @@
+//! Tests of source locations in the generated code, run on the compiler binary.
+
+mod common;
+
+use common::{compile, sysy_compiler, write_files};
+
+#[test]
+fn source_locations() {
+    let prog = "int f(int x) {
+  return x + 1;
+}
+int main() {
+  int a = 2;
+  if (a)
+    a = f(a);
+  return a;
+}
+";
+    let input = &write_files("locations", &[("x.c", prog)])[0];
+    let path = input.display();
+
+    // Statements start with the line they come from.
+    let koopa = compile("-koopa", input).unwrap();
+    let ret = format!("    // {path}:2\n    %0 = load @x_0\n    %1 = add %0, 1\n    ret %1\n");
+    assert!(koopa.contains(&ret), "{koopa}");
+    let decl = format!("%entry:\n    // {path}:5\n    @a_0 = alloc i32\n");
+    assert!(koopa.contains(&decl), "{koopa}");
+    assert!(koopa.contains(&format!("%endif_0:\n    // {path}:8\n")), "{koopa}");
+
+    // And so do the instructions they are lowered to, also at the start of blocks.
+    let riscv = compile("-riscv", input).unwrap();
+    assert!(riscv.contains(&format!("    sw a0, 0(sp)\n    # {path}:2\n    lw t1, 0(sp)\n")));
+    assert!(riscv.contains(&format!("then_0:\n    # {path}:6\n")), "{riscv}");
+    assert!(riscv.contains(&format!("endif_0:\n    # {path}:8\n")), "{riscv}");
+}
+
+#[test]
+fn debug_information() {
+    let prog = "int main() {\n  int a = 1;\n  return a;\n}\n";
+    let input = &write_files("debug-info", &[("x.c", prog)])[0];
+    let output = input.with_extension("s");
+    let args = ["-riscv".as_ref(), "-g".as_ref(), input.as_os_str()];
+    sysy_compiler(&[&args[..], &["-o".as_ref(), output.as_os_str()]].concat()).unwrap();
+
+    // Locations go to the line table, and functions get types, sizes and frames.
+    let riscv = std::fs::read_to_string(output).unwrap();
+    let file = format!("    .file 1 \"{}\"\n    .loc 1 2 0\n", input.display());
+    assert!(riscv.contains(&file), "{riscv}");
+    assert!(riscv.contains("    .loc 1 3 0\n"));
+    assert!(!riscv.contains("    # "));
+    assert!(riscv.contains("    .type main, @function\nmain:\n    .cfi_startproc\n"));
+    assert!(riscv.contains("    .cfi_def_cfa_offset 16\n"));
+    assert!(riscv.contains("    .cfi_endproc\n    .size main, .-main\n"));
+}
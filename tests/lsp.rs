@@ -0,0 +1,265 @@
+//! Tests of the language server, driving the `sysy-lsp` binary over stdio.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use sysy_compiler::lsp::Json;
+
+const URI: &str = "file:///tmp/sysy-lsp/main.sy";
+
+const PROG: &str = "int x = 1;
+int f(int x) {
+  return x + 1;
+}
+const int N = 4;
+int main() {
+  int y = x;
+  {
+    int x = 2;
+    y = y + x;
+  }
+  return f(y) + N * 3;
+}
+";
+
+/// A session with a running server.
+struct Session {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    id: usize,
+    version: usize, // Of the document, 0 if not opened yet.
+}
+
+impl Session {
+    /// Starts a server and initializes it.
+    fn new() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_sysy-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        let mut session = Self {
+            child,
+            input,
+            output,
+            id: 0,
+            version: 0,
+        };
+        let result = session.request("initialize", "{}");
+        let capabilities = result.get("capabilities");
+        assert_eq!(capabilities.get("hoverProvider"), &Json::Bool(true));
+        assert_eq!(capabilities.get("positionEncoding").as_str(), Some("utf-8"));
+        session.notify("initialized", "{}");
+        session
+    }
+
+    fn send(&mut self, msg: &str) {
+        write!(self.input, "Content-Length: {}\r\n\r\n{msg}", msg.len()).unwrap();
+        self.input.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Json {
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            self.output.read_line(&mut line).unwrap();
+            match line.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => len = value.parse().unwrap(),
+                _ => break,
+            }
+        }
+        let mut body = vec![0; len];
+        self.output.read_exact(&mut body).unwrap();
+        Json::parse(std::str::from_utf8(&body).unwrap()).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: &str) {
+        self.send(&format!(r#"{{"jsonrpc":"2.0","method":"{method}","params":{params}}}"#));
+    }
+
+    /// Sends a request and returns the result of its reply.
+    fn request(&mut self, method: &str, params: &str) -> Json {
+        self.id += 1;
+        let id = self.id;
+        self.send(&format!(
+            r#"{{"jsonrpc":"2.0","id":{id},"method":"{method}","params":{params}}}"#
+        ));
+        let reply = self.receive();
+        assert_eq!(reply.get("id").as_usize(), Some(id));
+        reply.get("result").clone()
+    }
+
+    /// Opens or changes the document, returns the diagnostics published,
+    /// as `(line, severity, message)` in order of lines.
+    fn check(&mut self, text: &str) -> Vec<(usize, usize, String)> {
+        let text = Json::from(text);
+        self.version += 1;
+        let version = self.version;
+        if version == 1 {
+            let doc = format!(r#"{{"uri":"{URI}","languageId":"sysy","version":1,"text":{text}}}"#);
+            self.notify("textDocument/didOpen", &format!(r#"{{"textDocument":{doc}}}"#));
+        } else {
+            let doc = format!(r#"{{"uri":"{URI}","version":{version}}}"#);
+            let params = format!(r#"{{"textDocument":{doc},"contentChanges":[{{"text":{text}}}]}}"#);
+            self.notify("textDocument/didChange", &params);
+        }
+        let msg = self.receive();
+        assert_eq!(msg.get("method").as_str(), Some("textDocument/publishDiagnostics"));
+        let Json::Array(diagnostics) = msg.get("params").get("diagnostics") else {
+            panic!("Expected diagnostics");
+        };
+        let mut diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let line = diagnostic.get("range").get("start").get("line").as_usize().unwrap();
+                let severity = diagnostic.get("severity").as_usize().unwrap();
+                (line, severity, diagnostic.get("message").as_str().unwrap().to_string())
+            })
+            .collect::<Vec<_>>();
+        diagnostics.sort();
+        diagnostics
+    }
+
+    /// Sends a request at the given position of the document.
+    fn request_at(&mut self, method: &str, line: usize, character: usize) -> Json {
+        let pos = format!(r#"{{"line":{line},"character":{character}}}"#);
+        let context = r#"{"includeDeclaration":true}"#;
+        let params =
+            format!(r#"{{"textDocument":{{"uri":"{URI}"}},"position":{pos},"context":{context}}}"#);
+        self.request(method, &params)
+    }
+
+    /// Shuts the server down and exits it, returns the exit code.
+    fn exit(mut self, shutdown: bool) -> i32 {
+        if shutdown {
+            assert_eq!(self.request("shutdown", "null"), Json::Null);
+        }
+        self.notify("exit", "null");
+        self.child.wait().unwrap().code().unwrap()
+    }
+}
+
+/// Returns the start of the given range as `(line, character)`.
+fn start(range: &Json) -> (usize, usize) {
+    let start = range.get("start");
+    (start.get("line").as_usize().unwrap(), start.get("character").as_usize().unwrap())
+}
+
+fn hover(session: &mut Session, line: usize, character: usize) -> String {
+    let result = session.request_at("textDocument/hover", line, character);
+    result.get("contents").get("value").as_str().unwrap().to_string()
+}
+
+#[test]
+fn diagnostics() {
+    let mut session = Session::new();
+    let shadow = (8, 2, "declaration of x shadows a global variable".to_string());
+    assert_eq!(session.check(PROG), vec![shadow.clone()]);
+
+    let prog = PROG.replace("int y = x;", "int y = x; int z;");
+    let diagnostics = session.check(&prog);
+    assert_eq!(diagnostics, vec![(6, 2, "unused variable z".to_string()), shadow.clone()]);
+
+    let prog = PROG.replace("y = y + x;", "N = y;");
+    let diagnostics = session.check(&prog);
+    assert_eq!(diagnostics, vec![(9, 1, "Assignment of read-only variable N".to_string())]);
+
+    let prog = PROG.replace("return x + 1;", "return g(x);");
+    let diagnostics = session.check(&prog);
+    assert_eq!(diagnostics, vec![(2, 1, "Implicit declaration of function g".to_string())]);
+
+    let prog = PROG.replace("y = y + x;", "y = y + ;");
+    let diagnostics = session.check(&prog);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].0, 9);
+    assert!(diagnostics[0].2.starts_with("parse error: "));
+
+    // All syntax errors are reported, and the partial AST is still navigable.
+    let prog = prog.replace("return x + 1;", "return x +;");
+    let diagnostics = session.check(&prog);
+    assert_eq!(diagnostics.iter().map(|d| d.0).collect::<Vec<_>>(), vec![2, 9]);
+    assert_eq!(hover(&mut session, 11, 16), "```sysy\nconst int N = 4\n```");
+
+    assert_eq!(session.check(PROG), vec![shadow]);
+    assert_eq!(session.exit(true), 0);
+}
+
+#[test]
+fn navigation() {
+    let mut session = Session::new();
+    session.check(PROG);
+
+    // Symbols show their declarations, other expressions their types and values.
+    assert_eq!(hover(&mut session, 11, 16), "```sysy\nconst int N = 4\n```");
+    assert_eq!(hover(&mut session, 11, 9), "```sysy\nint f(int)\n```");
+    assert_eq!(hover(&mut session, 11, 18), "```sysy\nint\n```\nValue: `12`");
+
+    // Definitions follow scopes.
+    let definition = |session: &mut Session, line, character| {
+        start(session.request_at("textDocument/definition", line, character).get("range"))
+    };
+    assert_eq!(definition(&mut session, 2, 9), (1, 10));
+    assert_eq!(definition(&mut session, 6, 10), (0, 4));
+    assert_eq!(definition(&mut session, 9, 12), (8, 8));
+    assert_eq!(definition(&mut session, 11, 9), (1, 4));
+    assert_eq!(session.request_at("textDocument/definition", 11, 4), Json::Null);
+
+    let Json::Array(refs) = session.request_at("textDocument/references", 0, 4) else {
+        panic!("Expected references");
+    };
+    let refs = refs.iter().map(|loc| start(loc.get("range"))).collect::<Vec<_>>();
+    assert_eq!(refs, vec![(0, 4), (6, 10)]);
+    let Json::Array(refs) = session.request_at("textDocument/references", 9, 4) else {
+        panic!("Expected references");
+    };
+    let refs = refs.iter().map(|loc| start(loc.get("range"))).collect::<Vec<_>>();
+    assert_eq!(refs, vec![(6, 6), (9, 4), (9, 8), (11, 11)]);
+
+    let params = format!(r#"{{"textDocument":{{"uri":"{URI}"}}}}"#);
+    let Json::Array(symbols) = session.request("textDocument/documentSymbol", &params) else {
+        panic!("Expected symbols");
+    };
+    let symbols = symbols
+        .iter()
+        .map(|symbol| {
+            let name = symbol.get("name").as_str().unwrap();
+            let detail = symbol.get("detail").as_str().unwrap();
+            (name, detail, start(symbol.get("selectionRange")))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        symbols,
+        vec![
+            ("x", "int x", (0, 4)),
+            ("f", "int f(int)", (1, 4)),
+            ("N", "const int N = 4", (4, 10)),
+            ("main", "int main()", (5, 4)),
+        ]
+    );
+    assert_eq!(session.exit(true), 0);
+}
+
+#[test]
+fn malformed_messages() {
+    let mut session = Session::new();
+    // Without a length, the body runs into the header of the next message.
+    let body = r#"{"jsonrpc":"2.0","id":100,"method":"shutdown"}"#;
+    write!(session.input, "Content-Type: application/json\r\n\r\n{body}").unwrap();
+    let msg = session.receive();
+    assert_eq!(msg.get("method").as_str(), Some("window/logMessage"));
+    let log = msg.get("params").get("message").as_str();
+    assert_eq!(log, Some("Skipped message: Missing Content-Length"));
+
+    // The server goes on, and was not shut down by the skipped message.
+    let shadow = (8, 2, "declaration of x shadows a global variable".to_string());
+    assert_eq!(session.check(PROG), vec![shadow]);
+    assert_eq!(session.exit(false), 1);
+}
+
+#[test]
+fn exit_without_shutdown() {
+    let session = Session::new();
+    assert_eq!(session.exit(false), 1);
+}
@@ -0,0 +1,135 @@
+//! Tests of the preprocessor, run on the compiler binary,
+//! checking the Koopa text of programs using macros.
+
+mod common;
+
+use common::{compile, write_files};
+use std::path::Path;
+
+/// Compiles the given file to Koopa text, which must succeed.
+fn koopa(input: &Path) -> String {
+    compile("-koopa", input).unwrap_or_else(|err| panic!("{err}"))
+}
+
+#[test]
+fn define_with_comments() {
+    let main = "#define N 10 // size
+#define M /* rows */ 2 /* a comment
+   going on */
+#define S \"a // b\" // Not a comment in quotes.
+int a[N][M];
+int main() {
+  putstr(S);
+  return N * M;
+}
+";
+    let files = write_files("define", &[("main.sy", main)]);
+    let koopa = koopa(&files[0]);
+    assert!(koopa.contains(" = alloc [[i32, 2], 10], zeroinit\n"), "{koopa}");
+    assert!(koopa.contains(" = alloc [i32, 2], "), "{koopa}"); // 6 bytes of `S`.
+    assert!(koopa.contains(" = mul 10, 2\n"), "{koopa}");
+}
+
+#[test]
+fn include() {
+    let header = "#ifndef LIB_H
+#define LIB_H
+const int K = 3;
+int triple(int x) { return x * K; }
+#endif
+";
+    let main = "#include \"lib.h\"
+#include \"lib.h\" // Guarded, so included once.
+#include <stdio.h>
+#include \"sylib.h\"
+int main() {
+  return triple(K) + __LINE__;
+}
+";
+    let files = write_files("include", &[("lib.h", header), ("main.sy", main)]);
+    let koopa = koopa(&files[1]);
+    assert_eq!(koopa.matches("fun @triple(").count(), 1, "{koopa}");
+    assert!(koopa.contains(" = call @triple(3)\n"), "{koopa}");
+    assert!(koopa.contains(", 6\n"), "{koopa}");
+
+    // Errors are located in the included file.
+    let header = "int f() { return 1 }\n";
+    let files = write_files("include_error", &[("lib.h", header), ("main.sy", main)]);
+    let err = compile("-koopa", &files[1]).unwrap_err();
+    assert!(err.starts_with(&format!("{}:1: parse error: ", files[0].display())), "{err}");
+
+    let files = write_files("include_recursive", &[("main.sy", "#include \"main.sy\"\n")]);
+    let err = compile("-koopa", &files[0]).unwrap_err();
+    assert!(err.contains("main.sy: recursive #include"), "{err}");
+}
+
+#[test]
+fn conditionals() {
+    let main = "#define A
+#ifdef A
+#ifndef B
+const int x = 1;
+#else
+const int x = 2;
+#endif
+#else
+#ifdef A
+const int x = 3;
+#endif
+const int x = 4;
+#endif
+#undef A
+#ifdef A
+const int y = 1;
+#else
+const int y = 2;
+#endif
+/* #ifdef in a comment is no directive
+#ifdef A
+*/
+int main() {
+  return x * 10 + y;
+}
+";
+    let files = write_files("conditionals", &[("main.sy", main)]);
+    let koopa = koopa(&files[0]);
+    assert!(koopa.contains(" = mul 1, 10\n"), "{koopa}");
+    assert!(koopa.contains(", 2\n"), "{koopa}");
+
+    let files = write_files("unterminated", &[("main.sy", "#ifdef A\nint main() {}\n")]);
+    let err = compile("-koopa", &files[0]).unwrap_err();
+    assert!(err.contains("main.sy: unterminated #ifdef"), "{err}");
+    let files = write_files("stray_endif", &[("main.sy", "int x;\n#endif\n")]);
+    let err = compile("-koopa", &files[0]).unwrap_err();
+    assert!(err.contains("main.sy:2: #endif without #ifdef"), "{err}");
+}
+
+#[test]
+fn timer_functions() {
+    let main = "#define START starttime()
+#define STOP stoptime
+int main() {
+  starttime();
+  int starttime = 1; // Only calls are rewritten.
+  stoptime ( );
+  START;
+  STOP();
+  return starttime;
+}
+";
+    let files = write_files("timer", &[("main.sy", main)]);
+    let koopa = koopa(&files[0]);
+    let calls = koopa
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("call "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        [
+            "@_sysy_starttime(4)",
+            "@_sysy_stoptime(6)",
+            "@_sysy_starttime(7)",
+            "@_sysy_stoptime(8)",
+        ]
+    );
+}
@@ -0,0 +1,82 @@
+//! Tests of error recovery in the parser: all syntax errors are reported
+//! in one run, and the partial AST is still analyzed.
+
+use std::panic;
+use sysy_compiler::frontend::ast::{BlockItem, CompUnit, Stmt};
+use sysy_compiler::frontend::Program;
+
+const PROG: &str = "int g = ;
+int main() {
+  int a = 1; a = a +;
+  a = 2;
+  if (a > ) return 1;
+  return a;
+}
+int h( { }
+int k() { return 3; }
+";
+
+/// Returns the lines of the given errors, which must all be parse errors.
+fn lines(errors: &[String]) -> Vec<usize> {
+    errors
+        .iter()
+        .map(|err| {
+            let (line, msg) = err.strip_prefix("r.sy:").unwrap().split_once(": ").unwrap();
+            assert!(msg.starts_with("parse error: "), "{err}");
+            line.parse().unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn multiple_errors() {
+    let (prog, _, errors) = Program::parse_sysy_text_recovering(PROG, "r.sy");
+    assert_eq!(lines(&errors), vec![1, 3, 5, 8]);
+
+    // Skipped code becomes error nodes, the rest is kept.
+    let prog = prog.unwrap();
+    let units = &prog.0;
+    assert!(matches!(units[0], CompUnit::Error(..)));
+    let CompUnit::FuncDef(main) = &units[1] else {
+        panic!("Expected main");
+    };
+    let stmts = main.block.0.iter().map(|item| match item {
+        BlockItem::Stmt(stmt, _) => matches!(stmt, Stmt::Error),
+        BlockItem::VarDecl(..) => false,
+    });
+    assert_eq!(stmts.collect::<Vec<_>>(), vec![false, true, false, true, false]);
+    assert!(matches!(&units[units.len() - 1], CompUnit::FuncDef(k) if k.ident == "k"));
+
+    // Semantic analysis runs on the partial AST.
+    let (_, _, index) = prog.analyze_sem_with_index();
+    assert!(index.symbols.contains_key("k"));
+}
+
+#[test]
+fn semantic_errors_in_partial_ast() {
+    let prog = PROG.replace("return a;", "return b;");
+    let (prog, _, errors) = Program::parse_sysy_text_recovering(&prog, "r.sy");
+    assert_eq!(errors.len(), 4);
+    let result = panic::catch_unwind(|| prog.unwrap().analyze_sem_with_index());
+    let payload = result.err().unwrap();
+    let msg = payload.downcast_ref::<String>().unwrap();
+    assert_eq!(msg, "r.sy:6: Use of undeclared identifier b");
+}
+
+#[test]
+fn compiler_reports_all_errors() {
+    let dir = std::env::temp_dir().join("sysy-recovery");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("r.sy");
+    std::fs::write(&path, PROG).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_sysy-compiler"))
+        .arg("-koopa")
+        .arg(&path)
+        .arg("-o")
+        .arg(dir.join("r.koopa"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("parse error: ").count(), 4);
+}
@@ -0,0 +1,95 @@
+//! Tests of character and string literals, run on the compiler binary.
+//! Strings are checked in Koopa text and in the sections of RISC-V assembly.
+
+mod common;
+
+use common::{compile, write_files};
+
+/// Returns the initializer of a string in Koopa text, i.e., its bytes,
+/// null terminated and packed into little-endian words.
+fn packed(text: &[u8]) -> String {
+    let mut bytes = text.to_vec();
+    bytes.resize(text.len() / 4 * 4 + 4, 0);
+    let words = bytes
+        .chunks(4)
+        .map(|word| i32::from_le_bytes(word.try_into().unwrap()).to_string())
+        .collect::<Vec<_>>();
+    format!("{{{}}}", words.join(", "))
+}
+
+#[test]
+fn char_literals() {
+    let prog = "const int c = 'A' + '\\n' + '\\x41' + '\\101' + '\\'';
+int main() {
+  return c;
+}
+";
+    let files = write_files("chars", &[("main.sy", prog)]);
+    let koopa = compile("-koopa", &files[0]).unwrap();
+    assert!(koopa.contains("ret 244\n"), "{koopa}");
+}
+
+#[test]
+fn putstr_and_putf() {
+    let prog = "int main() {
+  putstr(\"50% off\\n\");
+  putf(\"%d%% of %c\\n\", 100, 'a');
+  putstr(\"50% off\\n\");
+  return 0;
+}
+";
+    let files = write_files("putf", &[("main.sy", prog)]);
+    let koopa = compile("-koopa", &files[0]).unwrap();
+
+    // Formats are split at compile time, and strings are printed by `putf`
+    // of libsysy with a format only, so `%` is doubled.
+    let calls = koopa
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("call "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        [
+            "@putf(%ptr_0)",
+            "@putint(100)",
+            "@putf(%ptr_1)",
+            "@putch(97)",
+            "@putf(%ptr_2)",
+            "@putf(%ptr_3)",
+        ]
+    );
+    assert!(koopa.contains("%ptr_3 = getelemptr @__str0, 0\n"), "{koopa}");
+
+    // Identical strings share one array, marked read-only.
+    [(0, &b"50%% off\n"[..]), (1, b"%% of "), (2, b"\n")].iter().for_each(|(id, text)| {
+        let (len, init) = (text.len() / 4 + 1, packed(text));
+        let def = format!("// rodata, local\nglobal @__str{id} = alloc [i32, {len}], {init}\n");
+        assert!(koopa.contains(&def), "{koopa}");
+    });
+    assert!(!koopa.contains("@__str3"), "{koopa}");
+}
+
+#[test]
+fn read_only_data() {
+    let prog = "int g[2] = {1, 2};
+int main() {
+  putstr(\"abcd\");
+  return g[1];
+}
+";
+    let files = write_files("rodata", &[("main.sy", prog)]);
+    let riscv = compile("-riscv", &files[0]).unwrap();
+
+    // Strings go to read-only data, after other data and before code,
+    // and are not exported.
+    let data = riscv.find(".data\n").unwrap();
+    let rodata = riscv.find(".section .rodata\n").unwrap();
+    let text = riscv.find(".text\n").unwrap();
+    let global = riscv.find(".word 2\n").unwrap();
+    assert!(data < global && global < rodata, "{riscv}");
+    let string = format!("__str0:\n    .word {}\n    .zero 4\n", u32::from_le_bytes(*b"abcd"));
+    let pos = riscv.find(&string).unwrap_or_else(|| panic!("{riscv}"));
+    assert!(rodata < pos && pos < text);
+    assert!(!riscv.contains(".globl __str0"), "{riscv}");
+    assert!(riscv.contains("la t0, __str0\n"), "{riscv}");
+}
@@ -0,0 +1,109 @@
+//! Tests of the formatter, on the `sysyfmt` binary and the library.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use sysy_compiler::frontend::{self, Program};
+
+const UNFORMATTED: &str = "// Header.
+#include \"sylib.h\"
+#define N 10
+int g[N]; // Trailing on a global.
+const int c = 2;
+
+
+/* Block comment
+   on many lines. */
+int f(int a /* moved */, int b[]) {
+  // Leading in body.
+  int x = a+b[0]*c;   // Trailing.
+
+  if (x>3) { x = x-1; /* in then */ } else x=0;
+  {
+    // Only a comment.
+  }
+  return x; /* a */ /* b */
+  // Before closing brace.
+} // After function.
+int main(){putint(f(1,g));return 0;}
+// End of file.
+";
+
+const FORMATTED: &str = "// Header.
+#include \"sylib.h\"
+#define N 10
+int g[N]; // Trailing on a global.
+const int c = 2;
+
+/* Block comment
+   on many lines. */
+/* moved */
+int f(int a, int b[]) {
+  // Leading in body.
+  int x = a + b[0] * c; // Trailing.
+
+  if (x > 3) {
+    x = x - 1; /* in then */
+  } else
+    x = 0;
+  {
+    // Only a comment.
+  }
+  return x; /* a */ /* b */
+  // Before closing brace.
+} // After function.
+
+int main() {
+  putint(f(1, g));
+  return 0;
+}
+// End of file.
+";
+
+/// Runs `sysyfmt` with the given arguments on the given standard input,
+/// returns its exit code and standard output.
+fn sysyfmt(args: &[&str], input: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sysyfmt"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let res = child.wait_with_output().unwrap();
+    (res.status.code().unwrap(), String::from_utf8(res.stdout).unwrap())
+}
+
+#[test]
+fn comments_and_indentation() {
+    assert_eq!(sysyfmt(&["--indent=2"], UNFORMATTED), (0, FORMATTED.to_string()));
+}
+
+#[test]
+fn check_mode() {
+    assert_eq!(sysyfmt(&["--check", "--indent=2"], FORMATTED), (0, String::new()));
+    assert_eq!(sysyfmt(&["--check", "--indent=2"], UNFORMATTED).0, 1);
+    assert_eq!(sysyfmt(&["--check"], FORMATTED).0, 1);
+}
+
+/// Formatting keeps the AST, and formatted text stays as it is.
+#[test]
+fn test_programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sysy");
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sy"))
+        .for_each(|path| {
+            let path = path.to_string_lossy();
+            let prog = fs::read_to_string(&*path).unwrap();
+            let formatted = frontend::format_sysy_text(&prog, &path, 4);
+            let ast = Program::parse_sysy_text(&prog, &path).without_spans();
+            assert!(
+                Program::parse_sysy_text(&formatted, &path).without_spans() == ast,
+                "{path} changes in formatting, formatted as:\n{formatted}"
+            );
+            assert_eq!(frontend::format_sysy_text(&formatted, &path, 4), formatted, "{path}");
+        });
+}
@@ -0,0 +1,77 @@
+//! Tests of type checking in the semantic analyzer, run on the compiler binary.
+
+mod common;
+
+use common::{compile, write_files};
+
+#[test]
+fn array_arguments() {
+    // Whole and partially indexed arrays decay to pointers to their elements.
+    let prog = "int f(int p[][4]) {
+  return p[1][2];
+}
+int g(int p[]) {
+  return p[2];
+}
+int main() {
+  int a[3][4];
+  return f(a) + g(a[1]);
+}
+";
+    let input = &write_files("array-args", &[("x.c", prog)])[0];
+    let koopa = compile("-koopa", input).unwrap();
+    assert!(koopa.contains("fun @f(@p_0_f: *[i32, 4]): i32 {\n"));
+    assert!(koopa.contains("fun @g(@p_1_f: *i32): i32 {\n"));
+    assert!(koopa.contains("    %0 = getelemptr @a_0, 0\n    %1 = call @f(%0)\n"));
+    assert!(koopa.contains("    %ptr_0 = getelemptr @a_0, 1\n    %2 = getelemptr %ptr_0, 0\n"));
+}
+
+#[test]
+fn typed_lowering() {
+    // Pointers, arrays and constants are told apart by the recorded types.
+    let prog = "int g[2][3];
+int h(int p[]) {
+  return p[0];
+}
+int f(int p[][3]) {
+  const int n = 2;
+  return h(p[1]) + h(p[n]) + p[n - 1][n] + h(g[1]);
+}
+int main() {
+  return f(g);
+}
+";
+    let input = &write_files("typed-lowering", &[("x.c", prog)])[0];
+    let koopa = compile("-koopa", input).unwrap();
+    assert!(koopa.contains("    %ptr_0 = load @p_1\n    %ptr_1 = getptr %ptr_0, 1\n"));
+    assert!(koopa.contains("    %0 = getelemptr %ptr_1, 0\n    %1 = call @h(%0)\n"));
+    assert!(koopa.contains("    %ptr_3 = getptr %ptr_2, 2\n"));
+    assert!(koopa.contains("    %ptr_6 = getelemptr %ptr_5, 2\n    %6 = load %ptr_6\n"));
+    assert!(koopa.contains("    %ptr_7 = getelemptr @g, 1\n    %8 = getelemptr %ptr_7, 0\n"));
+    assert!(koopa.contains("    %0 = getelemptr @g, 0\n    %1 = call @f(%0)\n"));
+}
+
+#[test]
+fn type_errors() {
+    let cases = [
+        (
+            "int f(int p[][4]) { return 0; }\nint main() { int a[3][5]; return f(a); }\n",
+            "Incompatible argument to function f: expected `int[][4]`, found `int[3][5]`",
+        ),
+        (
+            "int main() { int a[3]; return a[1][2]; }\n",
+            "Too many subscripts for a of type `int`",
+        ),
+        (
+            "int main() { int a[3][4]; return a[1]; }\n",
+            "a of type `int[4]` used as a value",
+        ),
+        ("int main() { int a[3]; a = 1; return 0; }\n", "Assignment to a of type `int[3]`"),
+        ("int main() { return main + 1; }\n", "Function main used as a variable"),
+    ];
+    cases.iter().enumerate().for_each(|(i, (prog, msg))| {
+        let input = &write_files(&format!("type-error-{i}"), &[("x.c", prog)])[0];
+        let err = compile("-koopa", input).unwrap_err();
+        assert!(err.ends_with(msg), "{err}");
+    });
+}